@@ -98,15 +98,22 @@ pub fn try_broadcast_daily_leverage_reference(
 
 pub fn try_create_new_pool(
     deps: DepsMut,
-    pool_instantiate_msg: PoolInstantiatMsg,
+    mut pool_instantiate_msg: Box<PoolInstantiatMsg>,
 ) -> Result<Response, ContractError> {
     // TODO: Create new pool and pass contract id to leveraged_pool_addrs
     let state = STATE.load(deps.storage)?;
 
+    // Pools would otherwise be governed by the factory itself, which cannot
+    // adjust them, so they default to the factory owner
+    let governance = pool_instantiate_msg
+        .governance
+        .get_or_insert_with(|| state.owner.to_string())
+        .clone();
+
     Ok(Response::new().add_submessage(SubMsg {
-        // create asset token
+        // create asset token, upgradable by the pool's governance
         msg: WasmMsg::Instantiate {
-            admin: None,
+            admin: Some(governance),
             code_id: state.leveraged_pool_code_id,
             funds: vec![],
             label: "".to_string(),
@@ -179,7 +186,7 @@ mod tests {
     use super::*;
     use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
     use cosmwasm_std::Addr;
-    use cosmwasm_std::{coins, from_binary};
+    use cosmwasm_std::{coins, from_binary, from_slice, CosmosMsg};
 
     #[test]
     fn proper_initialization() {
@@ -229,4 +236,52 @@ mod tests {
         let value: LastResetResponse = from_binary(&res).unwrap();
         assert_eq!(mock_env().block.time.seconds(), value.timestamp);
     }
+
+    #[test]
+    fn created_pool_admin() {
+        let mut deps = mock_dependencies(&[]);
+        let msg = InstantiateMsg {
+            leveraged_pool_code_id: 10,
+        };
+        let info = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // Pools may be migrated by their governance, the owner by default
+        for (governance, admin) in
+            [(None, "creator"), (Some("governance"), "governance")]
+        {
+            let mut pool_msg: PoolInstantiatMsg = from_slice(
+                br#"{
+                    "leverage_amount": "2000000",
+                    "minimum_protocol_ratio": "2500000",
+                    "rebalance_ratio": "2000000",
+                    "mint_premium": "500000",
+                    "rebalance_premium": "10000000",
+                    "terraswap_pair_addr": "mTSLA-UST",
+                    "leveraged_asset_addr": "mTSLA",
+                    "token_code_id": 7
+                }"#,
+            )
+            .unwrap();
+            pool_msg.governance = governance.map(String::from);
+
+            let msg = ExecuteMsg::CreateNewPool {
+                pool_instantiate_msg: Box::new(pool_msg),
+            };
+            let info = mock_info("anyone", &[]);
+            let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+            match &res.messages[0].msg {
+                CosmosMsg::Wasm(WasmMsg::Instantiate {
+                    admin: pool_admin,
+                    msg,
+                    ..
+                }) => {
+                    assert_eq!(pool_admin.as_deref(), Some(admin));
+                    let pool_msg: PoolInstantiatMsg = from_binary(msg).unwrap();
+                    assert_eq!(pool_msg.governance.as_deref(), Some(admin));
+                }
+                _ => panic!("Factory did not instantiate the pool"),
+            }
+        }
+    }
 }
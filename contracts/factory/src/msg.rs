@@ -12,7 +12,7 @@ pub struct InstantiateMsg {
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
     CreateNewPool {
-        pool_instantiate_msg: PoolInstantiatMsg,
+        pool_instantiate_msg: Box<PoolInstantiatMsg>,
     },
     BroadcastLeverageUpdate {},
 }
@@ -4,6 +4,10 @@
 // https://github.com/rust-lang/rust-clippy/issues/702
 #![allow(unknown_lints)]
 #![allow(clippy::all)]
+// Lints newer than rust-protobuf 2.23.0
+#![allow(renamed_and_removed_lints)]
+#![allow(unused_parens)]
+#![allow(mismatched_lifetime_syntaxes)]
 
 #![allow(unused_attributes)]
 #![cfg_attr(rustfmt, rustfmt::skip)]
@@ -11,11 +11,18 @@ fn main() {
 
     export_schema(&schema_for!(InstantiateMsg), &out_dir);
     export_schema(&schema_for!(ExecuteMsg), &out_dir);
+    export_schema(&schema_for!(MigrateMsg), &out_dir);
     export_schema(&schema_for!(QueryMsg), &out_dir);
     export_schema(&schema_for!(PriceSnapshot), &out_dir);
     export_schema(&schema_for!(LiquidityResponse), &out_dir);
     export_schema(&schema_for!(PriceHistoryResponse), &out_dir);
+    export_schema(&schema_for!(HyperparameterChangesResponse), &out_dir);
     export_schema(&schema_for!(HyperparametersResponse), &out_dir);
     export_schema(&schema_for!(PoolStateResponse), &out_dir);
     export_schema(&schema_for!(AllPoolInfoResponse), &out_dir);
+    export_schema(&schema_for!(FeesCollectedResponse), &out_dir);
+    export_schema(&schema_for!(SettlementResponse), &out_dir);
+    export_schema(&schema_for!(TwapResponse), &out_dir);
+    export_schema(&schema_for!(CandlesResponse), &out_dir);
+    export_schema(&schema_for!(SimulationResponse), &out_dir);
 }
@@ -1,18 +1,31 @@
 use crate::error::ContractError;
+use crate::leverage_man::Settlement;
 use crate::{leverage_man, liquid_man, mint_man};
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
     from_binary, to_binary, Addr, Binary, Deps, DepsMut, Env, MessageInfo,
-    Response, StdError, StdResult, Uint128,
+    QuerierWrapper, Reply, Response, StdError, StdResult, Uint128,
 };
+use cw0::must_pay;
+use cw2::{get_contract_version, set_contract_version};
 use cw20::Cw20ReceiveMsg;
 use leveraged_pools::pool::{
-    AllPoolInfoResponse, Cw20HookMsg, ExecuteMsg, HyperparametersResponse,
-    InstantiateMsg, LeveragedPositionResponse, LiquidityPositionResponse,
-    PoolStateResponse, PriceHistoryResponse, ProtocolRatioResponse,
-    ProvideLiquidityMsg, QueryMsg, TryBurn, TryMint,
+    AllPoolInfoResponse, CandleRange, CandleResolution, CandlesResponse,
+    Cw20HookMsg, ExecuteMsg, FeesCollectedResponse,
+    HyperparameterChangesResponse, HyperparametersResponse,
+    HyperparametersUpdate, InstantiateMsg, LeveragedPositionResponse,
+    LiquidityPositionResponse, MigrateMsg, OrderBy, PoolStateResponse,
+    PoolStatus, PriceHistoryResponse, ProtocolRatioResponse,
+    ProvideLiquidityMsg, QueryMsg, ReceiveAs, SettlementResponse,
+    SimulationResponse, SlippageLimits, TryBurn, TryMint, TwapResponse,
+    WithdrawLiquidityMsg,
 };
+use terraswap::asset::{Asset, AssetInfo};
+
+/* Version info for migration */
+const CONTRACT_NAME: &str = "crates.io:leveraged_pool";
+const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 /**
  * Instantiation entrypoint
@@ -21,15 +34,35 @@ use leveraged_pools::pool::{
 pub fn instantiate(
     deps: DepsMut,
     env: Env,
-    _info: MessageInfo,
+    info: MessageInfo,
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
     /* TODO do mint_man and liquid_man really need to be init'd? */
     for init in [leverage_man::init] {
-        init(&env, deps.storage, deps.api, deps.querier, &msg)?;
+        init(
+            &env,
+            deps.storage,
+            deps.api,
+            deps.querier,
+            &info.sender,
+            &msg,
+        )?;
     }
 
-    Ok(Response::new().add_attribute("method", "instantiate"))
+    Ok(Response::new()
+        .add_submessage(mint_man::instantiate_leveraged_token(
+            &env,
+            msg.token_code_id,
+            vec![],
+        )?)
+        .add_submessage(liquid_man::instantiate_liquidity_token(
+            &env,
+            msg.token_code_id,
+            vec![],
+        )?)
+        .add_attribute("method", "instantiate"))
 }
 
 /**
@@ -41,18 +74,198 @@ pub fn execute(
     env: Env,
     info: MessageInfo,
     msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    /* Neither paused nor liquidated pools query any prices. A liquidated
+     * pool only pays out its settlement */
+    match leverage_man::get_pool_status(deps.storage)? {
+        PoolStatus::Settled => {
+            return match msg {
+                ExecuteMsg::Receive(msg) => {
+                    receive_settlement_claim(deps, info, msg)
+                }
+                ExecuteMsg::SetDailyLeverageReference {} => {
+                    Ok(skip_daily_leverage_reference(PoolStatus::Settled))
+                }
+                _ => Err(ContractError::PoolLiquidating {}),
+            };
+        }
+        PoolStatus::Paused => {
+            return match msg {
+                msg if is_admin_msg(&msg) => dispatch(deps, env, info, msg),
+                ExecuteMsg::SetDailyLeverageReference {} => {
+                    Ok(skip_daily_leverage_reference(PoolStatus::Paused))
+                }
+                _ => Err(ContractError::PoolPaused {}),
+            };
+        }
+        PoolStatus::Active | PoolStatus::MintPaused => {}
+    }
+
+    /* Fail a poke with nothing due before querying any prices */
+    if matches!(msg, ExecuteMsg::Poke {})
+        && !leverage_man::poke_is_due(deps.storage, &env)?
+    {
+        return Err(ContractError::NothingToPoke {});
+    }
+
+    /* Governance does not depend on the price source working */
+    if !is_admin_msg(&msg) {
+        /* Pause on a price move too large to trust before anything is
+         * priced, handing back whatever came with the message */
+        if let Some(event) = leverage_man::check_circuit_breaker(
+            deps.storage,
+            deps.api,
+            deps.querier,
+            &env,
+        )? {
+            return Ok(
+                refund_deposit(&deps.querier, &info, &msg)?.add_event(event)
+            );
+        }
+
+        /* Pay out a knock-out before anything is priced. Minting and
+         * burning stop until the knocked out positions have claimed, so the
+         * message is handed back rather than failing the knock-out with it */
+        if let Some(event) = leverage_man::check_knock_out(
+            deps.storage,
+            deps.api,
+            deps.querier,
+            &env,
+        )? {
+            if !leverage_man::knocked_out_share(deps.storage)?.is_zero() {
+                return Ok(refund_deposit(&deps.querier, &info, &msg)?
+                    .add_event(event));
+            }
+            return Ok(dispatch(deps, env, info, msg)?.add_event(event));
+        }
+    }
+
+    dispatch(deps, env, info, msg)
+}
+
+/**
+ * Whether `msg` only adjusts the pool's parameters or status
+ */
+fn is_admin_msg(msg: &ExecuteMsg) -> bool {
+    matches!(
+        msg,
+        ExecuteMsg::SetStatus { .. } | ExecuteMsg::UpdateHyperparameters { .. }
+    )
+}
+
+/**
+ * Route an execute message to its handler
+ */
+fn dispatch(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
     match msg {
         ExecuteMsg::Receive(msg) => receive_cw20(deps, env, info, msg),
-        ExecuteMsg::WithdrawLiquidity { share_of_pool } => {
-            execute_withdraw_liquidity(deps, info, env, share_of_pool)
+        ExecuteMsg::SetDailyLeverageReference {} => {
+            execute_set_daily_leverage_reference(deps, info, env)
         }
-        ExecuteMsg::BurnLeveragedAsset { share_of_pool } => {
-            execute_burn_leveraged(deps, info, env, share_of_pool)
+        ExecuteMsg::ProvideLiquidity {} => {
+            let amount = native_deposit(&deps.as_ref(), &info)?;
+            let provide_liquidity_msg = ProvideLiquidityMsg {
+                sender: info.sender.clone(),
+                amount,
+            };
+            execute_provide_liquidity(deps, info, env, provide_liquidity_msg)
+        }
+        ExecuteMsg::MintLeveragedPosition {
+            min_received,
+            max_leveraged_price,
+            deadline,
+        } => {
+            check_mints_open(&deps.as_ref())?;
+            let amount = native_deposit(&deps.as_ref(), &info)?;
+            let limits = SlippageLimits {
+                min_received,
+                max_leveraged_price,
+                deadline,
+            };
+            execute_mint_leveraged(deps, &env, info.sender, amount, limits)
+        }
+        ExecuteMsg::ZapIn {
+            max_spread,
+            min_received,
+            max_leveraged_price,
+            deadline,
+        } => {
+            check_mints_open(&deps.as_ref())?;
+            let limits = SlippageLimits {
+                min_received,
+                max_leveraged_price,
+                deadline,
+            };
+            mint_man::execute_zap_in(deps, &info, &env, max_spread, limits)
+        }
+        ExecuteMsg::FundKeeperBudget {} => {
+            let amount = native_deposit(&deps.as_ref(), &info)?;
+            execute_fund_keeper_budget(deps, info.sender.as_str(), amount)
+        }
+        ExecuteMsg::Rebalance {} => execute_rebalance(deps, info, env),
+        ExecuteMsg::Poke {} => execute_poke(deps, info, env),
+        ExecuteMsg::Liquidate {} => execute_liquidate(deps, info, env),
+        ExecuteMsg::SetStatus { status } => {
+            execute_set_status(deps, info, env, status)
         }
+        ExecuteMsg::UpdateHyperparameters {
+            minimum_protocol_ratio,
+            rebalance_ratio,
+            mint_premium,
+            rebalance_premium,
+        } => execute_update_hyperparameters(
+            deps,
+            env,
+            HyperparametersUpdate {
+                sender: info.sender,
+                minimum_protocol_ratio,
+                rebalance_ratio,
+                mint_premium,
+                rebalance_premium,
+            },
+        ),
+    }
+}
 
-        _ => Err(ContractError::InvalidPoolParams {}),
+/**
+ * Return the Cw20 or native coins sent along with a message the pool did not
+ * act on
+ */
+fn refund_deposit(
+    querier: &QuerierWrapper,
+    info: &MessageInfo,
+    msg: &ExecuteMsg,
+) -> StdResult<Response> {
+    let mut refunds = vec![];
+    if let ExecuteMsg::Receive(cw20_msg) = msg {
+        refunds.push(
+            Asset {
+                info: AssetInfo::Token {
+                    contract_addr: info.sender.to_string(),
+                },
+                amount: cw20_msg.amount,
+            }
+            .into_msg(querier, Addr::unchecked(&cw20_msg.sender))?,
+        );
+    }
+    for coin in &info.funds {
+        refunds.push(
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: coin.denom.clone(),
+                },
+                amount: coin.amount,
+            }
+            .into_msg(querier, info.sender.clone())?,
+        );
     }
+
+    Ok(Response::new().add_messages(refunds))
 }
 
 /**
@@ -64,13 +277,13 @@ pub fn receive_cw20(
     info: MessageInfo,
     cw20_msg: Cw20ReceiveMsg,
 ) -> Result<Response, ContractError> {
-    /* Ignore any Cw20s except the backing asset */
-    if !is_pooled_asset(&deps.as_ref(), &info.sender)? {
-        return Err(ContractError::WrongAssetLOL {});
-    }
-
     match from_binary(&cw20_msg.msg) {
         Ok(Cw20HookMsg::ProvideLiquidity {}) => {
+            /* Ignore any Cw20s except the backing asset */
+            if !is_pooled_asset(&deps.as_ref(), &info.sender)? {
+                return Err(ContractError::WrongAssetLOL {});
+            }
+
             // only asset contract can execute this message
             let cw20_sender_addr = deps.api.addr_validate(&cw20_msg.sender)?;
 
@@ -78,80 +291,416 @@ pub fn receive_cw20(
                 sender: cw20_sender_addr,
                 amount: cw20_msg.amount,
             };
-            let _ = liquid_man::try_execute_provide_liquidity(
+            execute_provide_liquidity(deps, info, env, provide_liquidity_msg)
+        }
+        Ok(Cw20HookMsg::MintLeveragedPosition {
+            min_received,
+            max_leveraged_price,
+            deadline,
+        }) => {
+            /* Ignore any Cw20s except the backing asset */
+            if !is_pooled_asset(&deps.as_ref(), &info.sender)? {
+                return Err(ContractError::WrongAssetLOL {});
+            }
+            check_mints_open(&deps.as_ref())?;
+
+            let limits = SlippageLimits {
+                min_received,
+                max_leveraged_price,
+                deadline,
+            };
+            let sender = deps.api.addr_validate(&cw20_msg.sender)?;
+            execute_mint_leveraged(deps, &env, sender, cw20_msg.amount, limits)
+        }
+        Ok(Cw20HookMsg::BurnLeveragedPosition {
+            min_received,
+            deadline,
+            receive_as,
+        }) => {
+            /* Only leveraged positions can be burned */
+            if !is_leveraged_token(&deps.as_ref(), &info.sender)? {
+                return Err(ContractError::WrongAssetLOL {});
+            }
+
+            let limits = SlippageLimits {
+                min_received,
+                max_leveraged_price: None,
+                deadline,
+            };
+            execute_burn_leveraged(
                 deps,
                 info,
-                &env,
-                provide_liquidity_msg,
-            );
+                env,
+                &cw20_msg,
+                limits,
+                receive_as.unwrap_or_default(),
+            )
+        }
+        Ok(Cw20HookMsg::WithdrawLiquidity {
+            min_received,
+            max_leveraged_price,
+            deadline,
+            receive_as,
+        }) => {
+            /* Only liquidity positions can be withdrawn */
+            if !is_liquidity_token(&deps.as_ref(), &info.sender)? {
+                return Err(ContractError::WrongAssetLOL {});
+            }
+
+            let withdraw_liquidity_msg = WithdrawLiquidityMsg {
+                sender: deps.api.addr_validate(&cw20_msg.sender)?,
+                share_of_pool: cw20_msg.amount,
+                limits: SlippageLimits {
+                    min_received,
+                    max_leveraged_price,
+                    deadline,
+                },
+                receive_as: receive_as.unwrap_or_default(),
+            };
+
+            execute_withdraw_liquidity(deps, info, env, withdraw_liquidity_msg)
+        }
+        Ok(Cw20HookMsg::FundKeeperBudget {}) => {
+            /* Keepers are paid in the backing asset */
+            if !is_pooled_asset(&deps.as_ref(), &info.sender)? {
+                return Err(ContractError::WrongAssetLOL {});
+            }
 
-            return Ok(Response::new());
+            execute_fund_keeper_budget(deps, &cw20_msg.sender, cw20_msg.amount)
         }
-        Ok(Cw20HookMsg::MintLeveragedPosition {}) => {
-            execute_mint_leveraged(deps, info, &env, &cw20_msg)?;
-            return Ok(Response::new());
+        Ok(Cw20HookMsg::ClaimSettlement {}) => {
+            /* Only knocked out positions claim from a pool still open */
+            if !is_leveraged_token(&deps.as_ref(), &info.sender)? {
+                return Err(ContractError::NotLiquidating {});
+            }
+
+            let sender = deps.api.addr_validate(&cw20_msg.sender)?;
+            mint_man::execute_claim_settlement(deps, &sender, cw20_msg.amount)
         }
         Err(err) => Err(ContractError::Std(err)),
     }
 }
 
 /**
- * ExecuteMsg::BurnLeveragedAsset
+ * Unpack Cw20 messages sent to a liquidated pool, of which only settlement
+ * claims are accepted
+ */
+fn receive_settlement_claim(
+    deps: DepsMut,
+    info: MessageInfo,
+    cw20_msg: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    match from_binary(&cw20_msg.msg)? {
+        Cw20HookMsg::ClaimSettlement {} => {
+            let sender = deps.api.addr_validate(&cw20_msg.sender)?;
+            if is_leveraged_token(&deps.as_ref(), &info.sender)? {
+                mint_man::execute_claim_settlement(
+                    deps,
+                    &sender,
+                    cw20_msg.amount,
+                )
+            } else if is_liquidity_token(&deps.as_ref(), &info.sender)? {
+                liquid_man::execute_claim_settlement(
+                    deps,
+                    &sender,
+                    cw20_msg.amount,
+                )
+            } else {
+                Err(ContractError::WrongAssetLOL {})
+            }
+        }
+        _ => Err(ContractError::PoolLiquidating {}),
+    }
+}
+
+/**
+ * Cw20HookMsg::BurnLeveragedPosition
  */
 pub fn execute_burn_leveraged(
     deps: DepsMut,
     info: MessageInfo,
     env: Env,
-    pool_share: Uint128,
+    msg: &Cw20ReceiveMsg,
+    limits: SlippageLimits,
+    receive_as: ReceiveAs,
 ) -> Result<Response, ContractError> {
+    let sender = deps.api.addr_validate(&msg.sender)?;
+    let amount = msg.amount;
+
     mint_man::execute_burn_leveraged(
         deps,
         &info,
         &env,
         &TryBurn {
-            sender: info.sender.clone(),
-            pool_share,
+            sender,
+            amount,
+            limits,
+            receive_as,
         },
     )
 }
 
-fn execute_mint_leveraged(
+/**
+ * ExecuteMsg::Rebalance
+ */
+pub fn execute_rebalance(
     deps: DepsMut,
     info: MessageInfo,
-    env: &Env,
-    msg: &Cw20ReceiveMsg,
+    env: Env,
 ) -> Result<Response, ContractError> {
-    let sender = deps.api.addr_validate(&msg.sender)?;
-    let amount = msg.amount;
+    mint_man::execute_rebalance(deps, &info, &env)
+}
 
-    mint_man::execute_mint_leveraged(
-        deps,
-        &info,
+/**
+ * ExecuteMsg::SetDailyLeverageReference
+ */
+pub fn execute_set_daily_leverage_reference(
+    deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+) -> Result<Response, ContractError> {
+    let reference = leverage_man::set_daily_leverage_reference(
+        deps.storage,
+        deps.api,
+        deps.querier,
+        &env,
+        &info.sender,
+    )?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_daily_leverage_reference")
+        .add_attribute("asset_price", reference.asset_price)
+        .add_attribute("leveraged_price", reference.leveraged_price))
+}
+
+/**
+ * ExecuteMsg::SetDailyLeverageReference sent to a paused or liquidated pool.
+ * The factory broadcasts resets to every pool at once, so rather than failing
+ * the whole broadcast the reset is ignored
+ */
+fn skip_daily_leverage_reference(status: PoolStatus) -> Response {
+    Response::new()
+        .add_attribute("action", "set_daily_leverage_reference")
+        .add_attribute("skipped", status.as_str())
+}
+
+/**
+ * ExecuteMsg::Poke
+ */
+pub fn execute_poke(
+    deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+) -> Result<Response, ContractError> {
+    let poked = leverage_man::check_reset_leverage(
+        deps.storage,
+        deps.api,
+        deps.querier,
+        &env,
+    )?;
+    if !poked {
+        return Err(ContractError::NothingToPoke {});
+    }
+
+    let reward = leverage_man::pay_keeper(deps.storage)?;
+    let mut res = Response::new()
+        .add_attribute("action", "poke")
+        .add_attribute("keeper", info.sender.as_str())
+        .add_attribute("reward", reward);
+    if !reward.is_zero() {
+        res = res.add_message(leverage_man::transfer_backing_asset(
+            deps.storage,
+            deps.api,
+            &deps.querier,
+            &info.sender,
+            reward,
+        )?);
+    }
+
+    Ok(res)
+}
+
+/**
+ * Cw20HookMsg::FundKeeperBudget and ExecuteMsg::FundKeeperBudget
+ */
+fn execute_fund_keeper_budget(
+    deps: DepsMut,
+    funder: &str,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let budget = leverage_man::fund_keeper_budget(deps.storage, amount)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "fund_keeper_budget")
+        .add_attribute("funder", funder)
+        .add_attribute("amount", amount)
+        .add_attribute("keeper_budget", budget))
+}
+
+/**
+ * ExecuteMsg::Liquidate
+ */
+pub fn execute_liquidate(
+    deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+) -> Result<Response, ContractError> {
+    let settlement =
+        leverage_man::liquidate(deps.storage, deps.api, deps.querier, &env)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "liquidate")
+        .add_attribute("caller", info.sender)
+        .add_attributes(settlement_attributes(&settlement)))
+}
+
+/**
+ * ExecuteMsg::SetStatus
+ */
+pub fn execute_set_status(
+    deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+    status: PoolStatus,
+) -> Result<Response, ContractError> {
+    let settlement = leverage_man::set_pool_status(
+        deps.storage,
+        deps.api,
+        deps.querier,
+        &env,
+        &info.sender,
+        status,
+    )?;
+
+    let res = Response::new()
+        .add_attribute("action", "set_status")
+        .add_attribute("caller", info.sender)
+        .add_attribute("status", status.as_str());
+    Ok(match settlement {
+        Some(settlement) => {
+            res.add_attributes(settlement_attributes(&settlement))
+        }
+        None => res,
+    })
+}
+
+fn settlement_attributes(settlement: &Settlement) -> Vec<(&str, String)> {
+    vec![
+        ("asset_price", settlement.settled_at.asset_price.to_string()),
+        (
+            "leveraged_price",
+            settlement.settled_at.leveraged_price.to_string(),
+        ),
+        ("leveraged_assets", settlement.leveraged_assets.to_string()),
+        ("liquidity_assets", settlement.liquidity_assets.to_string()),
+    ]
+}
+
+/**
+ * ExecuteMsg::UpdateHyperparameters
+ */
+pub fn execute_update_hyperparameters(
+    deps: DepsMut,
+    env: Env,
+    update: HyperparametersUpdate,
+) -> Result<Response, ContractError> {
+    let changes = leverage_man::update_hyperparameters(
+        deps.storage,
+        deps.api,
         &env,
-        &TryMint { sender, amount },
+        &update,
     )?;
 
-    Ok(Response::new())
+    let mut res = Response::new()
+        .add_attribute("action", "update_hyperparameters")
+        .add_attribute("governance", update.sender.as_str());
+    for change in changes {
+        res = res.add_attribute(change.parameter, change.updated.to_string());
+    }
+
+    Ok(res)
+}
+
+/**
+ * Cw20HookMsg::MintLeveragedPosition and ExecuteMsg::MintLeveragedPosition
+ */
+fn execute_mint_leveraged(
+    deps: DepsMut,
+    env: &Env,
+    sender: Addr,
+    amount: Uint128,
+    limits: SlippageLimits,
+) -> Result<Response, ContractError> {
+    mint_man::execute_mint_leveraged(
+        deps,
+        env,
+        &TryMint {
+            sender,
+            amount,
+            limits,
+        },
+    )
 }
 
 fn is_pooled_asset(deps: &Deps, addr: &Addr) -> StdResult<bool> {
-    Ok(leverage_man::get_asset_addr(&deps)? == *addr)
+    Ok(match leverage_man::get_backing_asset(deps)? {
+        AssetInfo::Token { contract_addr } => contract_addr == *addr,
+        AssetInfo::NativeToken { .. } => false,
+    })
+}
+
+/**
+ * Amount of the pool's native coin sent along with `info`, which must be the
+ * only coin sent
+ */
+fn native_deposit(
+    deps: &Deps,
+    info: &MessageInfo,
+) -> Result<Uint128, ContractError> {
+    match leverage_man::get_backing_asset(deps)? {
+        AssetInfo::NativeToken { denom } => Ok(must_pay(info, &denom)?),
+        AssetInfo::Token { .. } => Err(ContractError::WrongAssetLOL {}),
+    }
+}
+
+/**
+ * Fail mints unless the pool is fully active and no knocked out positions
+ * are left to claim their payout
+ */
+fn check_mints_open(deps: &Deps) -> Result<(), ContractError> {
+    match leverage_man::get_pool_status(deps.storage)? {
+        PoolStatus::Active => {}
+        _ => return Err(ContractError::MintsPaused {}),
+    }
+    if !leverage_man::knocked_out_share(deps.storage)?.is_zero() {
+        return Err(ContractError::KnockOutUnclaimed {});
+    }
+
+    Ok(())
+}
+
+fn is_leveraged_token(deps: &Deps, addr: &Addr) -> StdResult<bool> {
+    Ok(leverage_man::get_leveraged_token_addr(deps)? == *addr)
+}
+
+fn is_liquidity_token(deps: &Deps, addr: &Addr) -> StdResult<bool> {
+    Ok(leverage_man::get_liquidity_token_addr(deps)? == *addr)
 }
 
 /**
- * ExecuteMsg::WithdrawLiquidity
+ * Cw20HookMsg::WithdrawLiquidity
  */
 pub fn execute_withdraw_liquidity(
     deps: DepsMut,
     info: MessageInfo,
     env: Env,
-    share_of_pool: Uint128,
+    msg: WithdrawLiquidityMsg,
 ) -> Result<Response, ContractError> {
-    liquid_man::execute_withdraw_liquidity(deps, info, &env, share_of_pool)
+    liquid_man::execute_withdraw_liquidity(deps, info, &env, msg)
 }
 
 /**
- * ExecuteMsg::ProvideLiquidity
+ * Cw20HookMsg::ProvideLiquidity
  */
 pub fn execute_provide_liquidity(
     deps: DepsMut,
@@ -163,7 +712,8 @@ pub fn execute_provide_liquidity(
 }
 
 /**
- * Expose immutable hyperparameters configured at init time
+ * Expose hyperparameters configured at init time along with every
+ * adjustment governance has made since
  * QueryMsg::HyperParameters
  */
 fn query_hyperparameters(deps: Deps) -> StdResult<HyperparametersResponse> {
@@ -172,6 +722,7 @@ fn query_hyperparameters(deps: Deps) -> StdResult<HyperparametersResponse> {
     /* This never fails */
     Ok(HyperparametersResponse {
         leverage_amount: hyper_p.leverage_amount,
+        direction: hyper_p.direction,
         minimum_protocol_ratio: hyper_p.minimum_protocol_ratio,
         rebalance_ratio: hyper_p.rebalance_ratio,
         mint_premium: hyper_p.mint_premium,
@@ -180,19 +731,83 @@ fn query_hyperparameters(deps: Deps) -> StdResult<HyperparametersResponse> {
             .api
             .addr_humanize(&hyper_p.terraswap_pair_addr)?
             .to_string(),
-        leveraged_asset_addr: deps
-            .api
-            .addr_humanize(&hyper_p.leveraged_asset_addr)?
-            .to_string(),
+        leveraged_asset_addr: hyper_p
+            .leveraged_asset_addr
+            .as_ref()
+            .map(|addr| deps.api.addr_humanize(addr))
+            .transpose()?
+            .map(|addr| addr.to_string()),
+        leveraged_asset_denom: hyper_p.leveraged_asset_denom.clone(),
+        leveraged_token_addr: leverage_man::get_leveraged_token_addr(&deps)
+            .ok()
+            .map(|addr| addr.to_string()),
+        liquidity_token_addr: leverage_man::get_liquidity_token_addr(&deps)
+            .ok()
+            .map(|addr| addr.to_string()),
+        governance_addr: leverage_man::get_governance_addr(&deps)?.to_string(),
+        guardian_addr: leverage_man::get_guardian_addr(&deps)?
+            .map(|addr| addr.to_string()),
+        price_source: leverage_man::query_price_source_config(deps.storage),
+        twap_window: hyper_p.twap_window,
+        knock_out_floor: hyper_p.knock_out_floor,
+        price_data_expiry: hyper_p.price_data_expiry,
+        leverage_expiry: hyper_p.leverage_expiry,
+        leverage_reset_offset: hyper_p.leverage_reset_offset,
+        price_data_n: hyper_p.price_data_n,
+        keeper_reward: hyper_p.keeper_reward,
+        liquidation_ratio: hyper_p.liquidation_ratio,
+        circuit_breaker: hyper_p.circuit_breaker,
+        max_total_leveraged_value: hyper_p.max_total_leveraged_value,
+        max_assets_in_reserve: hyper_p.max_assets_in_reserve,
+        max_position_per_address: hyper_p.max_position_per_address,
+    })
+}
+
+/**
+ * QueryMsg::HyperparameterChanges
+ */
+fn query_hyperparameter_changes(
+    deps: Deps,
+    start_after: Option<u64>,
+    end_before: Option<u64>,
+    limit: Option<u32>,
+    order: Option<OrderBy>,
+) -> StdResult<HyperparameterChangesResponse> {
+    let (changes, next_cursor) = leverage_man::query_hyperparameter_changes(
+        &deps,
+        start_after,
+        end_before,
+        limit,
+        order.unwrap_or(OrderBy::Ascending),
+    )?;
+
+    Ok(HyperparameterChangesResponse {
+        changes,
+        next_cursor,
     })
 }
 
 /**
  * QueryMsg::PriceHistory
  */
-fn query_price_history(deps: Deps) -> StdResult<PriceHistoryResponse> {
+fn query_price_history(
+    deps: Deps,
+    start_after: Option<u64>,
+    end_before: Option<u64>,
+    limit: Option<u32>,
+    order: Option<OrderBy>,
+) -> StdResult<PriceHistoryResponse> {
+    let (price_history, next_cursor) = leverage_man::query_price_history(
+        &deps,
+        start_after,
+        end_before,
+        limit,
+        order.unwrap_or(OrderBy::Ascending),
+    )?;
+
     Ok(PriceHistoryResponse {
-        price_history: leverage_man::query_price_history(&deps),
+        price_history,
+        next_cursor,
     })
 }
 
@@ -232,6 +847,150 @@ fn query_pool_state(deps: Deps) -> StdResult<PoolStateResponse> {
         total_leveraged_assets: pool_state.total_leveraged_assets,
         total_asset_pool_share: pool_state.total_asset_pool_share,
         total_leveraged_pool_share: pool_state.total_leveraged_pool_share,
+        keeper_budget: pool_state.keeper_budget,
+        status: pool_state.status,
+    })
+}
+
+/**
+ * QueryMsg::FeesCollected
+ */
+fn query_fees_collected(deps: Deps) -> StdResult<FeesCollectedResponse> {
+    let fees = leverage_man::query_fees_collected(&deps)?;
+
+    Ok(FeesCollectedResponse {
+        mint_premium: fees.mint_premium,
+    })
+}
+
+/**
+ * QueryMsg::Settlement
+ */
+fn query_settlement(deps: Deps) -> StdResult<SettlementResponse> {
+    let settlement = leverage_man::query_settlement(&deps)?;
+
+    Ok(SettlementResponse {
+        settled_at: settlement.settled_at,
+        leveraged_assets: settlement.leveraged_assets,
+        leveraged_pool_share: settlement.leveraged_pool_share,
+        liquidity_assets: settlement.liquidity_assets,
+        asset_pool_share: settlement.asset_pool_share,
+    })
+}
+
+/**
+ * QueryMsg::Twap
+ */
+fn query_twap(
+    deps: Deps,
+    env: &Env,
+    window_seconds: u64,
+) -> StdResult<TwapResponse> {
+    if window_seconds == 0 {
+        return Err(StdError::generic_err("TWAP window must not be empty"));
+    }
+
+    Ok(TwapResponse {
+        window_seconds,
+        twap: leverage_man::get_twap_snapshot(
+            deps.storage,
+            deps.api,
+            deps.querier,
+            env,
+            window_seconds,
+        )?,
+    })
+}
+
+/**
+ * QueryMsg::Candles
+ */
+fn query_candles(
+    deps: Deps,
+    resolution: CandleResolution,
+    range: CandleRange,
+    limit: Option<u32>,
+) -> StdResult<CandlesResponse> {
+    let (candles, next_cursor) =
+        leverage_man::query_candles(&deps, resolution, &range, limit)?;
+
+    Ok(CandlesResponse {
+        resolution,
+        candles,
+        next_cursor,
+    })
+}
+
+/**
+ * QueryMsg::SimulateMint
+ */
+fn query_simulate_mint(
+    deps: Deps,
+    env: &Env,
+    amount: Uint128,
+) -> StdResult<SimulationResponse> {
+    let quote = mint_man::quote_mint(&deps, env, amount)
+        .map_err(|e| StdError::generic_err(e.to_string()))?;
+
+    Ok(SimulationResponse {
+        output_amount: quote.minted_share,
+        fee_amount: quote.mint_premium,
+        protocol_ratio: Some(quote.protocol_ratio),
+        passes_health_check: quote.is_healthy,
+    })
+}
+
+/**
+ * QueryMsg::SimulateBurn
+ */
+fn query_simulate_burn(
+    deps: Deps,
+    env: &Env,
+    address: Addr,
+    share: Uint128,
+) -> StdResult<SimulationResponse> {
+    let position = leverage_man::get_leveraged_position(&deps, &address)?;
+    if share > position.leveraged_pool_partial_share {
+        return Err(StdError::generic_err(
+            ContractError::InsufficientFunds {}.to_string(),
+        ));
+    }
+
+    let quote = mint_man::quote_burn(&deps, env, share)
+        .map_err(|e| StdError::generic_err(e.to_string()))?;
+
+    Ok(SimulationResponse {
+        output_amount: quote.redeemed_assets,
+        fee_amount: Uint128::zero(),
+        protocol_ratio: quote.protocol_ratio,
+        passes_health_check: quote.is_healthy,
+    })
+}
+
+/**
+ * QueryMsg::SimulateWithdraw
+ */
+fn query_simulate_withdraw(
+    deps: Deps,
+    env: &Env,
+    address: Addr,
+    share: Uint128,
+) -> StdResult<SimulationResponse> {
+    let position = leverage_man::get_liquidity_position(&deps, &address)?;
+    if share > position.asset_pool_partial_share {
+        return Err(StdError::generic_err(
+            ContractError::InsufficientFunds {}.to_string(),
+        ));
+    }
+
+    let quote = liquid_man::quote_withdraw(&deps, env, share)
+        .map_err(|e| StdError::generic_err(e.to_string()))?;
+
+    Ok(SimulationResponse {
+        output_amount: quote.claimed_units,
+        fee_amount: Uint128::zero(),
+        protocol_ratio: quote.protocol_ratio,
+        passes_health_check: quote.is_healthy,
     })
 }
 
@@ -242,15 +1001,23 @@ fn query_all_pool_info(
     deps: Deps,
     env: &Env,
 ) -> StdResult<AllPoolInfoResponse> {
+    let price_context = leverage_man::get_price_context(
+        deps.storage,
+        deps.api,
+        deps.querier,
+        env,
+    )?;
+    let capacity = leverage_man::remaining_capacity(
+        &deps,
+        &price_context.current_snapshot,
+    )
+    .map_err(|e| StdError::generic_err(e.to_string()))?;
+
     Ok(AllPoolInfoResponse {
         hyperparameters: query_hyperparameters(deps)?,
         pool_state: query_pool_state(deps)?,
-        price_context: leverage_man::get_price_context(
-            deps.storage,
-            deps.api,
-            deps.querier,
-            env,
-        )?,
+        price_context,
+        capacity,
     })
 }
 
@@ -262,10 +1029,101 @@ fn query_pr(
     env: &Env,
 ) -> Result<ProtocolRatioResponse, ContractError> {
     Ok(ProtocolRatioResponse {
-        pr: leverage_man::query_pr(&deps, &env)?,
+        pr: leverage_man::query_pr(deps, env)?,
     })
 }
 
+/**
+ * Reply entrypoint
+ */
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(
+    deps: DepsMut,
+    env: Env,
+    msg: Reply,
+) -> Result<Response, ContractError> {
+    match msg.id {
+        mint_man::LEVERAGED_TOKEN_REPLY_ID => {
+            mint_man::register_leveraged_token(deps, msg)
+        }
+        liquid_man::LIQUIDITY_TOKEN_REPLY_ID => {
+            liquid_man::register_liquidity_token(deps, msg)
+        }
+        mint_man::ZAP_IN_REPLY_ID => mint_man::zap_in_reply(deps, &env),
+        mint_man::ZAP_OUT_REPLY_ID => mint_man::zap_out_reply(deps, &env),
+        _ => Err(StdError::generic_err("reply id is invalid").into()),
+    }
+}
+
+/**
+ * Migration entrypoint
+ */
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(
+    mut deps: DepsMut,
+    env: Env,
+    msg: MigrateMsg,
+) -> Result<Response, ContractError> {
+    let res = match get_contract_version(deps.storage).ok() {
+        /* Pools deployed at 0.1.0 never recorded a contract version */
+        None => migrate_from_v0_1(deps.branch(), &env, msg)?,
+        Some(stored)
+            if stored.contract == CONTRACT_NAME
+                && stored.version == CONTRACT_VERSION =>
+        {
+            Response::new()
+        }
+        Some(stored) => {
+            return Err(ContractError::UnsupportedMigration {
+                contract: stored.contract,
+                version: stored.version,
+            })
+        }
+    };
+
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(res
+        .add_attribute("method", "migrate")
+        .add_attribute("version", CONTRACT_VERSION))
+}
+
+/**
+ * Convert 0.1.0 state and issue the pool tokens for positions which were
+ * previously tracked in storage
+ */
+fn migrate_from_v0_1(
+    deps: DepsMut,
+    env: &Env,
+    msg: MigrateMsg,
+) -> Result<Response, ContractError> {
+    let token_code_id = msg
+        .token_code_id
+        .ok_or(ContractError::InvalidPoolParams {})?;
+    let governance_addr = deps
+        .api
+        .addr_canonicalize(
+            &msg.governance.ok_or(ContractError::InvalidPoolParams {})?,
+        )
+        .map_err(|_| ContractError::InvalidAddr {})?;
+
+    leverage_man::migrate_pool_state_v0_1(deps.storage, governance_addr)?;
+    leverage_man::migrate_price_data_v0_1(deps.storage)?;
+    let (minted, provided) = leverage_man::take_legacy_positions(deps.storage)?;
+
+    Ok(Response::new()
+        .add_submessage(mint_man::instantiate_leveraged_token(
+            env,
+            token_code_id,
+            minted,
+        )?)
+        .add_submessage(liquid_man::instantiate_liquidity_token(
+            env,
+            token_code_id,
+            provided,
+        )?))
+}
+
 /**
  * Query entrypoint
  */
@@ -279,19 +1137,61 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
         QueryMsg::AllPoolInfo {} => {
             to_binary(&query_all_pool_info(deps, &env)?)
         }
-        QueryMsg::PriceHistory {} => to_binary(&query_price_history(deps)?),
+        QueryMsg::PriceHistory {
+            start_after,
+            end_before,
+            limit,
+            order,
+        } => to_binary(&query_price_history(
+            deps,
+            start_after,
+            end_before,
+            limit,
+            order,
+        )?),
+        QueryMsg::HyperparameterChanges {
+            start_after,
+            end_before,
+            limit,
+            order,
+        } => to_binary(&query_hyperparameter_changes(
+            deps,
+            start_after,
+            end_before,
+            limit,
+            order,
+        )?),
         QueryMsg::LiquidityPosition { address } => {
             to_binary(&query_addr_liquidity_position(deps, address)?)
         }
         QueryMsg::LeveragedPosition { address } => {
             to_binary(&query_addr_leveraged_position(deps, address)?)
         }
+        QueryMsg::FeesCollected {} => to_binary(&query_fees_collected(deps)?),
+        QueryMsg::Settlement {} => to_binary(&query_settlement(deps)?),
+        QueryMsg::Twap { window_seconds } => {
+            to_binary(&query_twap(deps, &env, window_seconds)?)
+        }
+        QueryMsg::Candles {
+            resolution,
+            range,
+            limit,
+        } => to_binary(&query_candles(deps, resolution, range, limit)?),
+        QueryMsg::SimulateMint { amount } => {
+            to_binary(&query_simulate_mint(deps, &env, amount)?)
+        }
+        QueryMsg::SimulateBurn { address, share } => {
+            to_binary(&query_simulate_burn(deps, &env, address, share)?)
+        }
+        QueryMsg::SimulateWithdraw { address, share } => {
+            to_binary(&query_simulate_withdraw(deps, &env, address, share)?)
+        }
         QueryMsg::ProtocolRatio {} => {
-            to_binary(&query_pr(&deps, &env).or_else(|_|
+            to_binary(&query_pr(&deps, &env).map_err(|_|
                     /* TODO handle contracterror -> StdResult gracefully */
-                    Err(StdError::GenericErr {
+                    StdError::GenericErr {
                         msg: String::from("No minted value"),
-                    }))?)
+                    })?)
         }
     }
 }
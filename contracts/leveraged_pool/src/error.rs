@@ -1,4 +1,5 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{StdError, Uint128};
+use cw0::PaymentError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -6,6 +7,9 @@ pub enum ContractError {
     #[error("{0}")]
     Std(#[from] StdError),
 
+    #[error("{0}")]
+    Payment(#[from] PaymentError),
+
     #[error("Error in computing arithmetic result")]
     /* TODO This is DivideByZero and Overflow and friends */
     ArithmeticError {},
@@ -16,6 +20,9 @@ pub enum ContractError {
     #[error("Total minted value is zero")]
     NoMintedValue {},
 
+    #[error("Liquidity pool has no value left to issue shares against")]
+    NoLiquidityValue {},
+
     /* TODO This is StdError::SerializeErr */
     #[error("Unable to serialize response")]
     SerializeErr {},
@@ -29,6 +36,47 @@ pub enum ContractError {
     #[error("Proposed transaction would destabilize the pool")]
     WouldViolatePoolHealth {},
 
+    #[error("Protocol ratio is not below the rebalance ratio")]
+    RebalanceNotNeeded {},
+
+    #[error("Rebalance would wipe out every position, liquidate instead")]
+    RebalanceWouldWipeOut {},
+
+    #[error("Protocol ratio is not below the liquidation ratio")]
+    LiquidationNotNeeded {},
+
+    #[error("Pool has been liquidated, positions may only claim settlement")]
+    PoolLiquidating {},
+
+    #[error("Pool has not been liquidated")]
+    NotLiquidating {},
+
+    #[error("Pool is paused")]
+    PoolPaused {},
+
+    #[error("Minting is paused")]
+    MintsPaused {},
+
+    #[error("Knocked out positions have yet to claim their payout")]
+    KnockOutUnclaimed {},
+
+    #[error("Mint of {requested} exceeds the {remaining} of minted value the pool has room for")]
+    LeveragedValueCapExceeded {
+        remaining: Uint128,
+        requested: Uint128,
+    },
+
+    #[error("Deposit of {requested} exceeds the {remaining} the reserve has room for")]
+    ReserveCapExceeded {
+        remaining: Uint128,
+        requested: Uint128,
+    },
+
+    #[error(
+        "Position worth {position} would exceed the per-address cap of {cap}"
+    )]
+    PositionCapExceeded { cap: Uint128, position: Uint128 },
+
     #[error("Token has no liquidity")]
     NoTokenLiquidity {},
 
@@ -38,6 +86,21 @@ pub enum ContractError {
     #[error("Unauthorized")]
     Unauthorized {},
 
+    #[error("Change to {parameter} exceeds the allowed delta")]
+    HyperparameterDeltaTooLarge { parameter: String },
+
+    #[error("Cannot migrate from {contract} version {version}")]
+    UnsupportedMigration { contract: String, version: String },
+
+    #[error("Slippage limit exceeded: expected {expected}, got {actual}")]
+    SlippageLimitExceeded { expected: Uint128, actual: Uint128 },
+
+    #[error("Deadline {deadline} passed at {block_time}")]
+    DeadlinePassed { deadline: u64, block_time: u64 },
+
+    #[error("No price snapshot or leverage reset is due")]
+    NothingToPoke {},
+
     #[error("Invalid Leveraged Pool Params")]
     InvalidPoolParams {},
 
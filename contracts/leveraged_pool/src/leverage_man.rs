@@ -5,64 +5,132 @@
  * multiplying that price volatility by the leverage_amount.
  */
 use crate::error::ContractError;
-use crate::swap::TSLiason;
 use cosmwasm_std::{
-    Addr, Api, CanonicalAddr, Deps, DepsMut, Env, QuerierWrapper, Response,
-    StdResult, Storage, Uint128,
+    Addr, Api, CanonicalAddr, CosmosMsg, Deps, Env, Event, Order,
+    QuerierWrapper, Response, StdError, StdResult, Storage, Uint128,
+};
+use cw20::Cw20Coin;
+use cw_storage_plus::{Bound, Item, Map, U64Key};
+use leveraged_pools::oracle::{
+    time_weighted_average, PriceSource, PriceSourceConfig, PushOracle,
+    TerraSwapSpot, TerraSwapTwap,
 };
-use cw_storage_plus::{Item, Map};
 use leveraged_pools::pool::{
-    multiply_ratio, InstantiateMsg, MinterPosition, PriceContext,
-    PriceSnapshot, ProviderPosition, PRECISION,
+    multiply_ratio, Candle, CandleRange, CandleResolution,
+    HyperparameterChange, HyperparametersUpdate, InstantiateMsg,
+    LeverageDirection, MinterPosition, Ohlc, OrderBy, PoolCapacity, PoolStatus,
+    PriceContext, PriceSnapshot, ProviderPosition, SlippageLimits,
+    TSPricePoint, PRECISION, PREMIUM_PRECISION,
 };
 use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
 use std::vec::Vec;
+use terraswap::asset::{Asset, AssetInfo};
+use terraswap::pair::{PoolResponse, QueryMsg as PairQueryMsg};
+use terraswap::querier::{query_supply, query_token_balance};
 
 /**
  * Initialize state
  */
-pub fn init<'a>(
+pub fn init(
     env: &Env,
     storage: &mut dyn Storage,
     api: &dyn Api,
     querier: QuerierWrapper,
+    sender: &Addr,
     msg: &InstantiateMsg,
 ) -> Result<(), ContractError> {
+    /* Governance defaults to whoever instantiated the pool */
+    let governance_addr = api
+        .addr_canonicalize(msg.governance.as_deref().unwrap_or(sender.as_str()))
+        .map_err(|_| ContractError::InvalidAddr {})?;
+
+    let guardian_addr = match &msg.guardian {
+        Some(guardian) => Some(
+            api.addr_canonicalize(guardian)
+                .map_err(|_| ContractError::InvalidAddr {})?,
+        ),
+        None => None,
+    };
+
     /* Validate that terraswap pair address is at least valid */
-    let terraswap_pair_addr =
-        api.addr_canonicalize(&msg.terraswap_pair_addr)
-            .or_else(|_| Err(ContractError::InvalidAddr {}))?;
-
-    /* Validate that leveraged asset address is at least valid */
-    let leveraged_asset_addr = api
-        .addr_canonicalize(&msg.leveraged_asset_addr)
-        .or_else(|_| Err(ContractError::InvalidAddr {}))?;
-
-    /* Fetch current TS price */
-    let liason: TSLiason = TSLiason::new_from_pair(
-        &api.addr_humanize(&terraswap_pair_addr)
-            .or_else(|_| Err(ContractError::InvalidAddr {}))?,
-        &api.addr_humanize(&leveraged_asset_addr)
-            .or_else(|_| Err(ContractError::InvalidAddr {}))?,
-    );
+    let terraswap_pair_addr = api
+        .addr_canonicalize(&msg.terraswap_pair_addr)
+        .map_err(|_| ContractError::InvalidAddr {})?;
+
+    /* Validate that leveraged asset address is at least valid. Pools backed
+     * by a native coin have none */
+    let leveraged_asset_addr = match &msg.leveraged_asset_denom {
+        Some(denom) if denom.is_empty() => {
+            return Err(ContractError::InvalidPoolParams {});
+        }
+        Some(_) if !msg.leveraged_asset_addr.is_empty() => {
+            return Err(ContractError::InvalidPoolParams {});
+        }
+        Some(_) => None,
+        None => Some(
+            api.addr_canonicalize(&msg.leveraged_asset_addr)
+                .map_err(|_| ContractError::InvalidAddr {})?,
+        ),
+    };
+
+    /* Validate the configured price source */
+    let price_source = match msg.price_source.clone().unwrap_or_default() {
+        PriceSourceConfig::TerraswapTwap { window_seconds: 0 } => {
+            return Err(ContractError::InvalidPoolParams {});
+        }
+        PriceSourceConfig::PushOracle {
+            oracle_addr,
+            max_age_seconds,
+        } => PriceSourceConfig::PushOracle {
+            oracle_addr: api
+                .addr_validate(&oracle_addr)
+                .map_err(|_| ContractError::InvalidAddr {})?
+                .to_string(),
+            max_age_seconds,
+        },
+        config => config,
+    };
 
     /* Set hyperparameters from inputs */
     let hyper_p = Hyperparameters {
         leverage_amount: msg.leverage_amount,
+        direction: msg.direction.unwrap_or_default(),
         minimum_protocol_ratio: msg.minimum_protocol_ratio,
         rebalance_ratio: msg.rebalance_ratio,
         mint_premium: msg.mint_premium,
         rebalance_premium: msg.rebalance_premium,
         terraswap_pair_addr,
         leveraged_asset_addr,
+        leveraged_asset_denom: msg.leveraged_asset_denom.clone(),
+        twap_window: msg.twap_window.unwrap_or(DEFAULT_TWAP_WINDOW),
+        knock_out_floor: msg
+            .knock_out_floor
+            .unwrap_or_else(|| Uint128::new(DEFAULT_KNOCK_OUT_FLOOR)),
+        price_data_expiry: msg
+            .price_data_expiry
+            .unwrap_or(DEFAULT_PRICE_DATA_EXPIRY),
+        leverage_expiry: msg.leverage_expiry.unwrap_or(DEFAULT_LEVERAGE_EXPIRY),
+        leverage_reset_offset: msg.leverage_reset_offset.unwrap_or_default(),
+        price_data_n: msg.price_data_n.unwrap_or(DEFAULT_PRICE_DATA_N),
+        keeper_reward: msg.keeper_reward.unwrap_or_default(),
+        liquidation_ratio: msg
+            .liquidation_ratio
+            .unwrap_or_else(|| Uint128::new(DEFAULT_LIQUIDATION_RATIO)),
+        circuit_breaker: msg.circuit_breaker,
+        max_total_leveraged_value: msg.max_total_leveraged_value,
+        max_assets_in_reserve: msg.max_assets_in_reserve,
+        max_position_per_address: msg.max_position_per_address,
     };
 
-    if hyperparameters_is_valid(&hyper_p) {
+    if !hyperparameters_is_valid(&hyper_p) {
         return Err(ContractError::InvalidPoolParams {});
     }
 
     /* TODO I don't really care about TSPricePoint.timestamp, refactor maybe */
-    let opening_price = liason.fetch_ts_price(&env, querier)?;
+    let opening_price =
+        build_price_source(&hyper_p, &price_source, api, Vec::new())?
+            .query_observation(env, querier)?;
     let genesis_snapshot = PriceSnapshot {
         asset_price: opening_price.u_price,
         leveraged_price: opening_price.u_price,
@@ -77,84 +145,312 @@ pub fn init<'a>(
         total_leveraged_assets: Uint128::zero(),
         total_asset_pool_share: Uint128::zero(),
         total_leveraged_pool_share: Uint128::zero(),
+        governance_addr,
+        factory_addr: Some(api.addr_canonicalize(sender.as_str())?),
+        keeper_budget: Uint128::zero(),
+        guardian_addr,
+        status: PoolStatus::Active,
     };
 
     /* Saving game data to memory card (PS2) in MEMORY CARD SLOT 1. Do not
      * remove memory card (PS2) or the controller, reset or switch off the
      * console */
     HYPERPARAMETERS.save(storage, &hyper_p)?;
+    PRICE_SOURCE.save(storage, &price_source)?;
     POOLSTATE.save(storage, &init_state)?;
-    PRICE_DATA.save(storage, &vec![genesis_snapshot])?;
-    // LIQUIDITYSTATE.save(storage, )
+    append_price_snapshot(storage, genesis_snapshot, hyper_p.price_data_n)?;
+
+    Ok(())
+}
+
+/**
+ * Convert the 0.1.0 PoolState, which had no governance, to the current layout
+ */
+pub fn migrate_pool_state_v0_1(
+    storage: &mut dyn Storage,
+    governance_addr: CanonicalAddr,
+) -> StdResult<PoolState> {
+    let legacy = LEGACY_POOLSTATE.load(storage)?;
+
+    let pool_state = PoolState {
+        latest_reset_snapshot: legacy.latest_reset_snapshot,
+        latest_historic_snapshot: legacy.latest_historic_snapshot,
+        assets_in_reserve: legacy.assets_in_reserve,
+        total_asset_pool_share: legacy.total_asset_pool_share,
+        total_leveraged_assets: legacy.total_leveraged_assets,
+        total_leveraged_pool_share: legacy.total_leveraged_pool_share,
+        governance_addr,
+        factory_addr: None,
+        keeper_budget: Uint128::zero(),
+        guardian_addr: None,
+        status: PoolStatus::Active,
+    };
+
+    POOLSTATE.save(storage, &pool_state)?;
+    Ok(pool_state)
+}
+
+/**
+ * Move the 0.1.0 price history, a single vector, into PRICE_DATA. Pools which
+ * never recorded a snapshot start from their latest historic snapshot
+ */
+pub fn migrate_price_data_v0_1(storage: &mut dyn Storage) -> StdResult<()> {
+    let mut price_data =
+        LEGACY_PRICE_DATA.may_load(storage)?.unwrap_or_default();
+    LEGACY_PRICE_DATA.remove(storage);
+
+    if price_data.is_empty() {
+        price_data.push(POOLSTATE.load(storage)?.latest_historic_snapshot);
+    }
+
+    let price_data_n = HYPERPARAMETERS.load(storage)?.price_data_n;
+    for snapshot in price_data {
+        append_price_snapshot(storage, snapshot, price_data_n)?;
+    }
 
     Ok(())
 }
 
 /**
- * Exchange a number of `unleveraged_assets` for their equivalent in leveraged
- * assets. Track this change in MINTSTATE.
+ * Remove the 0.1.0 per-address position maps and return their balances s/t
+ * they can be issued as pool tokens. Returns (minted, provided)
+ */
+pub fn take_legacy_positions(
+    storage: &mut dyn Storage,
+) -> StdResult<(Vec<Cw20Coin>, Vec<Cw20Coin>)> {
+    let mut take = |map: Map<&Addr, Uint128>| -> StdResult<Vec<Cw20Coin>> {
+        let positions = map
+            .range(storage, None, None, Order::Ascending)
+            .map(|item| {
+                let (addr, amount) = item?;
+                Ok(Cw20Coin {
+                    address: String::from_utf8(addr)?,
+                    amount,
+                })
+            })
+            .collect::<StdResult<Vec<Cw20Coin>>>()?;
+
+        for position in positions.iter() {
+            map.remove(storage, &Addr::unchecked(&position.address));
+        }
+
+        Ok(positions
+            .into_iter()
+            .filter(|position| !position.amount.is_zero())
+            .collect())
+    };
+
+    Ok((take(LEGACY_MINTSTATE)?, take(LEGACY_LIQUIDITYSTATE)?))
+}
+
+/**
+ * Apply a governance update to the hyperparameters. Each parameter may only
+ * move by a bounded delta per call and the result must still be valid
+ */
+pub fn update_hyperparameters(
+    storage: &mut dyn Storage,
+    api: &dyn Api,
+    env: &Env,
+    update: &HyperparametersUpdate,
+) -> Result<Vec<HyperparameterChange>, ContractError> {
+    let pool_state = POOLSTATE.load(storage)?;
+    if api.addr_canonicalize(update.sender.as_str())?
+        != pool_state.governance_addr
+    {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut hyper_p = HYPERPARAMETERS.load(storage)?;
+    let mut changes = Vec::new();
+
+    for (parameter, current, proposed, max_delta) in [
+        (
+            "minimum_protocol_ratio",
+            &mut hyper_p.minimum_protocol_ratio,
+            update.minimum_protocol_ratio,
+            MAX_RATIO_DELTA,
+        ),
+        (
+            "rebalance_ratio",
+            &mut hyper_p.rebalance_ratio,
+            update.rebalance_ratio,
+            MAX_RATIO_DELTA,
+        ),
+        (
+            "mint_premium",
+            &mut hyper_p.mint_premium,
+            update.mint_premium,
+            MAX_PREMIUM_DELTA,
+        ),
+        (
+            "rebalance_premium",
+            &mut hyper_p.rebalance_premium,
+            update.rebalance_premium,
+            MAX_PREMIUM_DELTA,
+        ),
+    ] {
+        let proposed = match proposed {
+            Some(proposed) if proposed != *current => proposed,
+            _ => continue,
+        };
+
+        let delta = if proposed > *current {
+            proposed - *current
+        } else {
+            *current - proposed
+        };
+        if delta > Uint128::new(max_delta) {
+            return Err(ContractError::HyperparameterDeltaTooLarge {
+                parameter: parameter.to_string(),
+            });
+        }
+
+        changes.push(HyperparameterChange {
+            parameter: parameter.to_string(),
+            previous: *current,
+            updated: proposed,
+            timestamp: env.block.time.seconds(),
+        });
+        *current = proposed;
+    }
+
+    if !hyperparameters_is_valid(&hyper_p) {
+        return Err(ContractError::InvalidPoolParams {});
+    }
+
+    HYPERPARAMETERS.save(storage, &hyper_p)?;
+    for change in &changes {
+        record_hyperparameter_change(storage, change)?;
+    }
+
+    Ok(changes)
+}
+
+/**
+ * Exchange a number of `unleveraged_assets` for `mint_count` leveraged
+ * assets. The position itself is tracked by the balances of the leveraged
+ * token which `mint_man` mints alongside this call.
+ *
+ * Returns the share of the leveraged pool issued for this position
  *
  * Assumes the position was already approved by `mint_man`
  */
 pub fn create_leveraged_position(
     storage: &mut dyn Storage,
-    sender: &Addr,
     mint_count: Uint128,
     unleveraged_assets: Uint128,
 ) -> Result<MinterPosition, ContractError> {
     let mut state = POOLSTATE.load(storage)?;
-    let already_minted = match MINTSTATE.load(storage, &sender) {
-        Ok(mint) => mint,
-        _ => Uint128::zero(),
-    };
-
-    let new_mint_count = already_minted + mint_count;
-
-    MINTSTATE.save(storage, &sender, &new_mint_count)?;
+    let mint_share = leveraged_assets_to_share(&state, mint_count)?;
 
     state.assets_in_reserve += unleveraged_assets;
-    state.total_leveraged_pool_share += mint_count;
+    state.total_leveraged_pool_share += mint_share;
     state.total_leveraged_assets += mint_count;
 
     POOLSTATE.save(storage, &state)?;
 
     Ok(MinterPosition {
-        leveraged_pool_partial_share: mint_count,
+        leveraged_pool_partial_share: mint_share,
         leveraged_pool_total_share: state.total_leveraged_pool_share,
     })
 }
 
 /**
- * Exchange a leveraged position for its equivalent in unleveraged assets and
- * send the result in Cw20 tokens back to the address that burned the position
+ * Exchange `burn` share of the leveraged pool, worth `burn_count` leveraged
+ * assets, for `redeem` unleveraged assets. The leveraged tokens themselves
+ * are burned by `mint_man`
  *
  * Assumes the burn was already approved by `mint_man`
  */
 pub fn burn_leveraged_position(
     storage: &mut dyn Storage,
-    sender: &Addr,
     burn: Uint128,
+    burn_count: Uint128,
     redeem: Uint128,
-) -> Result<MinterPosition, ContractError> {
+) -> Result<(), ContractError> {
     let mut pool_state = POOLSTATE.load(storage)?;
-    let mut curr_pos = match MINTSTATE.load(storage, &sender) {
-        Ok(curr_mint) => MinterPosition {
-            leveraged_pool_partial_share: curr_mint,
-            leveraged_pool_total_share: pool_state.total_leveraged_pool_share,
-        },
-        _ => return Err(ContractError::InsufficientFunds {}),
-    };
+
+    if burn > pool_state.total_leveraged_pool_share
+        || burn_count > pool_state.total_leveraged_assets
+        || redeem > pool_state.assets_in_reserve
+    {
+        return Err(ContractError::InsufficientFunds {});
+    }
 
     pool_state.assets_in_reserve -= redeem;
     pool_state.total_leveraged_pool_share -= burn;
-    pool_state.total_leveraged_assets -= burn;
-    curr_pos.leveraged_pool_partial_share -= burn;
-    curr_pos.leveraged_pool_total_share -= burn;
+    pool_state.total_leveraged_assets -= burn_count;
+
+    POOLSTATE.save(storage, &pool_state)?;
+
+    Ok(())
+}
+
+/**
+ * Shrink every minted position pro rata by removing `shrink_count` leveraged
+ * assets from the pool and paying `reward` unleveraged assets out of reserve.
+ * Shares are untouched so each share is simply backed by fewer assets
+ *
+ * Assumes the rebalance was already approved by `mint_man`
+ */
+pub fn rebalance_leveraged_positions(
+    storage: &mut dyn Storage,
+    shrink_count: Uint128,
+    reward: Uint128,
+) -> Result<PoolState, ContractError> {
+    let mut pool_state = POOLSTATE.load(storage)?;
+
+    if shrink_count > pool_state.total_leveraged_assets
+        || reward > pool_state.assets_in_reserve
+    {
+        return Err(ContractError::InsufficientFunds {});
+    }
+
+    pool_state.total_leveraged_assets -= shrink_count;
+    pool_state.assets_in_reserve -= reward;
 
-    MINTSTATE.save(storage, sender, &curr_pos.leveraged_pool_partial_share)?;
     POOLSTATE.save(storage, &pool_state)?;
 
-    Ok(curr_pos)
+    Ok(pool_state)
+}
+
+/**
+ * Number of leveraged assets backing `share` of the leveraged pool
+ */
+pub fn leveraged_share_to_assets(
+    state: &PoolState,
+    share: Uint128,
+) -> StdResult<Uint128> {
+    /* Shares and assets are 1:1 until the first rebalance */
+    if state.total_leveraged_pool_share.is_zero() {
+        return Ok(share);
+    }
+
+    multiply_ratio(
+        share,
+        state.total_leveraged_assets,
+        state.total_leveraged_pool_share,
+    )
+}
+
+/**
+ * Share of the leveraged pool to issue for `asset_count` leveraged assets
+ */
+pub fn leveraged_assets_to_share(
+    state: &PoolState,
+    asset_count: Uint128,
+) -> StdResult<Uint128> {
+    if state.total_leveraged_pool_share.is_zero()
+        || state.total_leveraged_assets.is_zero()
+    {
+        return Ok(asset_count);
+    }
+
+    multiply_ratio(
+        asset_count,
+        state.total_leveraged_pool_share,
+        state.total_leveraged_assets,
+    )
 }
 
 /**
@@ -168,10 +464,21 @@ pub fn leveraged_equivalence(
 ) -> Result<Uint128, ContractError> {
     let curr = get_price_context(deps.storage, deps.api, deps.querier, env)?
         .current_snapshot;
+    leveraged_equivalence_at(&curr, asset_count)
+}
+
+/**
+ * Convert `asset_count` *unleveraged* assets to their leveraged equivalent
+ * at the prices of `snapshot`
+ */
+pub fn leveraged_equivalence_at(
+    snapshot: &PriceSnapshot,
+    asset_count: Uint128,
+) -> Result<Uint128, ContractError> {
     Ok(multiply_ratio(
         asset_count,
-        curr.asset_price,
-        curr.leveraged_price,
+        snapshot.asset_price,
+        snapshot.leveraged_price,
     )?)
 }
 
@@ -186,10 +493,21 @@ pub fn unleveraged_equivalence(
 ) -> Result<Uint128, ContractError> {
     let curr = get_price_context(deps.storage, deps.api, deps.querier, env)?
         .current_snapshot;
+    unleveraged_equivalence_at(&curr, asset_count)
+}
+
+/**
+ * Convert `asset_count` *leveraged* assets to their unleveraged equivalent
+ * at the prices of `snapshot`
+ */
+pub fn unleveraged_equivalence_at(
+    snapshot: &PriceSnapshot,
+    asset_count: Uint128,
+) -> Result<Uint128, ContractError> {
     Ok(multiply_ratio(
         asset_count,
-        curr.leveraged_price,
-        curr.asset_price,
+        snapshot.leveraged_price,
+        snapshot.asset_price,
     )?)
 }
 
@@ -211,13 +529,24 @@ pub fn calculate_pr(
         get_price_context(deps.storage, deps.api, deps.querier, env)?
             .current_snapshot;
 
+    calculate_pr_at(&curr_snapshot, total_assets, total_leveraged_assets)
+}
+
+/**
+ * Protocol ratio at the prices of `snapshot`
+ */
+pub fn calculate_pr_at(
+    snapshot: &PriceSnapshot,
+    total_assets: Uint128,
+    total_leveraged_assets: Uint128,
+) -> Result<Uint128, ContractError> {
     let total_minted_value = total_leveraged_assets
-        .checked_mul(curr_snapshot.leveraged_price)
-        .or_else(|_| Err(ContractError::ArithmeticError {}))?;
+        .checked_mul(snapshot.leveraged_price)
+        .map_err(|_| ContractError::ArithmeticError {})?;
 
     let air_value = total_assets
-        .checked_mul(curr_snapshot.asset_price)
-        .or_else(|_| Err(ContractError::ArithmeticError {}))?;
+        .checked_mul(snapshot.asset_price)
+        .map_err(|_| ContractError::ArithmeticError {})?;
 
     Ok(multiply_ratio(
         air_value,
@@ -226,396 +555,1713 @@ pub fn calculate_pr(
     )?)
 }
 
-pub fn check_reset_leverage(
-    storage: &mut dyn Storage,
-    api: &dyn Api,
-    querier: QuerierWrapper,
+/**
+ * Enforce the bounds a user placed on a transaction which would give them
+ * `received` tokens at a leveraged price of `leveraged_price`
+ */
+pub fn check_slippage(
     env: &Env,
+    limits: &SlippageLimits,
+    received: Uint128,
+    leveraged_price: Uint128,
 ) -> Result<(), ContractError> {
-    /* TODO I can reduce the number of loads in this call stack */
-    let mut state = POOLSTATE.load(storage)?;
-
-    let price_context = get_price_context(storage, api, querier, env)?;
+    let block_time = env.block.time.seconds();
+    if let Some(deadline) = limits.deadline {
+        if block_time > deadline {
+            return Err(ContractError::DeadlinePassed {
+                deadline,
+                block_time,
+            });
+        }
+    }
 
-    /* Update historic price data */
-    if price_timestamp_is_expired(&state.latest_historic_snapshot, env) {
-        let mut prices = PRICE_DATA.load(storage)?;
-        push_drain(&mut prices, price_context.current_snapshot, PRICE_DATA_N);
-        state.latest_historic_snapshot = price_context.current_snapshot;
-        PRICE_DATA.save(storage, &prices)?;
-        POOLSTATE.save(storage, &state)?;
+    if let Some(min_received) = limits.min_received {
+        if received < min_received {
+            return Err(ContractError::SlippageLimitExceeded {
+                expected: min_received,
+                actual: received,
+            });
+        }
     }
 
-    /* Reset leverage */
-    if leverage_is_expired(&price_context.opening_snapshot, env) {
-        state.latest_reset_snapshot = price_context.current_snapshot;
-        POOLSTATE.save(storage, &state)?;
+    if let Some(max_leveraged_price) = limits.max_leveraged_price {
+        if leveraged_price > max_leveraged_price {
+            return Err(ContractError::SlippageLimitExceeded {
+                expected: max_leveraged_price,
+                actual: leveraged_price,
+            });
+        }
     }
 
     Ok(())
 }
 
 /**
- * Helper to get backing, unleveraged asset contract address
+ * Pay out all minted positions at the knock-out floor once the leveraged
+ * price has fallen to it at both the spot price and the TWAP. Their value at
+ * the TWAP moves out of the reserve into a settlement which the knocked out
+ * leveraged tokens claim from, while the pool itself stays open to providers
+ * and leverage restarts from the floor
+ *
+ * Returns the event describing the knock-out, if there was one
  */
-pub fn get_asset_addr(deps: &Deps) -> StdResult<Addr> {
-    Ok(deps.api.addr_humanize(
-        &HYPERPARAMETERS.load(deps.storage)?.leveraged_asset_addr,
-    )?)
-}
-
-pub fn query_hyperparameters(deps: &Deps) -> StdResult<Hyperparameters> {
-    Ok(HYPERPARAMETERS.load(deps.storage)?)
-}
-
-pub fn query_price_history(deps: &Deps) -> Vec<PriceSnapshot> {
-    price_history(deps.storage)
-}
-
-pub fn query_pr(deps: &Deps, env: &Env) -> Result<Uint128, ContractError> {
-    let state = POOLSTATE.load(deps.storage)?;
+pub fn check_knock_out(
+    storage: &mut dyn Storage,
+    api: &dyn Api,
+    querier: QuerierWrapper,
+    env: &Env,
+) -> Result<Option<Event>, ContractError> {
+    let hyper_p = HYPERPARAMETERS.load(storage)?;
+    let mut state = POOLSTATE.load(storage)?;
 
-    calculate_pr(
-        deps,
-        &env,
-        state.assets_in_reserve,
-        state.total_leveraged_assets,
-    )
-}
+    let floor_price = multiply_ratio(
+        state.latest_reset_snapshot.leveraged_price,
+        hyper_p.knock_out_floor,
+        Uint128::from(PRECISION),
+    )?;
+    let deps = Deps {
+        storage,
+        api,
+        querier,
+    };
+    let (low, high) = get_pricing_bounds(&deps, env)?;
+    if low.leveraged_price.max(high.leveraged_price) > floor_price {
+        return Ok(None);
+    }
 
-pub fn query_pool_state(deps: &Deps) -> StdResult<PoolState> {
-    POOLSTATE.load(deps.storage)
-}
+    /* The TWAP snapshot is already held at the floor */
+    let settled_at = settlement_snapshot(storage, api, querier, env)?;
+    let settled_value =
+        unleveraged_equivalence_at(&settled_at, state.total_leveraged_assets)?
+            .min(state.assets_in_reserve);
+    if !state.total_leveraged_pool_share.is_zero() {
+        let mut settlement = knock_out_settlement(storage, settled_at)?;
+        settlement.leveraged_assets += settled_value;
+        settlement.leveraged_pool_share += state.total_leveraged_pool_share;
+        SETTLEMENT.save(storage, &settlement)?;
+    }
 
-fn price_history(storage: &dyn Storage) -> Vec<PriceSnapshot> {
-    PRICE_DATA.load(storage).unwrap_or(Vec::new())
-}
+    state.assets_in_reserve -= settled_value;
+    state.total_leveraged_assets = Uint128::zero();
+    state.total_leveraged_pool_share = Uint128::zero();
+    state.latest_reset_snapshot = settled_at;
+    POOLSTATE.save(storage, &state)?;
 
-pub fn update_pool_state(
-    storage: &mut dyn Storage,
-    new_pool_state: PoolState,
-) -> Result<Response, ContractError> {
-    POOLSTATE.save(storage, &new_pool_state)?;
-    Ok(Response::new())
+    Ok(Some(
+        Event::new("knock_out")
+            .add_attribute("asset_price", settled_at.asset_price)
+            .add_attribute("leveraged_price", settled_at.leveraged_price)
+            .add_attribute("settled_value", settled_value),
+    ))
 }
 
-pub fn update_pool_share(
-    storage: &mut dyn Storage,
-    user_addr: &Addr,
-    user_shares: &Uint128,
-) -> Result<Response, ContractError> {
-    LIQUIDITYSTATE.save(storage, user_addr, user_shares)?;
-    Ok(Response::new())
+/**
+ * Share of the leveraged pool which was knocked out and has yet to claim its
+ * payout. The leveraged tokens still held for it are indistinguishable from
+ * freshly minted ones, so nothing can be minted until they are all claimed
+ */
+pub fn knocked_out_share(storage: &dyn Storage) -> StdResult<Uint128> {
+    Ok(SETTLEMENT
+        .may_load(storage)?
+        .map_or_else(Uint128::zero, |settlement| {
+            settlement.leveraged_pool_share
+        }))
 }
 
 /**
- * Retrives a Mutable PoolState
+ * The settlement knocked out positions claim from, with nothing owed to
+ * providers. Payouts still unclaimed from an earlier knock-out carry over
  */
-pub fn get_pool_state(deps: &Deps) -> StdResult<PoolState> {
-    POOLSTATE.load(deps.storage)
+fn knock_out_settlement(
+    storage: &dyn Storage,
+    settled_at: PriceSnapshot,
+) -> StdResult<Settlement> {
+    Ok(SETTLEMENT.may_load(storage)?.unwrap_or(Settlement {
+        settled_at,
+        leveraged_assets: Uint128::zero(),
+        leveraged_pool_share: Uint128::zero(),
+        liquidity_assets: Uint128::zero(),
+        asset_pool_share: Uint128::zero(),
+    }))
 }
 
 /**
- * Retrieves Current Minted Position
+ * Pause the pool before anything is priced when a fresh observation moved
+ * further from the latest snapshot than the circuit breaker allows, leaving
+ * the suspicious price unrecorded
  */
-pub fn get_mint_map(deps: &DepsMut, addr: Addr) -> StdResult<MinterPosition> {
-    let currently_in_pool = MINTSTATE.has(deps.storage, &addr);
-    let mut my_partial_share = Uint128::new(0); //if no position currently open in the pool
+pub fn check_circuit_breaker(
+    storage: &mut dyn Storage,
+    api: &dyn Api,
+    querier: QuerierWrapper,
+    env: &Env,
+) -> Result<Option<Event>, ContractError> {
+    let hyper_p = HYPERPARAMETERS.load(storage)?;
+    if hyper_p.circuit_breaker.is_none() {
+        return Ok(None);
+    }
 
-    if currently_in_pool {
-        my_partial_share = MINTSTATE.load(deps.storage, &addr)?;
+    let mut state = POOLSTATE.load(storage)?;
+    let observation = observe(storage, api, querier, env)?;
+    if !circuit_breaker_trips(
+        &hyper_p,
+        &state.latest_historic_snapshot,
+        &observation,
+    )? {
+        return Ok(None);
     }
 
-    let pool_state = POOLSTATE.load(deps.storage)?;
-    let total_share = pool_state.total_leveraged_pool_share;
+    state.status = PoolStatus::Paused;
+    POOLSTATE.save(storage, &state)?;
 
-    let my_position = MinterPosition {
-        leveraged_pool_partial_share: my_partial_share,
-        leveraged_pool_total_share: total_share,
-    };
-    return Ok(my_position);
+    Ok(Some(
+        Event::new("circuit_breaker")
+            .add_attribute(
+                "reference_price",
+                state.latest_historic_snapshot.asset_price,
+            )
+            .add_attribute("asset_price", observation.asset_price),
+    ))
 }
 
 /**
- * Assert that `addr` has at least `check` leveraged assets
+ * Record a price snapshot and reset leverage when either is due
+ *
+ * Returns whether anything was recorded or reset
  */
-pub fn addr_has_adequate_leveraged_share(
-    deps: &Deps,
-    addr: &Addr,
-    check: Uint128,
-) -> bool {
-    match MINTSTATE.load(deps.storage, addr) {
-        Ok(partial) => partial >= check,
-        Err(_) => false,
+pub fn check_reset_leverage(
+    storage: &mut dyn Storage,
+    api: &dyn Api,
+    querier: QuerierWrapper,
+    env: &Env,
+) -> Result<bool, ContractError> {
+    /* TODO I can reduce the number of loads in this call stack */
+    let hyper_p = HYPERPARAMETERS.load(storage)?;
+    let mut state = POOLSTATE.load(storage)?;
+
+    let price_context = get_price_context(storage, api, querier, env)?;
+    let mut poked = false;
+
+    /* Update historic price data with the raw reading of the price source */
+    if price_timestamp_is_expired(
+        &state.latest_historic_snapshot,
+        env,
+        &hyper_p,
+    ) {
+        /* check_circuit_breaker has already vetted this observation */
+        let observation = observe(storage, api, querier, env)?;
+        append_price_snapshot(storage, observation, hyper_p.price_data_n)?;
+        state.latest_historic_snapshot = observation;
+        POOLSTATE.save(storage, &state)?;
+        poked = true;
     }
+
+    /* Reset leverage as of the latest epoch boundary */
+    if leverage_is_expired(&price_context.opening_snapshot, env, &hyper_p) {
+        state.latest_reset_snapshot = PriceSnapshot {
+            timestamp: leverage_epoch_start(env.block.time.seconds(), &hyper_p),
+            ..price_context.current_snapshot
+        };
+        POOLSTATE.save(storage, &state)?;
+        poked = true;
+    }
+
+    Ok(poked)
 }
 
 /**
- * Assert that `addr` has at least `check` share of LP pool
+ * Whether check_reset_leverage has anything to do, without querying prices
  */
-pub fn addr_has_adequate_lp_share(
-    deps: &Deps,
-    addr: &Addr,
-    check: Uint128,
-) -> bool {
-    match LIQUIDITYSTATE.load(deps.storage, addr) {
-        Ok(partial) => partial >= check,
-        Err(_) => false,
-    }
+pub fn poke_is_due(storage: &dyn Storage, env: &Env) -> StdResult<bool> {
+    let hyper_p = HYPERPARAMETERS.load(storage)?;
+    let state = POOLSTATE.load(storage)?;
+
+    Ok(price_timestamp_is_expired(
+        &state.latest_historic_snapshot,
+        env,
+        &hyper_p,
+    ) || leverage_is_expired(&state.latest_reset_snapshot, env, &hyper_p))
 }
 
 /**
- * Get minter's leveraged position
+ * Add backing assets to the budget keepers are rewarded from
  */
-pub fn get_addr_leveraged_share(deps: &Deps, addr: &Addr) -> Uint128 {
-    match MINTSTATE.load(deps.storage, addr) {
-        Ok(pos) => pos,
-        Err(_) => Uint128::zero(),
-    }
+pub fn fund_keeper_budget(
+    storage: &mut dyn Storage,
+    amount: Uint128,
+) -> StdResult<Uint128> {
+    let mut state = POOLSTATE.load(storage)?;
+    state.keeper_budget += amount;
+    POOLSTATE.save(storage, &state)?;
+
+    Ok(state.keeper_budget)
 }
 
 /**
- * Find the leveraged position (if any) held by addr
+ * Take the keeper reward out of the budget, or whatever is left of it
  */
-pub fn get_leveraged_position(
-    deps: &Deps,
-    addr: &Addr,
-) -> StdResult<MinterPosition> {
-    let leveraged_pool_partial_share = get_addr_leveraged_share(&deps, &addr);
+pub fn pay_keeper(storage: &mut dyn Storage) -> StdResult<Uint128> {
+    let hyper_p = HYPERPARAMETERS.load(storage)?;
+    let mut state = POOLSTATE.load(storage)?;
 
-    let pool_state = query_pool_state(&deps)?;
-    let leveraged_pool_total_share = pool_state.total_leveraged_pool_share;
+    let reward = hyper_p.keeper_reward.min(state.keeper_budget);
+    state.keeper_budget -= reward;
+    POOLSTATE.save(storage, &state)?;
 
-    Ok(MinterPosition {
-        leveraged_pool_partial_share,
-        leveraged_pool_total_share,
-    })
+    Ok(reward)
 }
 
 /**
- * Retrieves Current Liquidity Position
+ * Room left under the pool's caps at the prices of `pricing`
  */
-pub fn get_liquidity_position(
+pub fn remaining_capacity(
     deps: &Deps,
-    addr: &Addr,
-) -> StdResult<ProviderPosition> {
-    let pool_state = POOLSTATE.load(deps.storage)?;
-    let total_share = pool_state.total_asset_pool_share;
+    pricing: &PriceSnapshot,
+) -> Result<PoolCapacity, ContractError> {
+    let hyper_p = HYPERPARAMETERS.load(deps.storage)?;
+    let state = POOLSTATE.load(deps.storage)?;
 
-    let my_partial_share = match LIQUIDITYSTATE.load(deps.storage, &addr) {
-        Ok(partial) => partial,
-        _ => Uint128::zero(),
+    let remaining_leveraged_value = match hyper_p.max_total_leveraged_value {
+        Some(cap) => Some(cap.saturating_sub(unleveraged_equivalence_at(
+            pricing,
+            state.total_leveraged_assets,
+        )?)),
+        None => None,
     };
 
-    let my_position = ProviderPosition {
-        asset_pool_partial_share: my_partial_share,
-        asset_pool_total_share: total_share,
-    };
-    return Ok(my_position);
+    Ok(PoolCapacity {
+        remaining_leveraged_value,
+        remaining_assets_in_reserve: hyper_p
+            .max_assets_in_reserve
+            .map(|cap| cap.saturating_sub(state.assets_in_reserve)),
+        max_position_per_address: hyper_p.max_position_per_address,
+    })
 }
 
 /**
- * Retrieves snapshot of the opening prices + calcualtes the snapshot of the current up-to-date TS price snapshot
- * with leveraged price
+ * What the pool currently accepts
  */
-pub fn get_price_context(
-    storage: &dyn Storage,
+pub fn get_pool_status(storage: &dyn Storage) -> StdResult<PoolStatus> {
+    Ok(POOLSTATE.load(storage)?.status)
+}
+
+/**
+ * Pause or resume the pool on behalf of its guardian or governance. Resuming
+ * a paused pool records the current price as the circuit breaker's new
+ * reference, while settling it liquidates the pool at the TWAP
+ *
+ * Returns the settlement if the pool was settled
+ */
+pub fn set_pool_status(
+    storage: &mut dyn Storage,
     api: &dyn Api,
     querier: QuerierWrapper,
     env: &Env,
-) -> StdResult<PriceContext> {
-    let hyper_p = HYPERPARAMETERS.load(storage)?;
-    let pool_state = POOLSTATE.load(storage)?;
+    sender: &Addr,
+    status: PoolStatus,
+) -> Result<Option<Settlement>, ContractError> {
+    let mut state = POOLSTATE.load(storage)?;
+    let sender = api.addr_canonicalize(sender.as_str())?;
+    if sender != state.governance_addr && Some(sender) != state.guardian_addr {
+        return Err(ContractError::Unauthorized {});
+    }
 
-    let liason: TSLiason = TSLiason::new_from_pair(
-        &api.addr_humanize(&hyper_p.terraswap_pair_addr)?,
-        &api.addr_humanize(&hyper_p.leveraged_asset_addr)?,
-    );
-    let opening_asset_price = pool_state.latest_reset_snapshot.asset_price;
-    let opening_leveraged_price =
-        pool_state.latest_reset_snapshot.leveraged_price;
-
-    let current_asset_price_ts_point = liason.fetch_ts_price(&env, querier)?;
-    let current_leveraged_price = get_leveraged_price(
-        opening_asset_price,
-        current_asset_price_ts_point.u_price,
-        hyper_p.leverage_amount,
-        opening_leveraged_price,
-    );
-
-    let current_snapshot = PriceSnapshot {
-        asset_price: current_asset_price_ts_point.u_price,
-        leveraged_price: current_leveraged_price,
-        timestamp: env.block.time.seconds(),
-    };
+    match (state.status, status) {
+        (PoolStatus::Settled, _) => {
+            return Err(ContractError::PoolLiquidating {});
+        }
+        (_, PoolStatus::Settled) => {
+            let settled_at = settlement_snapshot(storage, api, querier, env)?;
+            return Ok(Some(settle_at(storage, settled_at)?));
+        }
+        (PoolStatus::Paused, PoolStatus::Active | PoolStatus::MintPaused) => {
+            let reference = observe(storage, api, querier, env)?;
+            let price_data_n = HYPERPARAMETERS.load(storage)?.price_data_n;
+            append_price_snapshot(storage, reference, price_data_n)?;
+            state.latest_historic_snapshot = reference;
+        }
+        _ => {}
+    }
 
-    let context_snapshots = PriceContext {
-        opening_snapshot: pool_state.latest_reset_snapshot,
-        current_snapshot: current_snapshot,
-    };
+    state.status = status;
+    POOLSTATE.save(storage, &state)?;
 
-    Ok(context_snapshots)
+    Ok(None)
 }
 
 /**
- * Inputs the opening price, leveraged amount, etc to calculate the current leveraged price
+ * Liquidate the pool once its protocol ratio has fallen below the liquidation
+ * ratio at both the spot price and the TWAP. Minted positions are settled
+ * first at their unleveraged equivalence at the TWAP, as far as the reserve
+ * covers them, and providers split whatever is left. The whole reserve moves
+ * into the settlement, emptying the pool
  */
-fn get_leveraged_price(
-    start_asset_price: Uint128,
+pub fn liquidate(
+    storage: &mut dyn Storage,
+    api: &dyn Api,
+    querier: QuerierWrapper,
+    env: &Env,
+) -> Result<Settlement, ContractError> {
+    let hyper_p = HYPERPARAMETERS.load(storage)?;
+    let state = POOLSTATE.load(storage)?;
+
+    if state.total_leveraged_assets.is_zero() {
+        return Err(ContractError::NoMintedValue {});
+    }
+
+    /* A spot price pushed under the ratio alone cannot liquidate the pool */
+    let deps = Deps {
+        storage,
+        api,
+        querier,
+    };
+    let (low, high) = get_pricing_bounds(&deps, env)?;
+    for snapshot in [low, high].iter() {
+        if calculate_pr_at(
+            snapshot,
+            state.assets_in_reserve,
+            state.total_leveraged_assets,
+        )? >= hyper_p.liquidation_ratio
+        {
+            return Err(ContractError::LiquidationNotNeeded {});
+        }
+    }
+
+    let settled_at = settlement_snapshot(storage, api, querier, env)?;
+    settle_at(storage, settled_at)
+}
+
+/**
+ * Price a settlement at the TWAP, which a single trade cannot move far
+ */
+fn settlement_snapshot(
+    storage: &dyn Storage,
+    api: &dyn Api,
+    querier: QuerierWrapper,
+    env: &Env,
+) -> StdResult<PriceSnapshot> {
+    let twap_window = HYPERPARAMETERS.load(storage)?.twap_window;
+    get_twap_snapshot(storage, api, querier, env, twap_window)
+}
+
+/**
+ * Split the reserve into the settlement at the prices of `settled_at`. The
+ * keeper budget can no longer be spent once the pool is settled, so whatever
+ * is left of it is owed to providers along with the rest of the reserve.
+ * Knocked out positions still to claim keep their payout
+ */
+fn settle_at(
+    storage: &mut dyn Storage,
+    settled_at: PriceSnapshot,
+) -> Result<Settlement, ContractError> {
+    let mut state = POOLSTATE.load(storage)?;
+
+    let leveraged_assets =
+        unleveraged_equivalence_at(&settled_at, state.total_leveraged_assets)?
+            .min(state.assets_in_reserve);
+    let mut settlement = knock_out_settlement(storage, settled_at)?;
+    settlement.settled_at = settled_at;
+    settlement.leveraged_assets += leveraged_assets;
+    settlement.leveraged_pool_share += state.total_leveraged_pool_share;
+    settlement.liquidity_assets =
+        state.assets_in_reserve - leveraged_assets + state.keeper_budget;
+    settlement.asset_pool_share = state.total_asset_pool_share;
+    SETTLEMENT.save(storage, &settlement)?;
+
+    state.assets_in_reserve = Uint128::zero();
+    state.keeper_budget = Uint128::zero();
+    state.total_leveraged_assets = Uint128::zero();
+    state.total_leveraged_pool_share = Uint128::zero();
+    state.total_asset_pool_share = Uint128::zero();
+    state.status = PoolStatus::Settled;
+    POOLSTATE.save(storage, &state)?;
+
+    Ok(settlement)
+}
+
+/**
+ * Take `share` of the leveraged pool out of the settlement, returning the
+ * backing assets it is paid
+ */
+pub fn claim_leveraged_settlement(
+    storage: &mut dyn Storage,
+    share: Uint128,
+) -> Result<Uint128, ContractError> {
+    let mut settlement = load_settlement(storage)?;
+    let claimed = claim_pro_rata(
+        &mut settlement.leveraged_assets,
+        &mut settlement.leveraged_pool_share,
+        share,
+    )?;
+    SETTLEMENT.save(storage, &settlement)?;
+
+    Ok(claimed)
+}
+
+/**
+ * Take `share` of the liquidity pool out of the settlement, returning the
+ * backing assets it is paid
+ */
+pub fn claim_liquidity_settlement(
+    storage: &mut dyn Storage,
+    share: Uint128,
+) -> Result<Uint128, ContractError> {
+    let mut settlement = load_settlement(storage)?;
+    let claimed = claim_pro_rata(
+        &mut settlement.liquidity_assets,
+        &mut settlement.asset_pool_share,
+        share,
+    )?;
+    SETTLEMENT.save(storage, &settlement)?;
+
+    Ok(claimed)
+}
+
+pub fn query_settlement(deps: &Deps) -> StdResult<Settlement> {
+    SETTLEMENT.load(deps.storage)
+}
+
+fn load_settlement(storage: &dyn Storage) -> Result<Settlement, ContractError> {
+    SETTLEMENT
+        .may_load(storage)?
+        .ok_or(ContractError::NotLiquidating {})
+}
+
+/**
+ * Pay out `share` of `total_share` pro rata from `assets`. The last claim
+ * takes whatever rounding left behind
+ */
+fn claim_pro_rata(
+    assets: &mut Uint128,
+    total_share: &mut Uint128,
+    share: Uint128,
+) -> Result<Uint128, ContractError> {
+    if share.is_zero() || share > *total_share {
+        return Err(ContractError::InsufficientFunds {});
+    }
+
+    let claimed = multiply_ratio(*assets, share, *total_share)?;
+    *assets -= claimed;
+    *total_share -= share;
+
+    Ok(claimed)
+}
+
+/**
+ * Reset leverage at the current price on behalf of the factory which created
+ * the pool, recording the new reference in the price history
+ */
+pub fn set_daily_leverage_reference(
+    storage: &mut dyn Storage,
+    api: &dyn Api,
+    querier: QuerierWrapper,
+    env: &Env,
+    sender: &Addr,
+) -> Result<PriceSnapshot, ContractError> {
+    let mut state = POOLSTATE.load(storage)?;
+    if Some(api.addr_canonicalize(sender.as_str())?) != state.factory_addr {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    /* contract::execute has already run this price past the circuit
+     * breaker */
+    let reference =
+        get_price_context(storage, api, querier, env)?.current_snapshot;
+    let price_data_n = HYPERPARAMETERS.load(storage)?.price_data_n;
+    append_price_snapshot(storage, reference, price_data_n)?;
+    state.latest_reset_snapshot = reference;
+    state.latest_historic_snapshot = reference;
+    POOLSTATE.save(storage, &state)?;
+
+    Ok(reference)
+}
+
+/**
+ * Helper to get the backing, unleveraged asset: either a Cw20 or a native coin
+ */
+pub fn get_backing_asset(deps: &Deps) -> StdResult<AssetInfo> {
+    backing_asset(&HYPERPARAMETERS.load(deps.storage)?, deps.api)
+}
+
+fn backing_asset(
+    hyper_p: &Hyperparameters,
+    api: &dyn Api,
+) -> StdResult<AssetInfo> {
+    Ok(
+        match (
+            &hyper_p.leveraged_asset_addr,
+            &hyper_p.leveraged_asset_denom,
+        ) {
+            (Some(addr), _) => AssetInfo::Token {
+                contract_addr: api.addr_humanize(addr)?.to_string(),
+            },
+            (None, Some(denom)) => AssetInfo::NativeToken {
+                denom: denom.clone(),
+            },
+            (None, None) => {
+                return Err(StdError::generic_err("Pool has no backing asset"))
+            }
+        },
+    )
+}
+
+/**
+ * Pay `amount` of the backing asset out of the pool to `recipient`. Native
+ * coins are sent less the Terra tax s/t the pool pays exactly `amount`
+ */
+pub fn transfer_backing_asset(
+    storage: &dyn Storage,
+    api: &dyn Api,
+    querier: &QuerierWrapper,
+    recipient: &Addr,
+    amount: Uint128,
+) -> StdResult<CosmosMsg> {
+    let asset = Asset {
+        info: backing_asset(&HYPERPARAMETERS.load(storage)?, api)?,
+        amount,
+    };
+
+    asset.into_msg(querier, recipient.clone())
+}
+
+pub fn query_hyperparameters(deps: &Deps) -> StdResult<Hyperparameters> {
+    HYPERPARAMETERS.load(deps.storage)
+}
+
+/**
+ * Page of at most `limit` hyperparameter changes numbered strictly between
+ * `start_after` and `end_before`, along with the cursor to the following page
+ * if there is one
+ */
+pub fn query_hyperparameter_changes(
+    deps: &Deps,
+    start_after: Option<u64>,
+    end_before: Option<u64>,
+    limit: Option<u32>,
+    order: OrderBy,
+) -> StdResult<(Vec<HyperparameterChange>, Option<u64>)> {
+    let limit = limit
+        .unwrap_or(DEFAULT_HISTORY_LIMIT)
+        .min(MAX_HISTORY_LIMIT) as usize;
+
+    let min = start_after.map(Bound::exclusive_int);
+    let max = end_before.map(Bound::exclusive_int);
+    let order = match order {
+        OrderBy::Ascending => Order::Ascending,
+        OrderBy::Descending => Order::Descending,
+    };
+    let mut page = HYPERPARAMETER_CHANGES
+        .range(deps.storage, min, max, order)
+        .take(limit + 1)
+        .map(|item| {
+            let (key, change) = item?;
+            Ok((change_number(&key)?, change))
+        })
+        .collect::<StdResult<Vec<(u64, HyperparameterChange)>>>()?;
+
+    /* Fetched one extra to tell whether another page follows */
+    let next_cursor = if page.len() > limit {
+        page.truncate(limit);
+        page.last().map(|(number, _)| *number)
+    } else {
+        None
+    };
+
+    Ok((
+        page.into_iter().map(|(_, change)| change).collect(),
+        next_cursor,
+    ))
+}
+
+/**
+ * Number of a hyperparameter change from its HYPERPARAMETER_CHANGES key
+ */
+fn change_number(key: &[u8]) -> StdResult<u64> {
+    let bytes = key.try_into().map_err(|_| {
+        StdError::generic_err("Malformed hyperparameter change key")
+    })?;
+
+    Ok(u64::from_be_bytes(bytes))
+}
+
+/**
+ * Append `change` to the change log, numbered in the order changes are made
+ */
+fn record_hyperparameter_change(
+    storage: &mut dyn Storage,
+    change: &HyperparameterChange,
+) -> StdResult<()> {
+    let number = HYPERPARAMETER_CHANGES_LEN
+        .may_load(storage)?
+        .unwrap_or_default();
+    HYPERPARAMETER_CHANGES.save(storage, U64Key::new(number), change)?;
+
+    HYPERPARAMETER_CHANGES_LEN.save(storage, &(number + 1))
+}
+
+pub fn get_governance_addr(deps: &Deps) -> StdResult<Addr> {
+    deps.api
+        .addr_humanize(&POOLSTATE.load(deps.storage)?.governance_addr)
+}
+
+pub fn get_guardian_addr(deps: &Deps) -> StdResult<Option<Addr>> {
+    POOLSTATE
+        .load(deps.storage)?
+        .guardian_addr
+        .map(|addr| deps.api.addr_humanize(&addr))
+        .transpose()
+}
+
+/**
+ * Page of at most `limit` candles at `resolution` opening within `range`,
+ * oldest first, along with the cursor to the following page if there is one
+ */
+pub fn query_candles(
+    deps: &Deps,
+    resolution: CandleResolution,
+    range: &CandleRange,
+    limit: Option<u32>,
+) -> StdResult<(Vec<Candle>, Option<u64>)> {
+    let limit = limit
+        .unwrap_or(DEFAULT_HISTORY_LIMIT)
+        .min(MAX_HISTORY_LIMIT) as usize;
+
+    let mut page = CANDLES
+        .prefix(U64Key::new(resolution.seconds()))
+        .range(
+            deps.storage,
+            range.start.map(Bound::inclusive_int),
+            range.end.map(Bound::exclusive_int),
+            Order::Ascending,
+        )
+        .take(limit + 1)
+        .map(|item| item.map(|(_, candle)| candle))
+        .collect::<StdResult<Vec<Candle>>>()?;
+
+    /* Fetched one extra to tell whether another page follows, which starts
+     * at that candle */
+    if page.len() <= limit {
+        return Ok((page, None));
+    }
+    let next_cursor = page.pop().map(|candle| candle.open_time);
+
+    Ok((page, next_cursor))
+}
+
+/**
+ * Page of at most `limit` snapshots strictly between `start_after` and
+ * `end_before`, along with the cursor to the following page if there is one
+ */
+pub fn query_price_history(
+    deps: &Deps,
+    start_after: Option<u64>,
+    end_before: Option<u64>,
+    limit: Option<u32>,
+    order: OrderBy,
+) -> StdResult<(Vec<PriceSnapshot>, Option<u64>)> {
+    let limit = limit
+        .unwrap_or(DEFAULT_HISTORY_LIMIT)
+        .min(MAX_HISTORY_LIMIT) as usize;
+
+    let min = start_after.map(Bound::exclusive_int);
+    let max = end_before.map(Bound::exclusive_int);
+    let order = match order {
+        OrderBy::Ascending => Order::Ascending,
+        OrderBy::Descending => Order::Descending,
+    };
+    let mut page = PRICE_DATA
+        .range(deps.storage, min, max, order)
+        .take(limit + 1)
+        .map(|item| item.map(|(_, snapshot)| snapshot))
+        .collect::<StdResult<Vec<PriceSnapshot>>>()?;
+
+    /* Fetched one extra to tell whether another page follows */
+    if page.len() <= limit {
+        return Ok((page, None));
+    }
+    page.truncate(limit);
+    let next_cursor = page.last().map(|snapshot| snapshot.timestamp);
+
+    Ok((page, next_cursor))
+}
+
+pub fn query_pr(deps: &Deps, env: &Env) -> Result<Uint128, ContractError> {
+    let state = POOLSTATE.load(deps.storage)?;
+
+    calculate_pr(
+        deps,
+        env,
+        state.assets_in_reserve,
+        state.total_leveraged_assets,
+    )
+}
+
+pub fn query_fees_collected(deps: &Deps) -> StdResult<FeesCollected> {
+    Ok(FEES_COLLECTED.may_load(deps.storage)?.unwrap_or_default())
+}
+
+/**
+ * Add a charged mint premium to the lifetime totals
+ */
+pub fn record_mint_premium(
+    storage: &mut dyn Storage,
+    premium: Uint128,
+) -> StdResult<FeesCollected> {
+    let mut fees = FEES_COLLECTED.may_load(storage)?.unwrap_or_default();
+    fees.mint_premium += premium;
+
+    FEES_COLLECTED.save(storage, &fees)?;
+    Ok(fees)
+}
+
+pub fn query_pool_state(deps: &Deps) -> StdResult<PoolState> {
+    POOLSTATE.load(deps.storage)
+}
+
+/**
+ * Snapshots recorded after `start`, preceded by the latest one at or before
+ * `start` whose price still held at the time, oldest first
+ */
+fn price_history_since(
+    storage: &dyn Storage,
+    start: u64,
+) -> StdResult<Vec<PriceSnapshot>> {
+    let mut history = vec![];
+    for item in PRICE_DATA.range(storage, None, None, Order::Descending) {
+        let (_, snapshot) = item?;
+        history.push(snapshot);
+
+        if snapshot.timestamp <= start {
+            break;
+        }
+    }
+
+    history.reverse();
+    Ok(history)
+}
+
+/**
+ * Fold `snapshot` into the candle it falls in at every resolution, pruning
+ * candles which have aged out
+ */
+fn record_candles(
+    storage: &mut dyn Storage,
+    snapshot: &PriceSnapshot,
+) -> StdResult<()> {
+    for (resolution, retention) in CANDLE_RETENTION {
+        let width = resolution.seconds();
+        let open_time = snapshot.timestamp - snapshot.timestamp % width;
+        let key = (U64Key::new(width), U64Key::new(open_time));
+
+        let candle = match CANDLES.may_load(storage, key.clone())? {
+            Some(mut candle) => {
+                candle.asset.record(snapshot.asset_price);
+                candle.leveraged.record(snapshot.leveraged_price);
+                candle
+            }
+            None => Candle {
+                open_time,
+                asset: Ohlc::opened_at(snapshot.asset_price),
+                leveraged: Ohlc::opened_at(snapshot.leveraged_price),
+            },
+        };
+        CANDLES.save(storage, key, &candle)?;
+
+        /* Candles arrive in order so at most the oldest has aged out */
+        let cutoff = open_time.saturating_sub(retention);
+        let oldest = CANDLES
+            .prefix(U64Key::new(width))
+            .range(storage, None, None, Order::Ascending)
+            .next()
+            .transpose()?;
+        if let Some((_, oldest)) = oldest {
+            if oldest.open_time < cutoff {
+                CANDLES.remove(
+                    storage,
+                    (U64Key::new(width), U64Key::new(oldest.open_time)),
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/**
+ * Record `snapshot` into the price history, pruning the oldest snapshot s/t
+ * at most `max` are kept
+ */
+fn append_price_snapshot(
+    storage: &mut dyn Storage,
+    snapshot: PriceSnapshot,
+    max: u64,
+) -> StdResult<()> {
+    let key = U64Key::new(snapshot.timestamp);
+    let mut len = PRICE_DATA_LEN.may_load(storage)?.unwrap_or_default();
+    if !PRICE_DATA.has(storage, key.clone()) {
+        len += 1;
+    }
+    PRICE_DATA.save(storage, key, &snapshot)?;
+    record_candles(storage, &snapshot)?;
+
+    if len > max {
+        let oldest = PRICE_DATA
+            .keys(storage, None, None, Order::Ascending)
+            .next();
+        if let Some(oldest) = oldest {
+            PRICE_DATA.remove(storage, U64Key::from(oldest));
+            len -= 1;
+        }
+    }
+
+    PRICE_DATA_LEN.save(storage, &len)
+}
+
+pub fn update_pool_state(
+    storage: &mut dyn Storage,
+    new_pool_state: PoolState,
+) -> Result<Response, ContractError> {
+    POOLSTATE.save(storage, &new_pool_state)?;
+    Ok(Response::new())
+}
+
+/**
+ * Retrives a Mutable PoolState
+ */
+pub fn get_pool_state(deps: &Deps) -> StdResult<PoolState> {
+    POOLSTATE.load(deps.storage)
+}
+
+/**
+ * Helper to get the address of the Cw20 representing leveraged positions
+ */
+pub fn get_leveraged_token_addr(deps: &Deps) -> StdResult<Addr> {
+    deps.api.addr_humanize(&LEVERAGED_TOKEN.load(deps.storage)?)
+}
+
+/**
+ * Helper to get the TerraSwap pair the asset is priced and swapped through
+ */
+pub fn get_terraswap_pair_addr(deps: &Deps) -> StdResult<Addr> {
+    deps.api
+        .addr_humanize(&HYPERPARAMETERS.load(deps.storage)?.terraswap_pair_addr)
+}
+
+/**
+ * Hold a zap-in mint until its swap has settled
+ */
+pub fn save_pending_zap(
+    storage: &mut dyn Storage,
+    zap: &PendingZap,
+) -> StdResult<()> {
+    PENDING_ZAP.save(storage, zap)
+}
+
+/**
+ * Release the zap-in mint waiting on a settled swap
+ */
+pub fn take_pending_zap(storage: &mut dyn Storage) -> StdResult<PendingZap> {
+    let zap = PENDING_ZAP.load(storage)?;
+    PENDING_ZAP.remove(storage);
+
+    Ok(zap)
+}
+
+/**
+ * Hold a zap-out payout until its swap has settled
+ */
+pub fn save_pending_zap_out(
+    storage: &mut dyn Storage,
+    zap: &PendingZapOut,
+) -> StdResult<()> {
+    PENDING_ZAP_OUT.save(storage, zap)
+}
+
+/**
+ * Release the zap-out payout waiting on a settled swap
+ */
+pub fn take_pending_zap_out(
+    storage: &mut dyn Storage,
+) -> StdResult<PendingZapOut> {
+    let zap = PENDING_ZAP_OUT.load(storage)?;
+    PENDING_ZAP_OUT.remove(storage);
+
+    Ok(zap)
+}
+
+/**
+ * The other asset of the pool's TerraSwap pair, which the backing asset is
+ * priced in
+ */
+pub fn get_stable_asset(deps: &Deps) -> StdResult<AssetInfo> {
+    let backing = get_backing_asset(deps)?;
+    let pool: PoolResponse = deps.querier.query_wasm_smart(
+        get_terraswap_pair_addr(deps)?,
+        &PairQueryMsg::Pool {},
+    )?;
+
+    pool.assets
+        .iter()
+        .map(|asset| asset.info.clone())
+        .find(|info| !info.equal(&backing))
+        .ok_or_else(|| StdError::generic_err("Pair does not hold the asset"))
+}
+
+/**
+ * Record the Cw20 representing leveraged positions once it is instantiated
+ */
+pub fn set_leveraged_token_addr(
+    storage: &mut dyn Storage,
+    api: &dyn Api,
+    addr: &Addr,
+) -> Result<(), ContractError> {
+    /* The token is only ever registered once */
+    if LEVERAGED_TOKEN.may_load(storage)?.is_some() {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    LEVERAGED_TOKEN.save(storage, &api.addr_canonicalize(addr.as_str())?)?;
+    Ok(())
+}
+
+/**
+ * Get minter's leveraged position from the leveraged token balances
+ */
+pub fn get_addr_leveraged_share(
+    deps: &Deps,
+    addr: &Addr,
+) -> StdResult<Uint128> {
+    query_token_balance(
+        &deps.querier,
+        get_leveraged_token_addr(deps)?,
+        addr.clone(),
+    )
+}
+
+/**
+ * Find the leveraged position (if any) held by addr
+ */
+pub fn get_leveraged_position(
+    deps: &Deps,
+    addr: &Addr,
+) -> StdResult<MinterPosition> {
+    let leveraged_token = get_leveraged_token_addr(deps)?;
+
+    Ok(MinterPosition {
+        leveraged_pool_partial_share: query_token_balance(
+            &deps.querier,
+            leveraged_token.clone(),
+            addr.clone(),
+        )?,
+        leveraged_pool_total_share: query_supply(
+            &deps.querier,
+            leveraged_token,
+        )?,
+    })
+}
+
+/**
+ * Helper to get the address of the Cw20 representing liquidity positions
+ */
+pub fn get_liquidity_token_addr(deps: &Deps) -> StdResult<Addr> {
+    deps.api.addr_humanize(&LIQUIDITY_TOKEN.load(deps.storage)?)
+}
+
+/**
+ * Record the Cw20 representing liquidity positions once it is instantiated
+ */
+pub fn set_liquidity_token_addr(
+    storage: &mut dyn Storage,
+    api: &dyn Api,
+    addr: &Addr,
+) -> Result<(), ContractError> {
+    /* The token is only ever registered once */
+    if LIQUIDITY_TOKEN.may_load(storage)?.is_some() {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    LIQUIDITY_TOKEN.save(storage, &api.addr_canonicalize(addr.as_str())?)?;
+    Ok(())
+}
+
+/**
+ * Retrieves Current Liquidity Position from the liquidity token balances
+ */
+pub fn get_liquidity_position(
+    deps: &Deps,
+    addr: &Addr,
+) -> StdResult<ProviderPosition> {
+    let liquidity_token = get_liquidity_token_addr(deps)?;
+
+    Ok(ProviderPosition {
+        asset_pool_partial_share: query_token_balance(
+            &deps.querier,
+            liquidity_token.clone(),
+            addr.clone(),
+        )?,
+        asset_pool_total_share: query_supply(&deps.querier, liquidity_token)?,
+    })
+}
+
+/**
+ * Retrieves snapshot of the opening prices + calcualtes the snapshot of the current up-to-date TS price snapshot
+ * with leveraged price
+ */
+pub fn get_price_context(
+    storage: &dyn Storage,
+    api: &dyn Api,
+    querier: QuerierWrapper,
+    env: &Env,
+) -> StdResult<PriceContext> {
+    let pool_state = POOLSTATE.load(storage)?;
+    let current_price =
+        price_source(storage, api, env)?.query_price(env, querier)?;
+
+    Ok(PriceContext {
+        opening_snapshot: pool_state.latest_reset_snapshot,
+        current_snapshot: leveraged_snapshot(storage, env, current_price)?,
+    })
+}
+
+/**
+ * Time-weighted average over the last `window` seconds of the recorded price
+ * history, with the live reading of the price source as the latest point
+ */
+pub fn get_twap_snapshot(
+    storage: &dyn Storage,
+    api: &dyn Api,
+    querier: QuerierWrapper,
+    env: &Env,
+    window: u64,
+) -> StdResult<PriceSnapshot> {
+    let spot =
+        price_source(storage, api, env)?.query_observation(env, querier)?;
+
+    let start = env.block.time.seconds().saturating_sub(window);
+    let mut observations: Vec<TSPricePoint> =
+        price_history_since(storage, start)?
+            .iter()
+            .map(|snapshot| TSPricePoint {
+                u_price: snapshot.asset_price,
+                timestamp: snapshot.timestamp,
+            })
+            .collect();
+    observations.push(spot);
+
+    let twap = TSPricePoint {
+        u_price: time_weighted_average(
+            &observations,
+            env.block.time.seconds(),
+            window,
+        )?,
+        timestamp: env.block.time.seconds(),
+    };
+
+    leveraged_snapshot(storage, env, twap)
+}
+
+/**
+ * The current and TWAP snapshots ordered by how many leveraged assets one
+ * unleveraged asset buys, fewest first. Pricing at the first is the least
+ * favourable to minters and liquidity providers while pricing at the second
+ * is the least favourable to burners
+ */
+pub fn get_pricing_bounds(
+    deps: &Deps,
+    env: &Env,
+) -> StdResult<(PriceSnapshot, PriceSnapshot)> {
+    let hyper_p = HYPERPARAMETERS.load(deps.storage)?;
+    let current = get_price_context(deps.storage, deps.api, deps.querier, env)?
+        .current_snapshot;
+    let twap = get_twap_snapshot(
+        deps.storage,
+        deps.api,
+        deps.querier,
+        env,
+        hyper_p.twap_window,
+    )?;
+
+    /* Compare asset_price / leveraged_price of both snapshots */
+    let current_ratio = current.asset_price.full_mul(twap.leveraged_price);
+    let twap_ratio = twap.asset_price.full_mul(current.leveraged_price);
+
+    if current_ratio <= twap_ratio {
+        Ok((current, twap))
+    } else {
+        Ok((twap, current))
+    }
+}
+
+/**
+ * Snapshot of the price source's current reading
+ */
+fn observe(
+    storage: &dyn Storage,
+    api: &dyn Api,
+    querier: QuerierWrapper,
+    env: &Env,
+) -> Result<PriceSnapshot, ContractError> {
+    Ok(leveraged_snapshot(
+        storage,
+        env,
+        price_source(storage, api, env)?.query_observation(env, querier)?,
+    )?)
+}
+
+/**
+ * Whether the asset price moved further between `previous` and `next` than
+ * the circuit breaker allows
+ */
+fn circuit_breaker_trips(
+    hyper_p: &Hyperparameters,
+    previous: &PriceSnapshot,
+    next: &PriceSnapshot,
+) -> StdResult<bool> {
+    let threshold = match hyper_p.circuit_breaker {
+        Some(threshold) if !previous.asset_price.is_zero() => threshold,
+        _ => return Ok(false),
+    };
+
+    let high = previous.asset_price.max(next.asset_price);
+    let low = previous.asset_price.min(next.asset_price);
+    Ok(multiply_ratio(
+        high - low,
+        Uint128::from(PRECISION),
+        previous.asset_price,
+    )? > threshold)
+}
+
+/**
+ * Snapshot of the leveraged price at `asset_price`, relative to the price
+ * when leverage was last reset
+ */
+fn leveraged_snapshot(
+    storage: &dyn Storage,
+    env: &Env,
+    asset_price: TSPricePoint,
+) -> StdResult<PriceSnapshot> {
+    let hyper_p = HYPERPARAMETERS.load(storage)?;
+    let opening = POOLSTATE.load(storage)?.latest_reset_snapshot;
+
+    Ok(PriceSnapshot {
+        asset_price: asset_price.u_price,
+        leveraged_price: get_leveraged_price(
+            opening.asset_price,
+            asset_price.u_price,
+            hyper_p.leverage_amount,
+            hyper_p.direction,
+            opening.leveraged_price,
+            hyper_p.knock_out_floor,
+        ),
+        timestamp: env.block.time.seconds(),
+    })
+}
+
+/**
+ * The price source the pool was configured with
+ */
+fn price_source(
+    storage: &dyn Storage,
+    api: &dyn Api,
+    env: &Env,
+) -> StdResult<Box<dyn PriceSource>> {
+    let config = query_price_source_config(storage);
+
+    /* Only a TWAP needs the history within its window */
+    let observations = match config {
+        PriceSourceConfig::TerraswapTwap { window_seconds } => {
+            price_history_since(
+                storage,
+                env.block.time.seconds().saturating_sub(window_seconds),
+            )?
+        }
+        _ => Vec::new(),
+    };
+
+    build_price_source(
+        &HYPERPARAMETERS.load(storage)?,
+        &config,
+        api,
+        observations,
+    )
+}
+
+fn build_price_source(
+    hyper_p: &Hyperparameters,
+    config: &PriceSourceConfig,
+    api: &dyn Api,
+    observations: Vec<PriceSnapshot>,
+) -> StdResult<Box<dyn PriceSource>> {
+    let spot = TerraSwapSpot {
+        pair: api.addr_humanize(&hyper_p.terraswap_pair_addr)?,
+        asset: match backing_asset(hyper_p, api)? {
+            AssetInfo::Token { contract_addr } => {
+                Addr::unchecked(contract_addr)
+            }
+            AssetInfo::NativeToken { denom } => Addr::unchecked(denom),
+        },
+    };
+
+    Ok(match config {
+        PriceSourceConfig::TerraswapSpot {} => Box::new(spot),
+        PriceSourceConfig::TerraswapTwap { window_seconds } => {
+            Box::new(TerraSwapTwap {
+                spot,
+                window_seconds: *window_seconds,
+                observations,
+            })
+        }
+        PriceSourceConfig::PushOracle {
+            oracle_addr,
+            max_age_seconds,
+        } => Box::new(PushOracle {
+            oracle: api.addr_validate(oracle_addr)?,
+            asset: spot.asset,
+            max_age_seconds: *max_age_seconds,
+        }),
+    })
+}
+
+/**
+ * Pools deployed before price sources were configurable used the spot price
+ */
+pub fn query_price_source_config(storage: &dyn Storage) -> PriceSourceConfig {
+    PRICE_SOURCE
+        .may_load(storage)
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+}
+
+/**
+ * Inputs the opening price, leveraged amount, etc to calculate the current leveraged price
+ *
+ * Short pools move the leveraged price against the asset price, i.e. a 10%
+ * rise in the asset is a 20% drop in a -2x leveraged price
+ *
+ * The leveraged price never falls below `knock_out_floor` times the starting
+ * leveraged price, which is where positions are knocked out
+ */
+fn get_leveraged_price(
+    start_asset_price: Uint128,
     current_asset_price: Uint128,
     leverage_amount: Uint128,
+    direction: LeverageDirection,
     starting_leverage_price: Uint128,
+    knock_out_floor: Uint128,
 ) -> Uint128 {
     // If no change
     if start_asset_price == current_asset_price {
         return starting_leverage_price;
     }
 
-    // If asset increases in value
-    if start_asset_price < current_asset_price {
-        let absolute_change = current_asset_price - start_asset_price;
-        let percent_change = Uint128::new(1_000_000)
-            .saturating_mul(absolute_change)
-            / start_asset_price;
-        let leverage_percent_change = Uint128::new(1_000_000)
-            + leverage_amount.saturating_mul(percent_change)
-                / Uint128::new(1_000_000);
-
-        let current_leveraged_price = starting_leverage_price
-            .saturating_mul(leverage_percent_change)
-            / Uint128::new(1_000_000);
-        return current_leveraged_price;
-    }
-
-    // If asset decreases in value
-    if start_asset_price > current_asset_price {
-        let absolute_change = start_asset_price - current_asset_price;
-        let percent_change = Uint128::new(1_000_000)
-            .saturating_mul(absolute_change)
-            / start_asset_price;
-        let leverage_percent_change = Uint128::new(1_000_000)
-            - leverage_amount.saturating_mul(percent_change)
-                / Uint128::new(1_000_000);
-
-        let current_leveraged_price = starting_leverage_price
-            .saturating_mul(leverage_percent_change)
-            / Uint128::new(1_000_000);
-        return current_leveraged_price;
-    }
+    let asset_increased = start_asset_price < current_asset_price;
+    let absolute_change = if asset_increased {
+        current_asset_price - start_asset_price
+    } else {
+        start_asset_price - current_asset_price
+    };
+    let percent_change = Uint128::new(1_000_000)
+        .saturating_mul(absolute_change)
+        / start_asset_price;
+    let leveraged_change = leverage_amount.saturating_mul(percent_change)
+        / Uint128::new(1_000_000);
+
+    /* Long positions gain when the asset gains, short positions when it falls */
+    let leverage_percent_change = match (direction, asset_increased) {
+        (LeverageDirection::Long, true) | (LeverageDirection::Short, false) => {
+            Uint128::new(1_000_000) + leveraged_change
+        }
+        (LeverageDirection::Long, false) | (LeverageDirection::Short, true) => {
+            Uint128::new(1_000_000)
+                .saturating_sub(leveraged_change)
+                .max(knock_out_floor)
+        }
+    };
 
-    return Uint128::new(1_000_000);
+    starting_leverage_price.saturating_mul(leverage_percent_change)
+        / Uint128::new(1_000_000)
 }
 
 /**
  * Checks for valid hyperparameters
  */
 fn hyperparameters_is_valid(hyperparms: &Hyperparameters) -> bool {
-    if hyperparms.minimum_protocol_ratio > hyperparms.rebalance_premium {
+    /* Rebalancing kicks in below the minimum PR but positions stay backed */
+    if hyperparms.rebalance_ratio >= hyperparms.minimum_protocol_ratio {
+        return false;
+    }
+    if hyperparms.rebalance_ratio < Uint128::from(PRECISION) {
+        return false;
+    }
+    /* Liquidation is the last resort once rebalancing has not helped */
+    if hyperparms.liquidation_ratio.is_zero()
+        || hyperparms.liquidation_ratio > hyperparms.rebalance_ratio
+    {
         return false;
     }
-    if hyperparms.mint_premium > Uint128::new(1_000_000) {
+    if hyperparms.mint_premium > Uint128::new(MAX_MINT_PREMIUM) {
         return false;
     }
-    if hyperparms.rebalance_premium > Uint128::new(0_100_000) {
+    if hyperparms.rebalance_premium > Uint128::new(MAX_REBALANCE_PREMIUM) {
         return false;
     }
     if hyperparms.leverage_amount < Uint128::new(1_000_000) {
         return false;
     }
-    return true;
+    if hyperparms.twap_window == 0 {
+        return false;
+    }
+    if hyperparms.knock_out_floor.is_zero()
+        || hyperparms.knock_out_floor >= Uint128::from(PRECISION)
+    {
+        return false;
+    }
+    if hyperparms.circuit_breaker == Some(Uint128::zero()) {
+        return false;
+    }
+    /* A cap of nothing would never let anything in */
+    if [
+        hyperparms.max_total_leveraged_value,
+        hyperparms.max_assets_in_reserve,
+        hyperparms.max_position_per_address,
+    ]
+    .contains(&Some(Uint128::zero()))
+    {
+        return false;
+    }
+    if hyperparms.price_data_expiry == 0 || hyperparms.leverage_expiry == 0 {
+        return false;
+    }
+    if hyperparms.leverage_reset_offset >= hyperparms.leverage_expiry {
+        return false;
+    }
+    if hyperparms.price_data_n == 0
+        || hyperparms.price_data_n > MAX_PRICE_DATA_N
+    {
+        return false;
+    }
+    /* The recorded history has to cover the TWAP window */
+    if hyperparms
+        .price_data_n
+        .saturating_mul(hyperparms.price_data_expiry)
+        < hyperparms.twap_window
+    {
+        return false;
+    }
+    true
 }
 
 /**
- * Push an element onto the end of a vector and drop some of the front s/t
- * there are at most `usize` elements in the vector
- *
  * O(#￣▽￣)
  */
-fn push_drain<T>(v: &mut Vec<T>, append: T, max: usize) {
-    /* Stick onto the end of Vec */
-    v.push(append);
+fn price_timestamp_is_expired(
+    snapshot: &PriceSnapshot,
+    env: &Env,
+    hyper_p: &Hyperparameters,
+) -> bool {
+    let currently = env.block.time.seconds();
+    let timestamp = snapshot.timestamp;
 
-    if v.len() >= max {
-        /* Pop old data off the front */
-        v.drain(0..v.len() - max);
-    }
+    currently > timestamp && currently - timestamp >= hyper_p.price_data_expiry
 }
 
 /**
- * O(#￣▽￣)
+ * Leverage resets once per epoch of leverage_expiry seconds. Epochs are
+ * aligned to 00:00 UTC, shifted by leverage_reset_offset
  */
-fn price_timestamp_is_expired(snapshot: &PriceSnapshot, env: &Env) -> bool {
-    let currently = env.block.time.seconds();
-    let timestamp = snapshot.timestamp;
-
-    currently > timestamp && currently - timestamp >= PRICE_DATA_EXPIRY
+fn leverage_is_expired(
+    open: &PriceSnapshot,
+    env: &Env,
+    hyper_p: &Hyperparameters,
+) -> bool {
+    leverage_epoch_start(env.block.time.seconds(), hyper_p) > open.timestamp
 }
 
 /**
- * O(#￣▽￣)
+ * Latest epoch boundary at or before `time`. Any epochs missed since the
+ * previous reset are skipped over
  */
-fn leverage_is_expired(open: &PriceSnapshot, env: &Env) -> bool {
-    let currently = env.block.time.seconds();
-    let timestamp = open.timestamp;
+fn leverage_epoch_start(time: u64, hyper_p: &Hyperparameters) -> u64 {
+    let expiry = hyper_p.leverage_expiry;
+    let offset = hyper_p.leverage_reset_offset;
 
-    currently > timestamp && currently - timestamp >= LEVERAGE_EXPIRY
+    /* Seconds into the current epoch, offset < expiry */
+    time.saturating_sub((time + expiry - offset) % expiry)
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Hyperparameters {
     pub leverage_amount: Uint128,
+
+    /* Pools deployed before short pools existed are all long */
+    #[serde(default)]
+    pub direction: LeverageDirection,
+
     pub minimum_protocol_ratio: Uint128,
     pub rebalance_ratio: Uint128,
     pub mint_premium: Uint128,
     pub rebalance_premium: Uint128,
     pub terraswap_pair_addr: CanonicalAddr,
-    pub leveraged_asset_addr: CanonicalAddr,
+
+    /* Cw20 backing the pool, None for pools backed by a native coin */
+    pub leveraged_asset_addr: Option<CanonicalAddr>,
+
+    /* Native coin backing the pool in place of a Cw20 */
+    #[serde(default)]
+    pub leveraged_asset_denom: Option<String>,
+
+    /* Window of the TWAP which mints, burns and withdrawals are checked against */
+    #[serde(default = "default_twap_window")]
+    pub twap_window: u64,
+
+    /* Ratio of the reset leveraged price at which positions are knocked out */
+    #[serde(default = "default_knock_out_floor")]
+    pub knock_out_floor: Uint128,
+
+    /* Seconds between recorded price snapshots */
+    #[serde(default = "default_price_data_expiry")]
+    pub price_data_expiry: u64,
+
+    /* Seconds between leverage resets */
+    #[serde(default = "default_leverage_expiry")]
+    pub leverage_expiry: u64,
+
+    /* Seconds after 00:00 UTC at which the leverage epochs start */
+    #[serde(default)]
+    pub leverage_reset_offset: u64,
+
+    /* Number of price snapshots kept */
+    #[serde(default = "default_price_data_n")]
+    pub price_data_n: u64,
+
+    /* Backing assets paid out of the keeper budget per useful poke */
+    #[serde(default)]
+    pub keeper_reward: Uint128,
+
+    /* Protocol ratio below which the pool may be liquidated */
+    #[serde(default = "default_liquidation_ratio")]
+    pub liquidation_ratio: Uint128,
+
+    /* Asset price move between consecutive snapshots, as a ratio of the
+     * earlier price, beyond which the pool pauses itself */
+    #[serde(default)]
+    pub circuit_breaker: Option<Uint128>,
+
+    /* Caps in backing assets on the value of all minted positions, on the
+     * reserve and on the value of any one address's minted position */
+    #[serde(default)]
+    pub max_total_leveraged_value: Option<Uint128>,
+    #[serde(default)]
+    pub max_assets_in_reserve: Option<Uint128>,
+    #[serde(default)]
+    pub max_position_per_address: Option<Uint128>,
+}
+
+fn default_twap_window() -> u64 {
+    DEFAULT_TWAP_WINDOW
+}
+
+fn default_knock_out_floor() -> Uint128 {
+    Uint128::new(DEFAULT_KNOCK_OUT_FLOOR)
+}
+
+fn default_price_data_expiry() -> u64 {
+    DEFAULT_PRICE_DATA_EXPIRY
+}
+
+fn default_leverage_expiry() -> u64 {
+    DEFAULT_LEVERAGE_EXPIRY
+}
+
+fn default_liquidation_ratio() -> Uint128 {
+    Uint128::new(DEFAULT_LIQUIDATION_RATIO)
+}
+
+fn default_price_data_n() -> u64 {
+    DEFAULT_PRICE_DATA_N
 }
 
 /**
- * Fetch a new price about every 15 minutes
- * TODO Use
+ * Mint premium may be at most 1%
  */
-const PRICE_DATA_EXPIRY: u64 = 15 * 60;
+const MAX_MINT_PREMIUM: u128 = PREMIUM_PRECISION / 100;
 
 /**
- * Reset leverage after 24 hours
- * TODO Use
+ * Rebalance premium may be at most 10%
  */
-const LEVERAGE_EXPIRY: u64 = 24 * 60 * 60;
+const MAX_REBALANCE_PREMIUM: u128 = PREMIUM_PRECISION / 10;
 
 /**
- * Keep 90 days of price data at the 15-minute resolution
- * TODO Use
+ * Governance may move a protocol ratio by at most 0.25 per update
  */
-const PRICE_DATA_N: usize = 90 * 24 * 4;
+const MAX_RATIO_DELTA: u128 = PRECISION / 4;
 
 /**
- * Tracking minted leveraged assets and their unleveraged friends
+ * Governance may move a premium by at most 0.5% per update
  */
-pub const MINTSTATE: Map<&Addr, Uint128> = Map::new("minted_partial_shares");
+const MAX_PREMIUM_DELTA: u128 = PREMIUM_PRECISION / 200;
 
 /**
- * Tracking minted leveraged assets and their unleveraged friends
+ * Check mints, burns and withdrawals against a 1 hour TWAP by default
  */
-pub const LIQUIDITYSTATE: Map<&Addr, Uint128> =
-    Map::new("liquidity_partial_shares");
+const DEFAULT_TWAP_WINDOW: u64 = 60 * 60;
+
+/**
+ * Knock positions out once they have lost 95% of their value since the last
+ * leverage reset by default
+ */
+const DEFAULT_KNOCK_OUT_FLOOR: u128 = PRECISION / 20;
+
+/**
+ * Fetch a new price about every 15 minutes by default
+ */
+const DEFAULT_PRICE_DATA_EXPIRY: u64 = 15 * 60;
+
+/**
+ * Reset leverage after 24 hours by default
+ */
+const DEFAULT_LEVERAGE_EXPIRY: u64 = 24 * 60 * 60;
+
+/**
+ * Keep 90 days of price data at the 15-minute resolution by default
+ */
+const DEFAULT_PRICE_DATA_N: u64 = 90 * 24 * 4;
+
+/**
+ * Keep at most a year of price data at the 15-minute resolution
+ */
+const MAX_PRICE_DATA_N: u64 = 365 * 24 * 4;
+
+/**
+ * Pools may be liquidated once the reserve no longer covers minted positions
+ * by default
+ */
+const DEFAULT_LIQUIDATION_RATIO: u128 = PRECISION;
+
+/**
+ * Keep 15 minute candles for a week, hourly candles for 90 days and daily
+ * candles for a year
+ */
+const CANDLE_RETENTION: [(CandleResolution, u64); 3] = [
+    (CandleResolution::FifteenMinutes, 7 * 24 * 60 * 60),
+    (CandleResolution::OneHour, 90 * 24 * 60 * 60),
+    (CandleResolution::OneDay, 365 * 24 * 60 * 60),
+];
+
+/**
+ * Price history pages hold 100 snapshots unless asked for up to 500
+ */
+const DEFAULT_HISTORY_LIMIT: u32 = 100;
+const MAX_HISTORY_LIMIT: u32 = 500;
+
 /**
- * Historic price data
+ * Cw20 whose balances are the minted leveraged positions
  */
-const PRICE_DATA: Item<Vec<PriceSnapshot>> = Item::new("price_data");
+const LEVERAGED_TOKEN: Item<CanonicalAddr> = Item::new("leveraged_token");
 
 /**
- * Parameters which are (currently) never changed. Some parameters may be open
- * to adjustment within a tolerance via governance votes (TODO)
+ * Cw20 whose balances are the provided liquidity positions
+ */
+const LIQUIDITY_TOKEN: Item<CanonicalAddr> = Item::new("liquidity_token");
+
+/**
+ * Lifetime fee totals
+ */
+const FEES_COLLECTED: Item<FeesCollected> = Item::new("fees_collected");
+
+/**
+ * Where the asset price comes from
+ */
+const PRICE_SOURCE: Item<PriceSourceConfig> = Item::new("price_source");
+
+/**
+ * Zap-in mint waiting on its swap, only ever set within a single transaction
+ */
+const PENDING_ZAP: Item<PendingZap> = Item::new("pending_zap");
+
+/**
+ * Zap-out payout waiting on its swap, only ever set within a single
+ * transaction
+ */
+const PENDING_ZAP_OUT: Item<PendingZapOut> = Item::new("pending_zap_out");
+
+/**
+ * Backing assets owed to positions once the pool is liquidated, or to
+ * positions alone once they are knocked out
+ */
+const SETTLEMENT: Item<Settlement> = Item::new("settlement");
+
+/**
+ * Historic price data keyed by timestamp
+ */
+const PRICE_DATA: Map<U64Key, PriceSnapshot> = Map::new("price_snapshots");
+
+/**
+ * OHLC candles keyed by (width in seconds, open time)
+ */
+const CANDLES: Map<(U64Key, U64Key), Candle> = Map::new("candles");
+
+/**
+ * Number of snapshots in PRICE_DATA
+ */
+const PRICE_DATA_LEN: Item<u64> = Item::new("price_snapshots_len");
+
+/**
+ * PRICE_DATA as it was laid out up to 0.1.0
+ */
+pub(crate) const LEGACY_PRICE_DATA: Item<Vec<PriceSnapshot>> =
+    Item::new("price_data");
+
+/**
+ * Parameters fixed at init, except for the ratios and premiums which
+ * governance may adjust within a tolerance
  */
 const HYPERPARAMETERS: Item<Hyperparameters> = Item::new("hyperparameters");
 
+/**
+ * Every adjustment governance made to the hyperparameters, numbered from 0
+ */
+const HYPERPARAMETER_CHANGES: Map<U64Key, HyperparameterChange> =
+    Map::new("hyperparameter_change_log");
+
+/**
+ * Number of changes in HYPERPARAMETER_CHANGES
+ */
+const HYPERPARAMETER_CHANGES_LEN: Item<u64> =
+    Item::new("hyperparameter_change_log_len");
+
 /**
  * Tracking minted leveraged assets and their unleveraged friends
  */
 const POOLSTATE: Item<PoolState> = Item::new("pool_state");
 
+/**
+ * PoolState as it was laid out up to 0.1.0
+ */
+pub(crate) const LEGACY_POOLSTATE: Item<LegacyPoolState> =
+    Item::new("pool_state");
+
+/**
+ * Minted shares per address, replaced by the leveraged token after 0.1.0
+ */
+pub(crate) const LEGACY_MINTSTATE: Map<&Addr, Uint128> =
+    Map::new("minted_partial_shares");
+
+/**
+ * Provided shares per address, replaced by the liquidity token after 0.1.0
+ */
+pub(crate) const LEGACY_LIQUIDITYSTATE: Map<&Addr, Uint128> =
+    Map::new("liquidity_partial_shares");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct FeesCollected {
+    /**
+     * Mint premium retained in assets_in_reserve for liquidity providers
+     */
+    pub mint_premium: Uint128,
+}
+
 // pub struct ProviderPosition {
 //     pub asset_pool_partial_share: Uint128,
 //     pub asset_pool_total_share: Uint128,
@@ -646,8 +2292,8 @@ pub struct PoolState {
     pub total_asset_pool_share: Uint128,
 
     /**
-     * Total share of all minted leveraged assets
-     * TODO remove in favor of total_leveraged_pool_share
+     * Leveraged assets backing all minted positions. Equal to
+     * total_leveraged_pool_share until a rebalance shrinks the positions
      */
     pub total_leveraged_assets: Uint128,
 
@@ -655,23 +2301,246 @@ pub struct PoolState {
      * Total share of all minted leveraged assets
      */
     pub total_leveraged_pool_share: Uint128,
+
+    /**
+     * Address allowed to adjust hyperparameters
+     */
+    pub governance_addr: CanonicalAddr,
+
+    /**
+     * Factory which instantiated the pool and may reset its leverage. Pools
+     * migrated from before it was recorded have none
+     */
+    #[serde(default)]
+    pub factory_addr: Option<CanonicalAddr>,
+
+    /**
+     * Backing assets set aside to reward keepers, not part of the reserve
+     */
+    #[serde(default)]
+    pub keeper_budget: Uint128,
+
+    /**
+     * Address allowed to pause the pool alongside governance
+     */
+    #[serde(default)]
+    pub guardian_addr: Option<CanonicalAddr>,
+
+    /**
+     * What the pool currently accepts
+     */
+    #[serde(default)]
+    pub status: PoolStatus,
+}
+
+/**
+ * Mint opened from the proceeds of a swap once the swap replies
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PendingZap {
+    pub minter: Addr,
+    pub limits: SlippageLimits,
+
+    /* Pool's balance of the backing asset before the swap */
+    pub balance_before: Uint128,
+}
+
+/**
+ * Swap proceeds forwarded to a burner or provider once the swap replies
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PendingZapOut {
+    pub recipient: Addr,
+    pub min_output: Option<Uint128>,
+
+    /* Asset the swap pays out in and the pool's balance of it beforehand */
+    pub stable: AssetInfo,
+    pub balance_before: Uint128,
+}
+
+/**
+ * Reserve split between minters and providers when the pool was liquidated,
+ * each paid out pro rata to their remaining share
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Settlement {
+    pub settled_at: PriceSnapshot,
+    pub leveraged_assets: Uint128,
+    pub leveraged_pool_share: Uint128,
+    pub liquidity_assets: Uint128,
+    pub asset_pool_share: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LegacyPoolState {
+    pub latest_reset_snapshot: PriceSnapshot,
+    pub latest_historic_snapshot: PriceSnapshot,
+    pub assets_in_reserve: Uint128,
+    pub total_asset_pool_share: Uint128,
+    pub total_leveraged_assets: Uint128,
+    pub total_leveraged_pool_share: Uint128,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use cosmwasm_std::testing::{mock_env, MockStorage};
+    use cosmwasm_std::Timestamp;
+
+    fn snapshot_at(timestamp: u64) -> PriceSnapshot {
+        PriceSnapshot {
+            asset_price: Uint128::new(1_000_000),
+            leveraged_price: Uint128::new(1_000_000),
+            timestamp,
+        }
+    }
+
+    fn recorded_timestamps(storage: &dyn Storage) -> Vec<u64> {
+        PRICE_DATA
+            .range(storage, None, None, Order::Ascending)
+            .map(|item| item.unwrap().1.timestamp)
+            .collect()
+    }
 
     #[test]
-    fn push_drain_max() {
-        let mut v = vec![0];
-        push_drain(&mut v, 10, 3);
-        assert_eq!(v.len(), 2);
-        push_drain(&mut v, 11, 3);
-        assert_eq!(v.len(), 3);
-        push_drain(&mut v, 12, 3);
-        assert_eq!(v.len(), 3);
-        push_drain(&mut v, 13, 3);
-        assert_eq!(v.len(), 3);
+    fn append_price_snapshot_max() {
+        let mut storage = MockStorage::new();
+        append_price_snapshot(&mut storage, snapshot_at(0), 3).unwrap();
+        assert_eq!(recorded_timestamps(&storage), vec![0]);
+        append_price_snapshot(&mut storage, snapshot_at(10), 3).unwrap();
+        append_price_snapshot(&mut storage, snapshot_at(11), 3).unwrap();
+        assert_eq!(recorded_timestamps(&storage), vec![0, 10, 11]);
+        append_price_snapshot(&mut storage, snapshot_at(12), 3).unwrap();
+        assert_eq!(recorded_timestamps(&storage), vec![10, 11, 12]);
+
+        /* Recording the same timestamp twice replaces the snapshot */
+        append_price_snapshot(&mut storage, snapshot_at(12), 3).unwrap();
+        assert_eq!(recorded_timestamps(&storage), vec![10, 11, 12]);
+        assert_eq!(PRICE_DATA_LEN.load(&storage).unwrap(), 3);
+    }
+
+    #[test]
+    fn price_history_since_window() {
+        let mut storage = MockStorage::new();
+        for timestamp in [0, 10, 20, 30] {
+            append_price_snapshot(&mut storage, snapshot_at(timestamp), 10)
+                .unwrap();
+        }
+
+        /* The snapshot at 10 still held at 15 */
+        let history = price_history_since(&storage, 15).unwrap();
+        let timestamps: Vec<u64> =
+            history.iter().map(|s| s.timestamp).collect();
+        assert_eq!(timestamps, vec![10, 20, 30]);
+
+        let history = price_history_since(&storage, 40).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].timestamp, 30);
+    }
+
+    #[test]
+    fn candles_downsample() {
+        let mut storage = MockStorage::new();
+        let hour = CandleResolution::OneHour.seconds();
+        for (timestamp, price) in [
+            (3600, 100),
+            (4500, 120),
+            (5400, 90),
+            (6300, 110),
+            (7200, 105),
+        ] {
+            let snapshot = PriceSnapshot {
+                asset_price: Uint128::new(price),
+                leveraged_price: Uint128::new(2 * price),
+                timestamp,
+            };
+            append_price_snapshot(&mut storage, snapshot, 10).unwrap();
+        }
+
+        let candle = CANDLES
+            .load(&storage, (U64Key::new(hour), U64Key::new(3600)))
+            .unwrap();
+        assert_eq!(candle.open_time, 3600);
+        assert_eq!(candle.asset.open, Uint128::new(100));
+        assert_eq!(candle.asset.high, Uint128::new(120));
+        assert_eq!(candle.asset.low, Uint128::new(90));
+        assert_eq!(candle.asset.close, Uint128::new(110));
+        assert_eq!(candle.leveraged.high, Uint128::new(240));
+
+        let candle = CANDLES
+            .load(&storage, (U64Key::new(hour), U64Key::new(7200)))
+            .unwrap();
+        assert_eq!(candle.asset, Ohlc::opened_at(Uint128::new(105)));
+
+        /* 15 minute candles age out after a week */
+        let fifteen = CandleResolution::FifteenMinutes.seconds();
+        let snapshot = PriceSnapshot {
+            timestamp: 3600 + 7 * 24 * 60 * 60 + fifteen,
+            ..snapshot_at(0)
+        };
+        append_price_snapshot(&mut storage, snapshot, 10).unwrap();
+        assert!(
+            !CANDLES.has(&storage, (U64Key::new(fifteen), U64Key::new(3600)))
+        );
+        assert!(CANDLES.has(&storage, (U64Key::new(hour), U64Key::new(3600))));
+    }
+
+    fn hyperparameters() -> Hyperparameters {
+        Hyperparameters {
+            leverage_amount: Uint128::new(2_000_000),
+            direction: LeverageDirection::Long,
+            minimum_protocol_ratio: Uint128::new(2_500_000),
+            rebalance_ratio: Uint128::new(2_000_000),
+            mint_premium: Uint128::zero(),
+            rebalance_premium: Uint128::zero(),
+            terraswap_pair_addr: CanonicalAddr::from(vec![1]),
+            leveraged_asset_addr: Some(CanonicalAddr::from(vec![2])),
+            leveraged_asset_denom: None,
+            twap_window: DEFAULT_TWAP_WINDOW,
+            knock_out_floor: Uint128::new(DEFAULT_KNOCK_OUT_FLOOR),
+            price_data_expiry: DEFAULT_PRICE_DATA_EXPIRY,
+            leverage_expiry: DEFAULT_LEVERAGE_EXPIRY,
+            leverage_reset_offset: 14 * 60 * 60,
+            price_data_n: DEFAULT_PRICE_DATA_N,
+            keeper_reward: Uint128::zero(),
+            liquidation_ratio: Uint128::new(DEFAULT_LIQUIDATION_RATIO),
+            circuit_breaker: None,
+            max_total_leveraged_value: None,
+            max_assets_in_reserve: None,
+            max_position_per_address: None,
+        }
+    }
+
+    #[test]
+    fn leverage_epochs() {
+        let hyper_p = hyperparameters();
+        let day = 24 * 60 * 60;
+
+        /* Epochs start at 14:00 UTC */
+        assert_eq!(leverage_epoch_start(10 * day, &hyper_p), 9 * day + 50400);
+        assert_eq!(
+            leverage_epoch_start(10 * day + 50400, &hyper_p),
+            10 * day + 50400
+        );
+        assert_eq!(
+            leverage_epoch_start(10 * day + 50399, &hyper_p),
+            9 * day + 50400
+        );
+
+        /* Missing several epochs resets at the latest boundary */
+        let mut env = mock_env();
+        let open = PriceSnapshot {
+            timestamp: 9 * day + 60,
+            ..snapshot_at(0)
+        };
+        env.block.time = Timestamp::from_seconds(9 * day + 50399);
+        assert!(!leverage_is_expired(&open, &env, &hyper_p));
+        env.block.time = Timestamp::from_seconds(13 * day + 3600);
+        assert!(leverage_is_expired(&open, &env, &hyper_p));
+        assert_eq!(
+            leverage_epoch_start(env.block.time.seconds(), &hyper_p),
+            12 * day + 50400
+        );
     }
 
     #[test]
@@ -685,7 +2554,9 @@ mod tests {
             starting_price,
             end_price,
             leverage_amount,
+            LeverageDirection::Long,
             leverage_start_price,
+            Uint128::new(DEFAULT_KNOCK_OUT_FLOOR),
         );
         assert_eq!(Uint128::new(2_000_000), leverage_end_price);
 
@@ -698,21 +2569,75 @@ mod tests {
             starting_price,
             end_price,
             leverage_amount,
+            LeverageDirection::Long,
             leverage_start_price,
+            Uint128::new(DEFAULT_KNOCK_OUT_FLOOR),
         );
         assert_eq!(Uint128::new(2_500_000), leverage_end_price);
 
         // Testing 10% decrease in price with 3x leverage
         let starting_price = Uint128::new(1_000_000);
-        let end_price = Uint128::new(0_900_000);
+        let end_price = Uint128::new(900_000);
         let leverage_amount = Uint128::new(3_000_000);
         let leverage_start_price = Uint128::new(1_000_000);
         let leverage_end_price = get_leveraged_price(
             starting_price,
             end_price,
             leverage_amount,
+            LeverageDirection::Long,
             leverage_start_price,
+            Uint128::new(DEFAULT_KNOCK_OUT_FLOOR),
+        );
+        assert_eq!(Uint128::new(700_000), leverage_end_price);
+    }
+
+    #[test]
+    fn proper_percent_inverse() {
+        // Testing 10% increase with -2x leverage
+        let leverage_end_price = get_leveraged_price(
+            Uint128::new(1_000_000),
+            Uint128::new(1_100_000),
+            Uint128::new(2_000_000),
+            LeverageDirection::Short,
+            Uint128::new(1_000_000),
+            Uint128::new(DEFAULT_KNOCK_OUT_FLOOR),
+        );
+        assert_eq!(Uint128::new(800_000), leverage_end_price);
+
+        // Testing 10% decrease with -3x leverage
+        let leverage_end_price = get_leveraged_price(
+            Uint128::new(1_000_000),
+            Uint128::new(900_000),
+            Uint128::new(3_000_000),
+            LeverageDirection::Short,
+            Uint128::new(1_000_000),
+            Uint128::new(DEFAULT_KNOCK_OUT_FLOOR),
+        );
+        assert_eq!(Uint128::new(1_300_000), leverage_end_price);
+    }
+
+    #[test]
+    fn knock_out_floor() {
+        // Testing 60% decrease with 2x leverage stops at the 5% floor
+        let leverage_end_price = get_leveraged_price(
+            Uint128::new(1_000_000),
+            Uint128::new(400_000),
+            Uint128::new(2_000_000),
+            LeverageDirection::Long,
+            Uint128::new(1_000_000),
+            Uint128::new(DEFAULT_KNOCK_OUT_FLOOR),
+        );
+        assert_eq!(Uint128::new(50_000), leverage_end_price);
+
+        // Testing 40% increase with -3x leverage
+        let leverage_end_price = get_leveraged_price(
+            Uint128::new(1_000_000),
+            Uint128::new(1_400_000),
+            Uint128::new(3_000_000),
+            LeverageDirection::Short,
+            Uint128::new(1_000_000),
+            Uint128::new(DEFAULT_KNOCK_OUT_FLOOR),
         );
-        assert_eq!(Uint128::new(0_700_000), leverage_end_price);
+        assert_eq!(Uint128::new(50_000), leverage_end_price);
     }
 }
@@ -1,8 +1,9 @@
 pub mod contract;
 mod error;
 pub mod msg;
+pub mod response;
 pub mod state;
-pub mod swap;
+pub mod token;
 
 /* MAGI System */
 pub mod leverage_man;
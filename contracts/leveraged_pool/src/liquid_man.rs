@@ -4,15 +4,16 @@
  * Provides liquidity deposits and withdrawals
  */
 use crate::error::ContractError;
-use crate::leverage_man;
-use cosmwasm_std::entry_point;
+use crate::{leverage_man, mint_man, token};
 use cosmwasm_std::{
-    to_binary, CosmosMsg, DepsMut, Env, MessageInfo, Reply, Response, StdError,
-    StdResult, Uint128, WasmMsg,
+    to_binary, Addr, CosmosMsg, Deps, DepsMut, Env, MessageInfo, Reply,
+    Response, StdResult, SubMsg, Uint128, WasmMsg,
+};
+use leveraged_pools::pool::{
+    multiply_ratio, PriceSnapshot, ProvideLiquidityMsg, WithdrawLiquidityMsg,
 };
-use leveraged_pools::pool::ProvideLiquidityMsg;
 
-use cw20::Cw20ExecuteMsg;
+use cw20::{Cw20Coin, Cw20ExecuteMsg};
 
 /**
  * Represent how much of the pool an LP has provided
@@ -22,85 +23,183 @@ pub struct ProviderPosition {
     pub asset_pool_total_share: Uint128,
 }
 
-pub fn try_execute_provide_liquidity(
-    deps: DepsMut,
-    _info: MessageInfo,
-    env: &Env,
-    msg: ProvideLiquidityMsg,
-) -> Result<Response, ContractError> {
-    let mut pool_state = leverage_man::get_pool_state(&deps.as_ref())?;
-    let provider_position =
-        leverage_man::get_liquidity_position(&deps.as_ref(), &msg.sender)?;
-
-    let liquidity_value_added = msg.amount;
-    let new_provider_position =
-        provider_position.asset_pool_partial_share + liquidity_value_added;
+/**
+ * Reply ID of the submessage instantiating the liquidity token
+ */
+pub const LIQUIDITY_TOKEN_REPLY_ID: u64 = 2;
 
-    pool_state.assets_in_reserve = pool_state.assets_in_reserve + msg.amount;
-    pool_state.total_asset_pool_share =
-        pool_state.total_asset_pool_share + liquidity_value_added;
+/**
+ * Instantiate the Cw20 representing this pool's liquidity positions
+ */
+pub fn instantiate_liquidity_token(
+    env: &Env,
+    token_code_id: u64,
+    initial_balances: Vec<Cw20Coin>,
+) -> StdResult<SubMsg> {
+    token::instantiate_pool_token(
+        env,
+        token_code_id,
+        "leveraged pool liquidity token",
+        "uLP",
+        initial_balances,
+        LIQUIDITY_TOKEN_REPLY_ID,
+    )
+}
 
-    leverage_man::update_pool_share(
-        deps.storage,
-        &msg.sender,
-        &new_provider_position,
-    )?;
-    leverage_man::update_pool_state(deps.storage, pool_state)?;
+/**
+ * Record the address of the freshly instantiated liquidity token
+ */
+pub fn register_liquidity_token(
+    deps: DepsMut,
+    msg: Reply,
+) -> Result<Response, ContractError> {
+    let token_addr = token::parse_instantiated_addr(msg)?;
 
-    leverage_man::check_reset_leverage(
+    leverage_man::set_liquidity_token_addr(
         deps.storage,
         deps.api,
-        deps.querier,
-        env,
+        &token_addr,
     )?;
 
-    Ok(Response::new())
+    Ok(Response::new()
+        .add_attribute("liquidity_token_addr", token_addr.to_string()))
 }
 
-pub fn execute_withdraw_liquidity(
+/**
+ * Issue liquidity pool share in proportion to what the deposit adds to the
+ * value of the liquidity pool, so that earlier providers keep the premiums
+ * already earned
+ */
+pub fn try_execute_provide_liquidity(
     deps: DepsMut,
-    info: MessageInfo,
+    _info: MessageInfo,
     env: &Env,
-    requested_share_of_pool: Uint128,
+    msg: ProvideLiquidityMsg,
 ) -> Result<Response, ContractError> {
-    let provider_position =
-        leverage_man::get_liquidity_position(&deps.as_ref(), &info.sender)?;
+    let mut pool_state = leverage_man::get_pool_state(&deps.as_ref())?;
+    let liquidity_token =
+        leverage_man::get_liquidity_token_addr(&deps.as_ref())?;
 
-    // If requesting more than put into the pool
+    /* Value the pool as withdrawals do, but at whichever of the current
+     * price and TWAP leaves the most for liquidity providers */
+    let liquidity_value_added = if pool_state.total_asset_pool_share.is_zero() {
+        msg.amount
+    } else {
+        let (_, pricing) =
+            leverage_man::get_pricing_bounds(&deps.as_ref(), env)?;
+        let pool_value = liquidity_pool_value(&pool_state, &pricing);
+        if pool_value.is_zero() {
+            return Err(ContractError::NoLiquidityValue {});
+        }
+        multiply_ratio(
+            msg.amount,
+            pool_state.total_asset_pool_share,
+            pool_value,
+        )?
+    };
 
+    /* Stay within the pool's reserve cap */
     let hyper_p = leverage_man::query_hyperparameters(&deps.as_ref())?;
-
-    if requested_share_of_pool > provider_position.asset_pool_partial_share {
-        return Err(ContractError::InsufficientFunds {});
+    if let Some(cap) = hyper_p.max_assets_in_reserve {
+        let remaining = cap.saturating_sub(pool_state.assets_in_reserve);
+        if msg.amount > remaining {
+            return Err(ContractError::ReserveCapExceeded {
+                remaining,
+                requested: msg.amount,
+            });
+        }
     }
 
-    let mut pool_state = leverage_man::get_pool_state(&deps.as_ref())?;
+    pool_state.assets_in_reserve += msg.amount;
+    pool_state.total_asset_pool_share += liquidity_value_added;
 
-    if !leverage_man::addr_has_adequate_lp_share(
-        &deps.as_ref(),
-        &info.sender,
-        requested_share_of_pool,
-    ) {
-        Err(ContractError::InsufficientFunds {})?;
-    }
+    leverage_man::update_pool_state(deps.storage, pool_state)?;
 
-    let price_context = leverage_man::get_price_context(
+    /* The provided share is represented by the liquidity token */
+    let mint_msg = CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: liquidity_token.to_string(),
+        funds: vec![],
+        msg: to_binary(&Cw20ExecuteMsg::Mint {
+            recipient: msg.sender.to_string(),
+            amount: liquidity_value_added,
+        })?,
+    });
+
+    leverage_man::check_reset_leverage(
         deps.storage,
         deps.api,
         deps.querier,
         env,
     )?;
 
+    Ok(Response::new().add_message(mint_msg).add_attributes(vec![
+        ("action", "provide_liquidity"),
+        ("provider", msg.sender.as_str()),
+        ("deposit_amount", &msg.amount.to_string()),
+        ("share", &liquidity_value_added.to_string()),
+    ]))
+}
+
+/**
+ * Backing assets left to liquidity providers once minted positions are
+ * valued at `pricing`, zero if they are worth more than the whole reserve
+ */
+pub fn liquidity_pool_value(
+    pool_state: &leverage_man::PoolState,
+    pricing: &PriceSnapshot,
+) -> Uint128 {
     let total_asset_value = pool_state
         .assets_in_reserve
-        .saturating_mul(price_context.current_snapshot.asset_price);
+        .saturating_mul(pricing.asset_price);
     let total_minted_value = pool_state
         .total_leveraged_assets
-        .saturating_mul(price_context.current_snapshot.leveraged_price);
+        .saturating_mul(pricing.leveraged_price);
 
-    let total_liq_pool_value = total_asset_value - total_minted_value;
-    let available_pool_tokens =
-        total_liq_pool_value / price_context.current_snapshot.asset_price;
+    total_asset_value
+        .checked_sub(total_minted_value)
+        .unwrap_or_default()
+        / pricing.asset_price
+}
+
+/**
+ * Outcome of withdrawing some liquidity pool share at the current prices
+ */
+pub struct WithdrawQuote {
+    /* Backing assets paid out */
+    pub claimed_units: Uint128,
+
+    /* Prices minted positions are valued at */
+    pub pricing: PriceSnapshot,
+
+    /* Protocol ratio once the share is withdrawn, None if nothing is minted */
+    pub protocol_ratio: Option<Uint128>,
+
+    /* Whether the protocol ratio stays at or above the minimum */
+    pub is_healthy: bool,
+}
+
+/**
+ * Price withdrawing `requested_share_of_pool`, shared by the withdrawal itself
+ * and its simulation
+ */
+pub fn quote_withdraw(
+    deps: &Deps,
+    env: &Env,
+    requested_share_of_pool: Uint128,
+) -> Result<WithdrawQuote, ContractError> {
+    let hyper_p = leverage_man::query_hyperparameters(deps)?;
+    let pool_state = leverage_man::get_pool_state(deps)?;
+
+    // If requesting more than put into the pool
+    if requested_share_of_pool > pool_state.total_asset_pool_share {
+        return Err(ContractError::InsufficientFunds {});
+    }
+
+    /* Value minted positions at whichever of the current price and TWAP
+     * leaves the least for liquidity providers */
+    let (pricing, _) = leverage_man::get_pricing_bounds(deps, env)?;
+
+    let available_pool_tokens = liquidity_pool_value(&pool_state, &pricing);
 
     let percent_pool_requested = Uint128::new(1_000_000)
         .saturating_mul(requested_share_of_pool)
@@ -109,42 +208,88 @@ pub fn execute_withdraw_liquidity(
         .saturating_mul(percent_pool_requested)
         / Uint128::new(1_000_000);
 
-    if pool_state.total_leveraged_pool_share > Uint128::zero()
-        && leverage_man::calculate_pr(
-            &deps.as_ref(),
-            env,
-            pool_state.assets_in_reserve - claimed_units,
-            pool_state.total_leveraged_pool_share,
-        )? < hyper_p.minimum_protocol_ratio
-    {
+    let protocol_ratio = if pool_state.total_leveraged_assets.is_zero() {
+        None
+    } else {
+        Some(leverage_man::calculate_pr_at(
+            &pricing,
+            pool_state
+                .assets_in_reserve
+                .checked_sub(claimed_units)
+                .map_err(|_| ContractError::InsufficientFunds {})?,
+            pool_state.total_leveraged_assets,
+        )?)
+    };
+
+    /* Nothing left minted is always healthy */
+    let protocol_ratio_is_healthy = match protocol_ratio {
+        Some(pr) => pr >= hyper_p.minimum_protocol_ratio,
+        None => true,
+    };
+
+    Ok(WithdrawQuote {
+        claimed_units,
+        pricing,
+        protocol_ratio,
+        is_healthy: protocol_ratio_is_healthy,
+    })
+}
+
+/**
+ * Redeem liquidity tokens which have already been sent to the pool for their
+ * share of the backing assets not covering minted positions
+ */
+pub fn execute_withdraw_liquidity(
+    mut deps: DepsMut,
+    _info: MessageInfo,
+    env: &Env,
+    msg: WithdrawLiquidityMsg,
+) -> Result<Response, ContractError> {
+    let liquidity_token =
+        leverage_man::get_liquidity_token_addr(&deps.as_ref())?;
+    let requested_share_of_pool = msg.share_of_pool;
+
+    let quote = quote_withdraw(&deps.as_ref(), env, requested_share_of_pool)?;
+    let claimed_units = quote.claimed_units;
+
+    leverage_man::check_slippage(
+        env,
+        &msg.limits,
+        claimed_units,
+        quote.pricing.leveraged_price,
+    )?;
+
+    if !quote.is_healthy {
         return Err(ContractError::WouldViolatePoolHealth {});
     }
 
-    let new_provider_position =
-        provider_position.asset_pool_partial_share - requested_share_of_pool;
-    pool_state.assets_in_reserve -= claimed_units;
+    let mut pool_state = leverage_man::get_pool_state(&deps.as_ref())?;
+    pool_state.assets_in_reserve = pool_state
+        .assets_in_reserve
+        .checked_sub(claimed_units)
+        .map_err(|_| ContractError::InsufficientFunds {})?;
     pool_state.total_asset_pool_share -= requested_share_of_pool;
 
     // Update Pool State
     leverage_man::update_pool_state(deps.storage, pool_state)?;
-    leverage_man::update_pool_share(
-        deps.storage,
-        &info.sender,
-        &new_provider_position,
-    )?;
 
-    let request_tokens_msg = CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: deps
-            .api
-            .addr_humanize(&hyper_p.leveraged_asset_addr)?
-            .to_string(),
+    /* Destroy the liquidity tokens the pool received */
+    let burn_msg = CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: liquidity_token.to_string(),
         funds: vec![],
-        msg: to_binary(&Cw20ExecuteMsg::Transfer {
-            recipient: info.sender.to_string(),
-            amount: claimed_units,
+        msg: to_binary(&Cw20ExecuteMsg::Burn {
+            amount: requested_share_of_pool,
         })?,
     });
 
+    let request_tokens_msg = mint_man::redeem_as(
+        &mut deps,
+        env,
+        &msg.sender,
+        claimed_units,
+        &msg.receive_as,
+    )?;
+
     leverage_man::check_reset_leverage(
         deps.storage,
         deps.api,
@@ -152,21 +297,54 @@ pub fn execute_withdraw_liquidity(
         env,
     )?;
 
-    Ok(Response::new().add_message(request_tokens_msg))
+    Ok(Response::new()
+        .add_message(burn_msg)
+        .add_submessage(request_tokens_msg)
+        .add_attributes(vec![
+            ("action", "withdraw_liquidity"),
+            ("provider", msg.sender.as_str()),
+            ("share", &requested_share_of_pool.to_string()),
+            ("withdraw_amount", &claimed_units.to_string()),
+        ]))
 }
 
-/// This just stores the result for future query
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn reply(_deps: DepsMut, _env: Env, msg: Reply) -> StdResult<Response> {
-    if true {
-        return Err(StdError::generic_err("Reply was successful but wtf"));
-    }
+/**
+ * Burn `share` of a liquidated pool's liquidity for its part of the
+ * settlement. The liquidity tokens have already been sent to the pool
+ */
+pub fn execute_claim_settlement(
+    deps: DepsMut,
+    sender: &Addr,
+    share: Uint128,
+) -> Result<Response, ContractError> {
+    let liquidity_token =
+        leverage_man::get_liquidity_token_addr(&deps.as_ref())?;
+    let claimed =
+        leverage_man::claim_liquidity_settlement(deps.storage, share)?;
 
-    match msg.id {
-        1 => {
-            // Err(StdError::generic_err("Execute message failed"))
-            Ok(Response::new())
-        }
-        _ => Err(StdError::generic_err("reply id is invalid")),
+    /* Destroy the liquidity tokens the pool received */
+    let burn_msg = CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: liquidity_token.to_string(),
+        funds: vec![],
+        msg: to_binary(&Cw20ExecuteMsg::Burn { amount: share })?,
+    });
+
+    /* Providers are only paid what minters left of the reserve */
+    let mut res = Response::new().add_message(burn_msg);
+    if !claimed.is_zero() {
+        res = res.add_message(leverage_man::transfer_backing_asset(
+            deps.storage,
+            deps.api,
+            &deps.querier,
+            sender,
+            claimed,
+        )?);
     }
+
+    Ok(res.add_attributes(vec![
+        ("action", "claim_settlement"),
+        ("provider", sender.as_str()),
+        ("burn_amount", &share.to_string()),
+        ("claimed_amount", &claimed.to_string()),
+    ]))
 }
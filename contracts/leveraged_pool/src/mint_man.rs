@@ -4,57 +4,256 @@
  * Provides tools for minting and burning a user's leveraged assets
  */
 
-use crate::leverage_man;
+use crate::leverage_man::{PendingZap, PendingZapOut};
+use crate::{leverage_man, token};
 use cosmwasm_std::{
-    to_binary, CosmosMsg, DepsMut, Env, MessageInfo, Response, WasmMsg,
+    to_binary, Addr, CosmosMsg, Decimal, Deps, DepsMut, Env, MessageInfo,
+    Reply, Response, StdError, StdResult, SubMsg, Uint128, WasmMsg,
+};
+use cw0::one_coin;
+use cw20::{Cw20Coin, Cw20ExecuteMsg};
+use leveraged_pools::pool::{
+    multiply_ratio, PriceSnapshot, ReceiveAs, SlippageLimits, TryBurn, TryMint,
+    PRECISION, PREMIUM_PRECISION,
+};
+use terraswap::asset::{Asset, AssetInfo};
+use terraswap::pair::{
+    Cw20HookMsg as PairCw20HookMsg, ExecuteMsg as PairExecuteMsg,
 };
-use cw20::Cw20ExecuteMsg;
-use leveraged_pools::pool::{multiply_ratio, MinterPosition, TryBurn, TryMint};
 
 use crate::error::ContractError;
 
+/**
+ * Reply ID of the submessage instantiating the leveraged position token
+ */
+pub const LEVERAGED_TOKEN_REPLY_ID: u64 = 1;
+
+/**
+ * Reply ID of the swap funding a zap-in mint
+ */
+pub const ZAP_IN_REPLY_ID: u64 = 3;
+
+/**
+ * Reply ID of the swap paying out a burn or withdrawal in the stable asset
+ */
+pub const ZAP_OUT_REPLY_ID: u64 = 4;
+
+/**
+ * Instantiate the Cw20 representing this pool's leveraged positions
+ */
+pub fn instantiate_leveraged_token(
+    env: &Env,
+    token_code_id: u64,
+    initial_balances: Vec<Cw20Coin>,
+) -> StdResult<SubMsg> {
+    token::instantiate_pool_token(
+        env,
+        token_code_id,
+        "leveraged pool position token",
+        "uLEV",
+        initial_balances,
+        LEVERAGED_TOKEN_REPLY_ID,
+    )
+}
+
+/**
+ * Record the address of the freshly instantiated leveraged position token
+ */
+pub fn register_leveraged_token(
+    deps: DepsMut,
+    msg: Reply,
+) -> Result<Response, ContractError> {
+    let token_addr = token::parse_instantiated_addr(msg)?;
+
+    leverage_man::set_leveraged_token_addr(
+        deps.storage,
+        deps.api,
+        &token_addr,
+    )?;
+
+    Ok(Response::new()
+        .add_attribute("leveraged_token_addr", token_addr.to_string()))
+}
+
+/**
+ * Outcome of minting with some unleveraged assets at the current prices
+ */
+pub struct MintQuote {
+    /* Retained in the reserve for liquidity providers */
+    pub mint_premium: Uint128,
+
+    /* Leveraged assets backing the new position */
+    pub leveraged_assets: Uint128,
+
+    /* Share of the leveraged pool issued as position tokens */
+    pub minted_share: Uint128,
+
+    /* Prices the mint is executed at */
+    pub pricing: PriceSnapshot,
+
+    /* Protocol ratio once the position is minted */
+    pub protocol_ratio: Uint128,
+
+    /* Whether the protocol ratio stays at or above the minimum */
+    pub is_healthy: bool,
+}
+
+/**
+ * Price a mint with `sent_unleveraged_assets`, shared by the mint itself and
+ * its simulation
+ */
+pub fn quote_mint(
+    deps: &Deps,
+    env: &Env,
+    sent_unleveraged_assets: Uint128,
+) -> Result<MintQuote, ContractError> {
+    let state = leverage_man::query_pool_state(deps)?;
+    let hyper_p = leverage_man::query_hyperparameters(deps)?;
+
+    /*
+     * The premium stays in the reserve without issuing any LP share, which
+     * grows the value of every liquidity position
+     */
+    let mint_premium = multiply_ratio(
+        sent_unleveraged_assets,
+        hyper_p.mint_premium,
+        Uint128::from(PREMIUM_PRECISION),
+    )?;
+    let minted_unleveraged_assets = sent_unleveraged_assets - mint_premium;
+
+    /* Price the mint at whichever of the current price and TWAP buys fewer
+     * leveraged assets s/t a manipulated price cannot be minted at */
+    let (pricing, _) = leverage_man::get_pricing_bounds(deps, env)?;
+
+    /* How many leveraged assets could the remaining funds buy */
+    let leveraged_assets = leverage_man::leveraged_equivalence_at(
+        &pricing,
+        minted_unleveraged_assets,
+    )?;
+
+    /*
+     * For deposits, we include the sent funds (premium included) in the PR
+     * calculation
+     * (AIR + sent_funds) / (leveraged_assets + equivalence(minted)) >= PR
+     */
+    let protocol_ratio = leverage_man::calculate_pr_at(
+        &pricing,
+        state.assets_in_reserve + sent_unleveraged_assets,
+        state.total_leveraged_assets + leveraged_assets,
+    )?;
+
+    Ok(MintQuote {
+        mint_premium,
+        leveraged_assets,
+        minted_share: leverage_man::leveraged_assets_to_share(
+            &state,
+            leveraged_assets,
+        )?,
+        pricing,
+        protocol_ratio,
+        is_healthy: protocol_ratio >= hyper_p.minimum_protocol_ratio,
+    })
+}
+
+/**
+ * Fail a mint which would take the pool or the minter's position past their
+ * caps
+ */
+fn check_mint_capacity(
+    deps: &Deps,
+    quote: &MintQuote,
+    minter: &Addr,
+    sent_unleveraged_assets: Uint128,
+) -> Result<(), ContractError> {
+    let capacity = leverage_man::remaining_capacity(deps, &quote.pricing)?;
+
+    if let Some(remaining) = capacity.remaining_leveraged_value {
+        let requested = leverage_man::unleveraged_equivalence_at(
+            &quote.pricing,
+            quote.leveraged_assets,
+        )?;
+        if requested > remaining {
+            return Err(ContractError::LeveragedValueCapExceeded {
+                remaining,
+                requested,
+            });
+        }
+    }
+
+    if let Some(remaining) = capacity.remaining_assets_in_reserve {
+        if sent_unleveraged_assets > remaining {
+            return Err(ContractError::ReserveCapExceeded {
+                remaining,
+                requested: sent_unleveraged_assets,
+            });
+        }
+    }
+
+    /* The minter's existing tokens count towards their position */
+    if let Some(cap) = capacity.max_position_per_address {
+        let state = leverage_man::query_pool_state(deps)?;
+        let share = leverage_man::get_addr_leveraged_share(deps, minter)?
+            + quote.minted_share;
+        let position = leverage_man::unleveraged_equivalence_at(
+            &quote.pricing,
+            leverage_man::leveraged_share_to_assets(&state, share)?,
+        )?;
+        if position > cap {
+            return Err(ContractError::PositionCapExceeded { cap, position });
+        }
+    }
+
+    Ok(())
+}
+
 /**
  * Validate and mint the `proposed_mint` position
  */
 pub fn execute_mint_leveraged(
     deps: DepsMut,
-    _info: &MessageInfo,
     env: &Env,
     proposed_mint: &TryMint,
-) -> Result<MinterPosition, ContractError> {
-    let state = leverage_man::query_pool_state(&deps.as_ref())?;
-    let hyper_p = leverage_man::query_hyperparameters(&deps.as_ref())?;
+) -> Result<Response, ContractError> {
+    let leveraged_token =
+        leverage_man::get_leveraged_token_addr(&deps.as_ref())?;
 
     /* The unleveraged funds that were sent in the mint tx */
     let sent_unleveraged_assets = proposed_mint.amount;
 
-    /* How many leveraged assets could these unleveraged assets buy */
-    let new_leveraged_assets = leverage_man::leveraged_equivalence(
+    let quote = quote_mint(&deps.as_ref(), env, sent_unleveraged_assets)?;
+    if !quote.is_healthy {
+        return Err(ContractError::WouldViolatePoolHealth {});
+    }
+    check_mint_capacity(
         &deps.as_ref(),
-        env,
+        &quote,
+        &proposed_mint.sender,
         sent_unleveraged_assets,
     )?;
 
-    /*
-     * For deposits, we include the sent funds in the PR calculation
-     * (AIR + sent_funds) / (leveraged_assets + equivalence(sent_funds)) >= PR
-     */
-    if leverage_man::calculate_pr(
-        &deps.as_ref(),
-        env,
-        state.assets_in_reserve + sent_unleveraged_assets,
-        state.total_leveraged_pool_share + new_leveraged_assets,
-    )? < hyper_p.minimum_protocol_ratio
-    {
-        return Err(ContractError::WouldViolatePoolHealth {});
-    }
-
-    let ret = leverage_man::create_leveraged_position(
+    let position = leverage_man::create_leveraged_position(
         deps.storage,
-        &proposed_mint.sender,
-        new_leveraged_assets,
+        quote.leveraged_assets,
         sent_unleveraged_assets,
-    );
+    )?;
+    leverage_man::record_mint_premium(deps.storage, quote.mint_premium)?;
+
+    /* The minted position is represented by the leveraged token */
+    let minted_share = position.leveraged_pool_partial_share;
+    leverage_man::check_slippage(
+        env,
+        &proposed_mint.limits,
+        minted_share,
+        quote.pricing.leveraged_price,
+    )?;
+    let mint_msg = CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: leveraged_token.to_string(),
+        funds: vec![],
+        msg: to_binary(&Cw20ExecuteMsg::Mint {
+            recipient: proposed_mint.sender.to_string(),
+            amount: minted_share,
+        })?,
+    });
 
     leverage_man::check_reset_leverage(
         deps.storage,
@@ -63,79 +262,548 @@ pub fn execute_mint_leveraged(
         env,
     )?;
 
-    ret
+    Ok(Response::new().add_message(mint_msg).add_attributes(vec![
+        ("action", "mint_leveraged"),
+        ("minter", proposed_mint.sender.as_str()),
+        ("deposit_amount", &sent_unleveraged_assets.to_string()),
+        ("mint_premium", &quote.mint_premium.to_string()),
+        ("mint_amount", &minted_share.to_string()),
+    ]))
 }
 
 /**
- * Validate and burn the `proposed_burn` leveraged position
+ * Swap the sent coin into the backing asset through the pool's TerraSwap pair,
+ * minting from the proceeds once the swap replies
  */
-pub fn execute_burn_leveraged(
+pub fn execute_zap_in(
     deps: DepsMut,
-    _info: &MessageInfo,
+    info: &MessageInfo,
     env: &Env,
-    proposed_burn: &TryBurn,
+    max_spread: Option<Decimal>,
+    limits: SlippageLimits,
 ) -> Result<Response, ContractError> {
-    let state = leverage_man::query_pool_state(&deps.as_ref())?;
-    let hyper_p = leverage_man::query_hyperparameters(&deps.as_ref())?;
-    let leverage_share = leverage_man::get_addr_leveraged_share(
-        &deps.as_ref(),
-        &proposed_burn.sender,
-    );
-
-    let proposed_share = proposed_burn.pool_share;
+    let offer = one_coin(info)?;
 
-    if !leverage_man::addr_has_adequate_leveraged_share(
-        &deps.as_ref(),
-        &proposed_burn.sender,
-        proposed_burn.pool_share,
-    ) {
-        Err(ContractError::InsufficientFunds {})?;
+    /* The backing asset itself is minted with directly */
+    let backing = leverage_man::get_backing_asset(&deps.as_ref())?;
+    if let AssetInfo::NativeToken { denom } = &backing {
+        if *denom == offer.denom {
+            return Err(ContractError::WrongAssetLOL {});
+        }
     }
 
-    let proposed_burn_units = multiply_ratio(
-        leverage_share,
-        proposed_share,
-        state.total_leveraged_pool_share,
+    leverage_man::save_pending_zap(
+        deps.storage,
+        &PendingZap {
+            minter: info.sender.clone(),
+            limits,
+            balance_before: backing.query_pool(
+                &deps.querier,
+                deps.api,
+                env.contract.address.clone(),
+            )?,
+        },
     )?;
 
-    let proposed_redeem_units = leverage_man::unleveraged_equivalence(
-        &deps.as_ref(),
+    /* The pair receives the offer less the Terra tax */
+    let offer_asset = Asset {
+        info: AssetInfo::NativeToken {
+            denom: offer.denom.clone(),
+        },
+        amount: offer.amount,
+    };
+    let offer_coin = offer_asset.deduct_tax(&deps.querier)?;
+    let swap_msg = WasmMsg::Execute {
+        contract_addr: leverage_man::get_terraswap_pair_addr(&deps.as_ref())?
+            .to_string(),
+        funds: vec![offer_coin.clone()],
+        msg: to_binary(&PairExecuteMsg::Swap {
+            offer_asset: Asset {
+                amount: offer_coin.amount,
+                ..offer_asset
+            },
+            belief_price: None,
+            max_spread,
+            to: None,
+        })?,
+    };
+
+    Ok(Response::new()
+        .add_submessage(SubMsg::reply_on_success(swap_msg, ZAP_IN_REPLY_ID))
+        .add_attributes(vec![
+            ("action", "zap_in"),
+            ("minter", info.sender.as_str()),
+            ("offer_amount", &offer.amount.to_string()),
+            ("offer_denom", &offer.denom),
+        ]))
+}
+
+/**
+ * Mint from the proceeds of a settled zap-in swap. Failing here reverts the
+ * swap along with it
+ */
+pub fn zap_in_reply(
+    deps: DepsMut,
+    env: &Env,
+) -> Result<Response, ContractError> {
+    let zap = leverage_man::take_pending_zap(deps.storage)?;
+
+    let balance_after = leverage_man::get_backing_asset(&deps.as_ref())?
+        .query_pool(&deps.querier, deps.api, env.contract.address.clone())?;
+    let proceeds = balance_after
+        .checked_sub(zap.balance_before)
+        .map_err(StdError::from)?;
+
+    execute_mint_leveraged(
+        deps,
         env,
-        proposed_burn_units,
+        &TryMint {
+            sender: zap.minter,
+            amount: proceeds,
+            limits: zap.limits,
+        },
+    )
+}
+
+/**
+ * Pay `amount` of the backing asset out to `recipient`, either directly or
+ * swapped into the other side of the pool's TerraSwap pair. Shared by burns
+ * and withdrawals
+ */
+pub fn redeem_as(
+    deps: &mut DepsMut,
+    env: &Env,
+    recipient: &Addr,
+    amount: Uint128,
+    receive_as: &ReceiveAs,
+) -> Result<SubMsg, ContractError> {
+    let (min_output, max_spread) = match receive_as {
+        ReceiveAs::Backing => {
+            return Ok(SubMsg::new(leverage_man::transfer_backing_asset(
+                deps.storage,
+                deps.api,
+                &deps.querier,
+                recipient,
+                amount,
+            )?))
+        }
+        ReceiveAs::Stable {
+            min_output,
+            max_spread,
+        } => (*min_output, *max_spread),
+    };
+
+    let stable = leverage_man::get_stable_asset(&deps.as_ref())?;
+    let balance_before = stable.query_pool(
+        &deps.querier,
+        deps.api,
+        env.contract.address.clone(),
+    )?;
+    leverage_man::save_pending_zap_out(
+        deps.storage,
+        &PendingZapOut {
+            recipient: recipient.clone(),
+            min_output,
+            stable,
+            balance_before,
+        },
     )?;
 
-    if leverage_man::calculate_pr(
-        &deps.as_ref(),
-        env,
-        state.assets_in_reserve - proposed_redeem_units,
-        state.total_leveraged_pool_share - proposed_burn_units,
-    )? < hyper_p.minimum_protocol_ratio
-    {
+    let pair =
+        leverage_man::get_terraswap_pair_addr(&deps.as_ref())?.to_string();
+    let swap_msg = match leverage_man::get_backing_asset(&deps.as_ref())? {
+        AssetInfo::Token { contract_addr } => WasmMsg::Execute {
+            contract_addr,
+            funds: vec![],
+            msg: to_binary(&Cw20ExecuteMsg::Send {
+                contract: pair,
+                amount,
+                msg: to_binary(&PairCw20HookMsg::Swap {
+                    belief_price: None,
+                    max_spread,
+                    to: None,
+                })?,
+            })?,
+        },
+        info => {
+            /* The pair receives the redemption less the Terra tax */
+            let offer_asset = Asset { info, amount };
+            let offer_coin = offer_asset.deduct_tax(&deps.querier)?;
+            WasmMsg::Execute {
+                contract_addr: pair,
+                funds: vec![offer_coin.clone()],
+                msg: to_binary(&PairExecuteMsg::Swap {
+                    offer_asset: Asset {
+                        amount: offer_coin.amount,
+                        ..offer_asset
+                    },
+                    belief_price: None,
+                    max_spread,
+                    to: None,
+                })?,
+            }
+        }
+    };
+
+    Ok(SubMsg::reply_on_success(swap_msg, ZAP_OUT_REPLY_ID))
+}
+
+/**
+ * Forward the proceeds of a settled zap-out swap. Failing here reverts the
+ * burn or withdrawal along with it
+ */
+pub fn zap_out_reply(
+    deps: DepsMut,
+    env: &Env,
+) -> Result<Response, ContractError> {
+    let zap = leverage_man::take_pending_zap_out(deps.storage)?;
+
+    let balance_after = zap.stable.query_pool(
+        &deps.querier,
+        deps.api,
+        env.contract.address.clone(),
+    )?;
+    let proceeds = balance_after
+        .checked_sub(zap.balance_before)
+        .map_err(StdError::from)?;
+
+    /* Native proceeds are forwarded less the Terra tax, so the limit applies
+     * to what the recipient actually receives */
+    let payout = Asset {
+        info: zap.stable,
+        amount: proceeds,
+    };
+    let received = match &payout.info {
+        AssetInfo::NativeToken { .. } => {
+            payout.deduct_tax(&deps.querier)?.amount
+        }
+        AssetInfo::Token { .. } => proceeds,
+    };
+    if let Some(min_output) = zap.min_output {
+        if received < min_output {
+            return Err(ContractError::SlippageLimitExceeded {
+                expected: min_output,
+                actual: received,
+            });
+        }
+    }
+
+    let payout_msg = payout.into_msg(&deps.querier, zap.recipient.clone())?;
+
+    Ok(Response::new().add_message(payout_msg).add_attributes(vec![
+        ("action", "zap_out"),
+        ("recipient", zap.recipient.as_str()),
+        ("output_amount", &received.to_string()),
+    ]))
+}
+
+/**
+ * Outcome of burning some leveraged pool share at the current prices
+ */
+pub struct BurnQuote {
+    /* Leveraged assets backing the burned share */
+    pub burned_assets: Uint128,
+
+    /* Unleveraged assets paid out */
+    pub redeemed_assets: Uint128,
+
+    /* Prices the burn is redeemed at */
+    pub pricing: PriceSnapshot,
+
+    /* Protocol ratio once the share is burned, None if no minted positions
+     * remain */
+    pub protocol_ratio: Option<Uint128>,
+
+    /* Whether the reserve covers the redemption and the protocol ratio stays
+     * at or above the minimum */
+    pub is_healthy: bool,
+}
+
+/**
+ * Price burning `burn_share` of the leveraged pool, shared by the burn itself
+ * and its simulation
+ */
+pub fn quote_burn(
+    deps: &Deps,
+    env: &Env,
+    burn_share: Uint128,
+) -> Result<BurnQuote, ContractError> {
+    let state = leverage_man::query_pool_state(deps)?;
+    let hyper_p = leverage_man::query_hyperparameters(deps)?;
+
+    if burn_share > state.total_leveraged_pool_share {
+        return Err(ContractError::InsufficientFunds {});
+    }
+
+    /* Rebalancing may have shrunk the assets backing each share */
+    let burned_assets =
+        leverage_man::leveraged_share_to_assets(&state, burn_share)?;
+
+    /* Redeem at whichever of the current price and TWAP returns fewer
+     * unleveraged assets, but check health at the stricter of the two */
+    let (health_pricing, redeem_pricing) =
+        leverage_man::get_pricing_bounds(deps, env)?;
+
+    let redeemed_assets = leverage_man::unleveraged_equivalence_at(
+        &redeem_pricing,
+        burned_assets,
+    )?;
+
+    /* Burning the last of the minted positions always leaves a healthy PR */
+    let remaining_leveraged_assets =
+        state.total_leveraged_assets - burned_assets;
+    let protocol_ratio = if remaining_leveraged_assets.is_zero() {
+        None
+    } else {
+        Some(leverage_man::calculate_pr_at(
+            &health_pricing,
+            state.assets_in_reserve.saturating_sub(redeemed_assets),
+            remaining_leveraged_assets,
+        )?)
+    };
+
+    let protocol_ratio_is_healthy = match protocol_ratio {
+        Some(pr) => pr >= hyper_p.minimum_protocol_ratio,
+        None => true,
+    };
+
+    Ok(BurnQuote {
+        burned_assets,
+        redeemed_assets,
+        pricing: redeem_pricing,
+        protocol_ratio,
+        is_healthy: redeemed_assets <= state.assets_in_reserve
+            && protocol_ratio_is_healthy,
+    })
+}
+
+/**
+ * Validate and burn the `proposed_burn` leveraged position. The leveraged
+ * tokens being burned have already been sent to the pool
+ */
+pub fn execute_burn_leveraged(
+    mut deps: DepsMut,
+    _info: &MessageInfo,
+    env: &Env,
+    proposed_burn: &TryBurn,
+) -> Result<Response, ContractError> {
+    let leveraged_token =
+        leverage_man::get_leveraged_token_addr(&deps.as_ref())?;
+
+    let proposed_burn_share = proposed_burn.amount;
+
+    let quote = quote_burn(&deps.as_ref(), env, proposed_burn_share)?;
+    if !quote.is_healthy {
         return Err(ContractError::WouldViolatePoolHealth {});
     }
 
+    let proposed_burn_units = quote.burned_assets;
+    let proposed_redeem_units = quote.redeemed_assets;
+    leverage_man::check_slippage(
+        env,
+        &proposed_burn.limits,
+        proposed_redeem_units,
+        quote.pricing.leveraged_price,
+    )?;
+
     leverage_man::burn_leveraged_position(
         deps.storage,
-        &proposed_burn.sender,
+        proposed_burn_share,
         proposed_burn_units,
         proposed_redeem_units,
     )?;
 
+    /* Destroy the leveraged tokens the pool received */
+    let burn_msg = CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: leveraged_token.to_string(),
+        funds: vec![],
+        msg: to_binary(&Cw20ExecuteMsg::Burn {
+            amount: proposed_burn_share,
+        })?,
+    });
+
     /* TODO this is inappropriate here, should be in
      * contract.rs, but I need the leveraged_asset_addr.
      * Maybe could pass that back through MinterPosition */
+    let redeem_msg = redeem_as(
+        &mut deps,
+        env,
+        &proposed_burn.sender,
+        proposed_redeem_units,
+        &proposed_burn.receive_as,
+    )?;
+
+    leverage_man::check_reset_leverage(
+        deps.storage,
+        deps.api,
+        deps.querier,
+        env,
+    )?;
+
+    Ok(Response::new()
+        .add_message(burn_msg)
+        .add_submessage(redeem_msg)
+        .add_attributes(vec![
+            ("action", "burn_leveraged"),
+            ("minter", proposed_burn.sender.as_str()),
+            ("burn_amount", &proposed_burn_share.to_string()),
+            ("redeem_amount", &proposed_redeem_units.to_string()),
+        ]))
+}
+
+/**
+ * Burn `share` of a liquidated pool's leveraged positions for their part of
+ * the settlement. The leveraged tokens have already been sent to the pool
+ */
+pub fn execute_claim_settlement(
+    deps: DepsMut,
+    sender: &Addr,
+    share: Uint128,
+) -> Result<Response, ContractError> {
+    let leveraged_token =
+        leverage_man::get_leveraged_token_addr(&deps.as_ref())?;
+    let claimed =
+        leverage_man::claim_leveraged_settlement(deps.storage, share)?;
+
+    /* Destroy the leveraged tokens the pool received */
     let burn_msg = CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: deps
-            .api
-            .addr_humanize(&hyper_p.leveraged_asset_addr)?
-            .to_string(),
+        contract_addr: leveraged_token.to_string(),
         funds: vec![],
-        msg: to_binary(&Cw20ExecuteMsg::Transfer {
-            recipient: proposed_burn.sender.to_string(),
-            amount: proposed_redeem_units,
-        })?,
+        msg: to_binary(&Cw20ExecuteMsg::Burn { amount: share })?,
     });
 
+    let mut res = Response::new().add_message(burn_msg);
+    if !claimed.is_zero() {
+        res = res.add_message(leverage_man::transfer_backing_asset(
+            deps.storage,
+            deps.api,
+            &deps.querier,
+            sender,
+            claimed,
+        )?);
+    }
+
+    Ok(res.add_attributes(vec![
+        ("action", "claim_settlement"),
+        ("minter", sender.as_str()),
+        ("burn_amount", &share.to_string()),
+        ("claimed_amount", &claimed.to_string()),
+    ]))
+}
+
+/**
+ * Shrink all minted positions pro rata once the protocol ratio has fallen
+ * below the rebalance ratio. Positions shrink until the pool is back at the
+ * minimum protocol ratio, and the caller is paid the rebalance premium out of
+ * the value removed from the minted positions. A rebalance which would leave
+ * no position standing fails, leaving the pool to be liquidated instead
+ */
+pub fn execute_rebalance(
+    deps: DepsMut,
+    info: &MessageInfo,
+    env: &Env,
+) -> Result<Response, ContractError> {
+    let state = leverage_man::query_pool_state(&deps.as_ref())?;
+    let hyper_p = leverage_man::query_hyperparameters(&deps.as_ref())?;
+
+    if state.total_leveraged_assets.is_zero() {
+        return Err(ContractError::NoMintedValue {});
+    }
+
+    /* A spot price pushed under the ratio alone cannot force a rebalance */
+    let (low, high) = leverage_man::get_pricing_bounds(&deps.as_ref(), env)?;
+    for snapshot in [low, high].iter() {
+        if leverage_man::calculate_pr_at(
+            snapshot,
+            state.assets_in_reserve,
+            state.total_leveraged_assets,
+        )? >= hyper_p.rebalance_ratio
+        {
+            return Err(ContractError::RebalanceNotNeeded {});
+        }
+    }
+
+    /* Positions shrink like a burn, where leveraged assets are worth least */
+    let minted_value = leverage_man::unleveraged_equivalence_at(
+        &high,
+        state.total_leveraged_assets,
+    )?;
+
+    /* Premium as a ratio precise out to 6 decimals like the protocol ratio */
+    let premium = multiply_ratio(
+        hyper_p.rebalance_premium,
+        Uint128::from(PRECISION),
+        Uint128::from(PREMIUM_PRECISION),
+    )?;
+    let target = hyper_p.minimum_protocol_ratio;
+    if target <= premium {
+        return Err(ContractError::InvalidPoolParams {});
+    }
+
+    /*
+     * Remove value V from the minted positions and pay p * V of it to the
+     * caller s/t the pool sits at the minimum protocol ratio T afterwards
+     *
+     * (AIR - p * V) / (minted_value - V) = T
+     * V = (T * minted_value - AIR) / (T - p)
+     */
+    let numerator = target
+        .checked_mul(minted_value)
+        .and_then(|v| {
+            v.checked_sub(
+                state.assets_in_reserve.checked_mul(PRECISION.into())?,
+            )
+        })
+        .map_err(|_| ContractError::ArithmeticError {})?;
+    let removed_value =
+        multiply_ratio(numerator, Uint128::from(1u128), target - premium)?;
+
+    /* Shares would be left without any assets behind them, diluting the
+     * next mint, so a pool this far gone is only ever liquidated */
+    if removed_value >= minted_value {
+        return Err(ContractError::RebalanceWouldWipeOut {});
+    }
+
+    let reward =
+        multiply_ratio(removed_value, premium, Uint128::from(PRECISION))?;
+
+    /*
+     * Round in favor of the pool s/t the target is always reached, never
+     * leaving more assets than the remaining reserve covers at the target
+     */
+    let healthy_assets = multiply_ratio(
+        leverage_man::leveraged_equivalence_at(
+            &high,
+            state.assets_in_reserve - reward,
+        )?,
+        Uint128::from(PRECISION),
+        target,
+    )?;
+    let remaining_assets = multiply_ratio(
+        state.total_leveraged_assets,
+        minted_value - removed_value,
+        minted_value,
+    )?
+    .min(healthy_assets);
+    if remaining_assets.is_zero() {
+        return Err(ContractError::RebalanceWouldWipeOut {});
+    }
+    let shrink_count = state.total_leveraged_assets - remaining_assets;
+
+    leverage_man::rebalance_leveraged_positions(
+        deps.storage,
+        shrink_count,
+        reward,
+    )?;
+
+    let reward_msg = leverage_man::transfer_backing_asset(
+        deps.storage,
+        deps.api,
+        &deps.querier,
+        &info.sender,
+        reward,
+    )?;
+
     leverage_man::check_reset_leverage(
         deps.storage,
         deps.api,
@@ -143,5 +811,10 @@ pub fn execute_burn_leveraged(
         env,
     )?;
 
-    Ok(Response::new().add_message(burn_msg))
+    Ok(Response::new().add_message(reward_msg).add_attributes(vec![
+        ("action", "rebalance"),
+        ("caller", info.sender.as_str()),
+        ("shrink_amount", &shrink_count.to_string()),
+        ("reward_amount", &reward.to_string()),
+    ]))
 }
@@ -14,15 +14,19 @@ use cosmwasm_std::testing::{
     MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR,
 };
 use cosmwasm_std::{
-    from_binary, from_slice, to_binary, Coin, ContractResult, OwnedDeps,
-    Querier, QuerierResult, QueryRequest, SystemError, SystemResult, Uint128,
-    WasmQuery,
+    from_binary, from_slice, to_binary, Coin, ContractResult, Decimal,
+    OwnedDeps, Querier, QuerierResult, QueryRequest, SystemError, SystemResult,
+    Uint128, WasmQuery,
 };
+use cw20::{BalanceResponse, TokenInfoResponse};
+use leveraged_pools::oracle::PushOraclePriceResponse;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::str::FromStr;
-use terra_cosmwasm::TerraQueryWrapper;
+use terra_cosmwasm::{
+    TaxCapResponse, TaxRateResponse, TerraQuery, TerraQueryWrapper, TerraRoute,
+};
 use terraswap::asset::{Asset, AssetInfo};
 use terraswap::pair::PoolResponse;
 
@@ -47,6 +51,53 @@ pub fn mock_dependencies(
 pub struct WasmMockQuerier {
     base: MockQuerier<TerraQueryWrapper>,
     terraswap_pools_querier: TerraswapPoolsQuerier,
+    token_querier: TokenQuerier,
+    oracle_querier: OracleQuerier,
+    tax_querier: TaxQuerier,
+}
+
+#[derive(Clone, Default)]
+pub struct TaxQuerier {
+    rate: Decimal,
+    /* Maximum tax charged per transfer, keyed by denom */
+    caps: HashMap<String, Uint128>,
+}
+
+#[derive(Clone, Default)]
+pub struct OracleQuerier {
+    /* (price, last_updated) pushed to each oracle contract */
+    prices: HashMap<String, (Uint128, u64)>,
+}
+
+#[derive(Clone, Default)]
+pub struct TokenQuerier {
+    /* Balances of each account, keyed by token contract address */
+    balances: HashMap<String, HashMap<String, Uint128>>,
+}
+
+impl TokenQuerier {
+    pub fn new(balances: &[(&String, &[(&String, &Uint128)])]) -> Self {
+        TokenQuerier {
+            balances: balances_to_map(balances),
+        }
+    }
+}
+
+pub(crate) fn balances_to_map(
+    balances: &[(&String, &[(&String, &Uint128)])],
+) -> HashMap<String, HashMap<String, Uint128>> {
+    let mut balances_map: HashMap<String, HashMap<String, Uint128>> =
+        HashMap::new();
+    for (contract_addr, balances) in balances.iter() {
+        let mut contract_balances_map: HashMap<String, Uint128> =
+            HashMap::new();
+        for (addr, balance) in balances.iter() {
+            contract_balances_map.insert(addr.to_string(), **balance);
+        }
+
+        balances_map.insert(contract_addr.to_string(), contract_balances_map);
+    }
+    balances_map
 }
 
 #[derive(Clone, Default)]
@@ -116,6 +167,13 @@ pub struct EpochStateResponse {
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     Pool {},
+    Balance {
+        address: String,
+    },
+    TokenInfo {},
+    Price {
+        asset: String,
+    },
     GetReferenceData {
         base_symbol: String,
         quote_symbol: String,
@@ -132,6 +190,30 @@ impl WasmMockQuerier {
         request: &QueryRequest<TerraQueryWrapper>,
     ) -> QuerierResult {
         match &request {
+            QueryRequest::Custom(TerraQueryWrapper { route, query_data })
+                if *route == TerraRoute::Treasury =>
+            {
+                match query_data {
+                    TerraQuery::TaxRate {} => SystemResult::Ok(
+                        ContractResult::from(to_binary(&TaxRateResponse {
+                            rate: self.tax_querier.rate,
+                        })),
+                    ),
+                    TerraQuery::TaxCap { denom } => SystemResult::Ok(
+                        ContractResult::from(to_binary(&TaxCapResponse {
+                            cap: self
+                                .tax_querier
+                                .caps
+                                .get(denom)
+                                .copied()
+                                .unwrap_or_default(),
+                        })),
+                    ),
+                    _ => SystemResult::Err(SystemError::UnsupportedRequest {
+                        kind: "terra".to_string(),
+                    }),
+                }
+            }
             QueryRequest::Wasm(WasmQuery::Smart { contract_addr, msg }) => {
                 match from_binary(msg).unwrap() {
                     QueryMsg::Pool {} => match self
@@ -165,6 +247,67 @@ impl WasmMockQuerier {
                             })
                         }
                     },
+                    QueryMsg::Balance { address } => {
+                        match self.token_querier.balances.get(contract_addr) {
+                            Some(balances) => {
+                                SystemResult::Ok(ContractResult::from(
+                                    to_binary(&BalanceResponse {
+                                        balance: *balances
+                                            .get(&address)
+                                            .unwrap_or(&Uint128::zero()),
+                                    }),
+                                ))
+                            }
+                            None => {
+                                SystemResult::Err(SystemError::InvalidRequest {
+                                    error: "No balance info exists".to_string(),
+                                    request: msg.as_slice().into(),
+                                })
+                            }
+                        }
+                    }
+                    QueryMsg::TokenInfo {} => {
+                        match self.token_querier.balances.get(contract_addr) {
+                            Some(balances) => {
+                                SystemResult::Ok(ContractResult::from(
+                                    to_binary(&TokenInfoResponse {
+                                        name: contract_addr.to_string(),
+                                        symbol: "uTOK".to_string(),
+                                        decimals: 6,
+                                        total_supply: balances
+                                            .values()
+                                            .fold(Uint128::zero(), |a, b| {
+                                                a + *b
+                                            }),
+                                    }),
+                                ))
+                            }
+                            None => {
+                                SystemResult::Err(SystemError::InvalidRequest {
+                                    error: "No token info exists".to_string(),
+                                    request: msg.as_slice().into(),
+                                })
+                            }
+                        }
+                    }
+                    QueryMsg::Price { .. } => {
+                        match self.oracle_querier.prices.get(contract_addr) {
+                            Some((price, last_updated)) => {
+                                SystemResult::Ok(ContractResult::from(
+                                    to_binary(&PushOraclePriceResponse {
+                                        price: *price,
+                                        last_updated: *last_updated,
+                                    }),
+                                ))
+                            }
+                            None => {
+                                SystemResult::Err(SystemError::InvalidRequest {
+                                    error: "No oracle price exists".to_string(),
+                                    request: msg.as_slice().into(),
+                                })
+                            }
+                        }
+                    }
                     QueryMsg::GetReferenceData { .. } => SystemResult::Ok(
                         ContractResult::from(to_binary(&ReferenceData {
                             rate: Uint128::from(3465211050000000000000u128),
@@ -191,9 +334,48 @@ impl WasmMockQuerier {
         WasmMockQuerier {
             base,
             terraswap_pools_querier: TerraswapPoolsQuerier::default(),
+            token_querier: TokenQuerier::default(),
+            oracle_querier: OracleQuerier::default(),
+            tax_querier: TaxQuerier::default(),
         }
     }
 
+    /* Configure the Terra tax on native transfers */
+    pub fn with_tax(&mut self, rate: Decimal, caps: &[(&str, Uint128)]) {
+        self.tax_querier = TaxQuerier {
+            rate,
+            caps: caps
+                .iter()
+                .map(|(denom, cap)| (denom.to_string(), *cap))
+                .collect(),
+        };
+    }
+
+    /* Push a price to a mocked push oracle */
+    pub fn with_oracle_price(
+        &mut self,
+        oracle: &str,
+        price: Uint128,
+        last_updated: u64,
+    ) {
+        self.oracle_querier
+            .prices
+            .insert(oracle.to_string(), (price, last_updated));
+    }
+
+    /* Configure the native coin balances of an address */
+    pub fn with_native_balances(&mut self, address: &str, balances: Vec<Coin>) {
+        self.base.update_balance(address, balances);
+    }
+
+    /* Configure the token balances (and total supply) of Cw20 contracts */
+    pub fn with_token_balances(
+        &mut self,
+        balances: &[(&String, &[(&String, &Uint128)])],
+    ) {
+        self.token_querier = TokenQuerier::new(balances);
+    }
+
     #[allow(clippy::type_complexity)]
     pub fn with_terraswap_pools(
         &mut self,
@@ -1,16 +1,111 @@
-use crate::contract::{execute, instantiate, query};
+use crate::contract::{execute, instantiate, migrate, query, reply};
 use crate::error::ContractError;
+use crate::leverage_man::{
+    LegacyPoolState, LEGACY_LIQUIDITYSTATE, LEGACY_MINTSTATE, LEGACY_POOLSTATE,
+    LEGACY_PRICE_DATA,
+};
+use crate::liquid_man::LIQUIDITY_TOKEN_REPLY_ID;
+use crate::mint_man::{
+    LEVERAGED_TOKEN_REPLY_ID, ZAP_IN_REPLY_ID, ZAP_OUT_REPLY_ID,
+};
+use crate::response::MsgInstantiateContractResponse;
 use crate::testing::mock_querier::{mock_dependencies, OwnedMockDeps};
-use cosmwasm_std::testing::{mock_env, mock_info};
+use cosmwasm_std::testing::{mock_env, mock_info, MOCK_CONTRACT_ADDR};
 use cosmwasm_std::{
-    coins, from_binary, to_binary, Addr, CosmosMsg, Response, Uint128, WasmMsg,
+    coins, from_binary, to_binary, Addr, BankMsg, ContractResult, CosmosMsg,
+    Decimal, Env, Reply, ReplyOn, Response, Storage, SubMsgExecutionResponse,
+    Uint128, WasmMsg,
 };
-use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
+use cw2::{get_contract_version, set_contract_version};
+use cw20::{Cw20Coin, Cw20ExecuteMsg, Cw20ReceiveMsg};
+use leveraged_pools::oracle::PriceSourceConfig;
 use leveraged_pools::pool::{
-    Cw20HookMsg, ExecuteMsg, HyperparametersResponse, InstantiateMsg,
-    LiquidityPositionResponse, PoolStateResponse, PriceHistoryResponse,
-    ProtocolRatioResponse, ProviderPosition, QueryMsg,
+    AllPoolInfoResponse, CandleRange, CandleResolution, CandlesResponse,
+    Cw20HookMsg, ExecuteMsg, FeesCollectedResponse, HyperparameterChange,
+    HyperparameterChangesResponse, HyperparametersResponse, InstantiateMsg,
+    LeverageDirection, LeveragedPositionResponse, LiquidityPositionResponse,
+    MigrateMsg, Ohlc, OrderBy, PoolCapacity, PoolStateResponse, PoolStatus,
+    PriceHistoryResponse, PriceSnapshot, ProtocolRatioResponse,
+    ProviderPosition, QueryMsg, ReceiveAs, SettlementResponse,
+    SimulationResponse, TwapResponse,
 };
+use protobuf::Message;
+use terraswap::asset::{Asset, AssetInfo};
+use terraswap::pair::{
+    Cw20HookMsg as PairCw20HookMsg, ExecuteMsg as PairExecuteMsg,
+};
+use terraswap::token::InstantiateMsg as TokenInstantiateMsg;
+
+/* Pretend the chain instantiated a token as requested in submessage `id` */
+fn reply_token_instantiated(deps: &mut OwnedMockDeps, id: u64, addr: &str) {
+    let mut instantiate_res = MsgInstantiateContractResponse::new();
+    instantiate_res.set_contract_address(addr.to_string());
+
+    let msg = Reply {
+        id,
+        result: ContractResult::Ok(SubMsgExecutionResponse {
+            events: vec![],
+            data: Some(instantiate_res.write_to_bytes().unwrap().into()),
+        }),
+    };
+    reply(deps.as_mut(), mock_env(), msg).unwrap();
+}
+
+/* Extract the (contract, Cw20ExecuteMsg) of the nth message in a response */
+fn cw20_msg_at(res: &Response, n: usize) -> (String, Cw20ExecuteMsg) {
+    match &res.messages[n].msg {
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr, msg, ..
+        }) => (contract_addr.clone(), from_binary(msg).unwrap()),
+        _ => panic!("Expected a Cw20ExecuteMsg"),
+    }
+}
+
+/* Hyperparameters of the 2x mTSLA pool */
+fn mtsla_ust_2x_instantiate_msg() -> InstantiateMsg {
+    InstantiateMsg {
+        leverage_amount: Uint128::new(2_000_000),
+        minimum_protocol_ratio: Uint128::new(2_500_000),
+        rebalance_ratio: Uint128::new(2_000_000),
+        mint_premium: Uint128::new(500_000),
+        rebalance_premium: Uint128::new(10_000_000),
+        /* Previous terraswap pool */
+        terraswap_pair_addr: String::from("mTSLA-UST"),
+        /* Contract of the asset that is being leveraged */
+        leveraged_asset_addr: String::from("mTSLA"),
+        /* Backed by the Cw20 rather than a native coin */
+        leveraged_asset_denom: None,
+        /* Cw20 code for the leveraged position token */
+        token_code_id: 7,
+        /* Address allowed to adjust hyperparameters */
+        governance: Some(String::from("governance")),
+        /* Where the asset price comes from */
+        price_source: None,
+        /* Check pricing against the TWAP of the last hour */
+        twap_window: None,
+        /* Long leverage */
+        direction: None,
+        /* Knock positions out at 5% of the reset leveraged price */
+        knock_out_floor: None,
+        /* Record prices every 15 minutes, reset leverage daily at 00:00 UTC
+         * and keep 90 days of prices */
+        price_data_expiry: None,
+        leverage_expiry: None,
+        leverage_reset_offset: None,
+        /* No keeper reward */
+        keeper_reward: None,
+        price_data_n: None,
+        /* Liquidate below a PR of 1.0 */
+        liquidation_ratio: None,
+        /* Only governance may pause, and never automatically */
+        guardian: None,
+        circuit_breaker: None,
+        /* Uncapped */
+        max_total_leveraged_value: None,
+        max_assets_in_reserve: None,
+        max_position_per_address: None,
+    }
+}
 
 /* Create a 2x pool from a CW20
  * + TS liquidity at 1000:1 mTSLA:UST
@@ -20,6 +115,14 @@ use leveraged_pools::pool::{
  * + 10% premium on rebalanced positions
  */
 fn mtsla_ust_2x_init(deps: &mut OwnedMockDeps) -> Response {
+    mtsla_ust_2x_init_priced_by(deps, None)
+}
+
+/* Create the same 2x pool but priced by `price_source` */
+fn mtsla_ust_2x_init_priced_by(
+    deps: &mut OwnedMockDeps,
+    price_source: Option<PriceSourceConfig>,
+) -> Response {
     /* Create a TerraSwap pool and fill it with mTSLA and uusd */
     deps.querier.with_terraswap_pools(&[(
         &"mTSLA-UST".to_string(),
@@ -33,20 +136,19 @@ fn mtsla_ust_2x_init(deps: &mut OwnedMockDeps) -> Response {
 
     /* Hyperparameters */
     let msg = InstantiateMsg {
-        leverage_amount: Uint128::new(2_000_000),
-        minimum_protocol_ratio: Uint128::new(2_500_000),
-        rebalance_ratio: Uint128::new(2_000_000),
-        mint_premium: Uint128::new(0_500_000),
-        rebalance_premium: Uint128::new(10_000_000),
-        /* Previous terraswap pool */
-        terraswap_pair_addr: String::from("mTSLA-UST"),
-        /* Contract of the asset that is being leveraged */
-        leveraged_asset_addr: String::from("mTSLA"),
+        price_source,
+        ..mtsla_ust_2x_instantiate_msg()
     };
 
     /* Initialize leveraged pool */
     let info = mock_info("leveraged", &coins(1000, "big_ones"));
-    instantiate(deps.as_mut(), mock_env(), info, msg).unwrap()
+    let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    /* Position tokens are instantiated alongside the pool */
+    reply_token_instantiated(deps, LEVERAGED_TOKEN_REPLY_ID, "leveraged_token");
+    reply_token_instantiated(deps, LIQUIDITY_TOKEN_REPLY_ID, "liquidity_token");
+
+    res
 }
 
 #[test]
@@ -54,7 +156,21 @@ fn proper_init() {
     let mut deps = mock_dependencies(&[]);
 
     let res = mtsla_ust_2x_init(&mut deps);
-    assert_eq!(0, res.messages.len());
+    assert_eq!(2, res.messages.len());
+
+    /* The pool instantiates its leveraged and liquidity tokens */
+    for (n, id) in [LEVERAGED_TOKEN_REPLY_ID, LIQUIDITY_TOKEN_REPLY_ID]
+        .iter()
+        .enumerate()
+    {
+        assert_eq!(res.messages[n].id, *id);
+        match &res.messages[n].msg {
+            CosmosMsg::Wasm(WasmMsg::Instantiate { code_id, .. }) => {
+                assert_eq!(*code_id, 7)
+            }
+            _ => panic!("Pool did not instantiate its tokens"),
+        }
+    }
 
     /* Query hyperparameters and validate they are as we set them to be */
     let msg = QueryMsg::Hyperparameters {};
@@ -63,10 +179,31 @@ fn proper_init() {
     assert_eq!(hyper_p.leverage_amount, Uint128::new(2_000_000));
     assert_eq!(hyper_p.rebalance_ratio, Uint128::new(2_000_000));
     assert_eq!(hyper_p.minimum_protocol_ratio, Uint128::new(2_500_000));
-    assert_eq!(hyper_p.mint_premium, Uint128::new(0_500_000));
+    assert_eq!(hyper_p.mint_premium, Uint128::new(500_000));
     assert_eq!(hyper_p.rebalance_premium, Uint128::new(10_000_000));
     assert_eq!(hyper_p.terraswap_pair_addr, Addr::unchecked("mTSLA-UST"));
-    assert_eq!(hyper_p.leveraged_asset_addr, Addr::unchecked("mTSLA"));
+    assert_eq!(hyper_p.leveraged_asset_addr, Some(String::from("mTSLA")));
+    assert_eq!(
+        hyper_p.leveraged_token_addr,
+        Some(String::from("leveraged_token"))
+    );
+    assert_eq!(
+        hyper_p.liquidity_token_addr,
+        Some(String::from("liquidity_token"))
+    );
+    assert_eq!(hyper_p.governance_addr, "governance");
+
+    /* Governance has not changed anything yet */
+    let msg = QueryMsg::HyperparameterChanges {
+        start_after: None,
+        end_before: None,
+        limit: None,
+        order: None,
+    };
+    let bin = query(deps.as_ref(), mock_env(), msg).unwrap();
+    let res: HyperparameterChangesResponse = from_binary(&bin).unwrap();
+    assert!(res.changes.is_empty());
+    assert_eq!(res.next_cursor, None);
 
     /* Check that pool state was also initialized correctly */
     let msg = QueryMsg::PoolState {};
@@ -75,14 +212,19 @@ fn proper_init() {
     assert_eq!(pool_state.assets_in_reserve, Uint128::zero());
 
     /* Assert that inital price was correctly queried from mocked TerraSwap */
-    assert_eq!(pool_state.opening_snapshot.timestamp > 0, true);
+    assert!(pool_state.opening_snapshot.timestamp > 0);
     assert_eq!(
         pool_state.opening_snapshot.asset_price.u128() / 1_000_000,
         1_000
     );
 
     /* Query asset price history */
-    let msg = QueryMsg::PriceHistory {};
+    let msg = QueryMsg::PriceHistory {
+        start_after: None,
+        end_before: None,
+        limit: None,
+        order: None,
+    };
     let res = query(deps.as_ref(), mock_env(), msg).unwrap();
     let u_price_history: PriceHistoryResponse = from_binary(&res).unwrap();
     let genesis_snapshot = u_price_history.price_history;
@@ -98,6 +240,107 @@ fn proper_init() {
     assert_eq!(genesis_snapshot.asset_price.u128() / 1_000_000, 1_000);
 }
 
+#[test]
+fn proper_update_hyperparameters() {
+    let mut deps = mock_dependencies(&[]);
+
+    /* mTSLA pool init */
+    mtsla_ust_2x_init(&mut deps);
+
+    let update = |minimum_protocol_ratio, rebalance_ratio, mint_premium| {
+        ExecuteMsg::UpdateHyperparameters {
+            minimum_protocol_ratio,
+            rebalance_ratio,
+            mint_premium,
+            rebalance_premium: None,
+        }
+    };
+
+    /* Only governance can adjust hyperparameters */
+    let msg = update(Some(Uint128::new(2_750_000)), None, None);
+    match execute(deps.as_mut(), mock_env(), mock_info("minter", &[]), msg) {
+        Err(ContractError::Unauthorized {}) => {}
+        _ => panic!("Hyperparameters were adjusted by a stranger"),
+    }
+
+    /* Ratios move by at most 0.25 per update */
+    let msg = update(Some(Uint128::new(3_000_000)), None, None);
+    match execute(deps.as_mut(), mock_env(), mock_info("governance", &[]), msg)
+    {
+        Err(ContractError::HyperparameterDeltaTooLarge { parameter }) => {
+            assert_eq!(parameter, "minimum_protocol_ratio")
+        }
+        _ => panic!("Hyperparameters moved beyond the allowed delta"),
+    }
+
+    /* Bounded changes must still leave valid hyperparameters */
+    let msg = update(
+        Some(Uint128::new(2_250_000)),
+        Some(Uint128::new(2_250_000)),
+        None,
+    );
+    match execute(deps.as_mut(), mock_env(), mock_info("governance", &[]), msg)
+    {
+        Err(ContractError::InvalidPoolParams {}) => {}
+        _ => panic!("Hyperparameters were made invalid"),
+    }
+
+    /* Raise the minimum PR and the mint premium */
+    let msg = update(
+        Some(Uint128::new(2_750_000)),
+        None,
+        Some(Uint128::new(1_000_000)),
+    );
+    execute(deps.as_mut(), mock_env(), mock_info("governance", &[]), msg)
+        .unwrap();
+
+    let bin =
+        query(deps.as_ref(), mock_env(), QueryMsg::Hyperparameters {}).unwrap();
+    let hyper_p: HyperparametersResponse = from_binary(&bin).unwrap();
+    assert_eq!(hyper_p.minimum_protocol_ratio, Uint128::new(2_750_000));
+    assert_eq!(hyper_p.rebalance_ratio, Uint128::new(2_000_000));
+    assert_eq!(hyper_p.mint_premium, Uint128::new(1_000_000));
+
+    /* Each adjustment is kept in the change log, one page at a time */
+    let changes = |start_after, end_before, order| {
+        let msg = QueryMsg::HyperparameterChanges {
+            start_after,
+            end_before,
+            limit: Some(1),
+            order,
+        };
+        let bin = query(deps.as_ref(), mock_env(), msg).unwrap();
+        from_binary::<HyperparameterChangesResponse>(&bin).unwrap()
+    };
+    let minimum_protocol_ratio_change = HyperparameterChange {
+        parameter: String::from("minimum_protocol_ratio"),
+        previous: Uint128::new(2_500_000),
+        updated: Uint128::new(2_750_000),
+        timestamp: mock_env().block.time.seconds(),
+    };
+    let mint_premium_change = HyperparameterChange {
+        parameter: String::from("mint_premium"),
+        previous: Uint128::new(500_000),
+        updated: Uint128::new(1_000_000),
+        timestamp: mock_env().block.time.seconds(),
+    };
+
+    let res = changes(None, None, None);
+    assert_eq!(res.changes, vec![minimum_protocol_ratio_change.clone()]);
+    assert_eq!(res.next_cursor, Some(0));
+    let res = changes(res.next_cursor, None, None);
+    assert_eq!(res.changes, vec![mint_premium_change.clone()]);
+    assert_eq!(res.next_cursor, None);
+
+    /* Newest first */
+    let res = changes(None, None, Some(OrderBy::Descending));
+    assert_eq!(res.changes, vec![mint_premium_change]);
+    assert_eq!(res.next_cursor, Some(1));
+    let res = changes(None, res.next_cursor, Some(OrderBy::Descending));
+    assert_eq!(res.changes, vec![minimum_protocol_ratio_change]);
+    assert_eq!(res.next_cursor, None);
+}
+
 #[test]
 fn proper_mint() {
     let mut deps = mock_dependencies(&[]);
@@ -120,7 +363,12 @@ fn proper_mint() {
     let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
         sender: "minter".to_string(),
         amount: Uint128::new(100_000_000),
-        msg: to_binary(&Cw20HookMsg::MintLeveragedPosition {}).unwrap(),
+        msg: to_binary(&Cw20HookMsg::MintLeveragedPosition {
+            min_received: None,
+            max_leveraged_price: None,
+            deadline: None,
+        })
+        .unwrap(),
     });
     match execute(deps.as_mut(), mock_env(), mock_info("mTSLA", &[]), msg) {
         Err(e) => match e {
@@ -132,12 +380,17 @@ fn proper_mint() {
 
     /*
      * Attempt to mint a leveraged position which would make a PR of 2.499999
-     * and therefore should be rejected too
+     * after the 0.5% mint premium and therefore should be rejected too
      */
     let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
         sender: "minter".to_string(),
-        amount: Uint128::new(66_666_667),
-        msg: to_binary(&Cw20HookMsg::MintLeveragedPosition {}).unwrap(),
+        amount: Uint128::new(67_226_891),
+        msg: to_binary(&Cw20HookMsg::MintLeveragedPosition {
+            min_received: None,
+            max_leveraged_price: None,
+            deadline: None,
+        })
+        .unwrap(),
     });
     match execute(deps.as_mut(), mock_env(), mock_info("mTSLA", &[]), msg) {
         Err(e) => match e {
@@ -152,27 +405,73 @@ fn proper_mint() {
      */
     let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
         sender: "minter".to_string(),
-        amount: Uint128::new(66_666_666),
-        msg: to_binary(&Cw20HookMsg::MintLeveragedPosition {}).unwrap(),
+        amount: Uint128::new(67_226_890),
+        msg: to_binary(&Cw20HookMsg::MintLeveragedPosition {
+            min_received: None,
+            max_leveraged_price: None,
+            deadline: None,
+        })
+        .unwrap(),
     });
-    execute(deps.as_mut(), mock_env(), mock_info("mTSLA", &[]), msg).unwrap();
+    let res = execute(deps.as_mut(), mock_env(), mock_info("mTSLA", &[]), msg)
+        .unwrap();
+    assert!(res
+        .attributes
+        .iter()
+        .any(|attr| attr.key == "mint_premium" && attr.value == "336134"));
+
+    /* The position net of the premium is minted as leveraged tokens */
+    let (token, mint_msg) = cw20_msg_at(&res, 0);
+    assert_eq!(token, "leveraged_token");
+    assert_eq!(
+        mint_msg,
+        Cw20ExecuteMsg::Mint {
+            recipient: "minter".to_string(),
+            amount: Uint128::new(66_890_756),
+        }
+    );
+
+    /* The premium stays in the reserve without issuing any LP share */
+    let bin = query(deps.as_ref(), mock_env(), QueryMsg::PoolState {}).unwrap();
+    let pool_state: PoolStateResponse = from_binary(&bin).unwrap();
+    assert_eq!(pool_state.assets_in_reserve, Uint128::new(167_226_890));
+    assert_eq!(pool_state.total_asset_pool_share, Uint128::new(100_000_000));
+
+    let bin =
+        query(deps.as_ref(), mock_env(), QueryMsg::FeesCollected {}).unwrap();
+    let fees: FeesCollectedResponse = from_binary(&bin).unwrap();
+    assert_eq!(fees.mint_premium, Uint128::new(336_134));
 
     /* Verify legal PR of 2.5 */
     let bin =
-        &query(deps.as_ref(), mock_env(), QueryMsg::ProtocolRatio {}).unwrap();
+        query(deps.as_ref(), mock_env(), QueryMsg::ProtocolRatio {}).unwrap();
     let res: ProtocolRatioResponse = from_binary(&bin).unwrap();
     assert_eq!(res.pr, Uint128::new(2_500_000));
 
     /*
      * Attempt to remove liquidity which should result in an illegal PR
      */
-    let msg = ExecuteMsg::WithdrawLiquidity {
-        share_of_pool: Uint128::new(100_000_000),
-    };
-    match execute(deps.as_mut(), mock_env(), mock_info("provider", &[]), msg) {
-        Ok(_) => panic!("LP withdrawal was able to create unhealthy PR"),
-        Err(_) => {}
-    }
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(100_000_000),
+        msg: to_binary(&Cw20HookMsg::WithdrawLiquidity {
+            min_received: None,
+            max_leveraged_price: None,
+            deadline: None,
+            receive_as: None,
+        })
+        .unwrap(),
+    });
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("liquidity_token", &[]),
+        msg,
+    );
+    assert!(
+        res.is_err(),
+        "LP withdrawal was able to create unhealthy PR"
+    );
 
     /*
      * I PUT THE LIQUIDITY IN
@@ -182,33 +481,82 @@ fn proper_mint() {
         amount: Uint128::new(100_000_000),
         msg: to_binary(&Cw20HookMsg::ProvideLiquidity {}).unwrap(),
     });
-    execute(deps.as_mut(), mock_env(), mock_info("mTSLA", &[]), msg).unwrap();
+    let res = execute(deps.as_mut(), mock_env(), mock_info("mTSLA", &[]), msg)
+        .unwrap();
+
+    /* The liquidity pool is worth 100.336134 mTSLA including the premium,
+     * which the new share leaves with the earlier provider */
+    let (_, mint_msg) = cw20_msg_at(&res, 0);
+    assert_eq!(
+        mint_msg,
+        Cw20ExecuteMsg::Mint {
+            recipient: "provider".to_string(),
+            amount: Uint128::new(99_664_992),
+        }
+    );
 
     /*
      * JUST TO TAKE IT BACK OUT AGAIN
      */
-    let msg = ExecuteMsg::WithdrawLiquidity {
-        share_of_pool: Uint128::new(100_000_000),
-    };
-    execute(deps.as_mut(), mock_env(), mock_info("provider", &[]), msg)
-        .unwrap();
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(99_664_992),
+        msg: to_binary(&Cw20HookMsg::WithdrawLiquidity {
+            min_received: None,
+            max_leveraged_price: None,
+            deadline: None,
+            receive_as: None,
+        })
+        .unwrap(),
+    });
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("liquidity_token", &[]),
+        msg,
+    )
+    .unwrap();
+
+    /* Which pays back the deposit, less rounding */
+    let (_, redeem_msg) = cw20_msg_at(&res, 1);
+    assert_eq!(
+        redeem_msg,
+        Cw20ExecuteMsg::Transfer {
+            recipient: "provider".to_string(),
+            amount: Uint128::new(99_999_984),
+        }
+    );
 
     /* Verify legal PR of 2.5 */
     let bin =
-        &query(deps.as_ref(), mock_env(), QueryMsg::ProtocolRatio {}).unwrap();
+        query(deps.as_ref(), mock_env(), QueryMsg::ProtocolRatio {}).unwrap();
     let res: ProtocolRatioResponse = from_binary(&bin).unwrap();
     assert_eq!(res.pr, Uint128::new(2_500_000));
 
     /*
      * Try to create an illegal PR by taking just a little out
      */
-    let msg = ExecuteMsg::WithdrawLiquidity {
-        share_of_pool: Uint128::new(100),
-    };
-    match execute(deps.as_mut(), mock_env(), mock_info("provider", &[]), msg) {
-        Ok(_) => panic!("LP withdrawal was able to create unhealthy PR"),
-        Err(_) => {}
-    }
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(1_000),
+        msg: to_binary(&Cw20HookMsg::WithdrawLiquidity {
+            min_received: None,
+            max_leveraged_price: None,
+            deadline: None,
+            receive_as: None,
+        })
+        .unwrap(),
+    });
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("liquidity_token", &[]),
+        msg,
+    );
+    assert!(
+        res.is_err(),
+        "LP withdrawal was able to create unhealthy PR"
+    );
 }
 
 #[test]
@@ -224,7 +572,19 @@ fn proper_lp() {
         amount: Uint128::new(100_000_000),
         msg: to_binary(&Cw20HookMsg::ProvideLiquidity {}).unwrap(),
     });
-    execute(deps.as_mut(), mock_env(), mock_info("mTSLA", &[]), msg).unwrap();
+    let res = execute(deps.as_mut(), mock_env(), mock_info("mTSLA", &[]), msg)
+        .unwrap();
+
+    /* Our share of the pool is minted as liquidity tokens */
+    let (token, mint_msg) = cw20_msg_at(&res, 0);
+    assert_eq!(token, "liquidity_token");
+    assert_eq!(
+        mint_msg,
+        Cw20ExecuteMsg::Mint {
+            recipient: "provider".to_string(),
+            amount: Uint128::new(100_000_000),
+        }
+    );
 
     /* Verify 100 mTSLA were recorded as pool liquidity */
     let res = query(deps.as_ref(), mock_env(), QueryMsg::PoolState {}).unwrap();
@@ -232,22 +592,6 @@ fn proper_lp() {
     assert_eq!(pool_state.assets_in_reserve, Uint128::new(100_000_000));
     assert_eq!(pool_state.total_asset_pool_share, Uint128::new(100_000_000));
 
-    /* Verify the pool recorded our position */
-    let bin = &query(
-        deps.as_ref(),
-        mock_env(),
-        QueryMsg::LiquidityPosition {
-            address: Addr::unchecked("provider"),
-        },
-    )
-    .unwrap();
-    let res: LiquidityPositionResponse = from_binary(&bin).unwrap();
-    let position: ProviderPosition = res.position;
-
-    /* We own the entire pool of course */
-    assert_eq!(position.asset_pool_total_share, Uint128::new(100_000_000));
-    assert_eq!(position.asset_pool_partial_share, Uint128::new(100_000_000));
-
     /* Someone else provides 100 mTSLA as well */
     let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
         sender: "someone_else".to_string(),
@@ -256,8 +600,17 @@ fn proper_lp() {
     });
     execute(deps.as_mut(), mock_env(), mock_info("mTSLA", &[]), msg).unwrap();
 
+    /* Liquidity positions are read from the liquidity token */
+    deps.querier.with_token_balances(&[(
+        &"liquidity_token".to_string(),
+        &[
+            (&"provider".to_string(), &Uint128::new(100_000_000)),
+            (&"someone_else".to_string(), &Uint128::new(100_000_000)),
+        ],
+    )]);
+
     /* Check our LP position after someone else deposits 100 mTSLA */
-    let bin = &query(
+    let bin = query(
         deps.as_ref(),
         mock_env(),
         QueryMsg::LiquidityPosition {
@@ -272,60 +625,83 @@ fn proper_lp() {
     assert_eq!(position.asset_pool_total_share, Uint128::new(200_000_000));
     assert_eq!(position.asset_pool_partial_share, Uint128::new(100_000_000));
 
-    /* Attempt to withdraw an excessive amount of liquidity */
-    let msg = ExecuteMsg::WithdrawLiquidity {
-        share_of_pool: Uint128::new(1_000_000_000),
-    };
-    match execute(deps.as_mut(), mock_env(), mock_info("provider", &[]), msg) {
-        Ok(_) => panic!("LP was able to withdraw more than their share!"),
-        Err(_) => {}
+    /* Liquidity can only be withdrawn with the liquidity token */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(100_000_000),
+        msg: to_binary(&Cw20HookMsg::WithdrawLiquidity {
+            min_received: None,
+            max_leveraged_price: None,
+            deadline: None,
+            receive_as: None,
+        })
+        .unwrap(),
+    });
+    match execute(deps.as_mut(), mock_env(), mock_info("mTSLA", &[]), msg) {
+        Err(ContractError::WrongAssetLOL {}) => {}
+        _ => panic!("Pool withdrew liquidity for the wrong Cw20"),
     }
 
-    /* Attempt to withdraw our liquidity */
-    let msg = ExecuteMsg::WithdrawLiquidity {
-        share_of_pool: Uint128::new(100_000_000),
-    };
-    let res =
-        execute(deps.as_mut(), mock_env(), mock_info("provider", &[]), msg)
-            .unwrap();
-
-    /* Extract Cw20ExecuteMsg::Transfer from response */
-    let (denom, receipt) = match &res.messages[0].msg {
-        CosmosMsg::Wasm(w) => match w {
-            WasmMsg::Execute {
-                contract_addr, msg, ..
-            } => (contract_addr, msg),
-            _ => panic!("Invalid WithdrawLiquidity response"),
-        },
-        _ => panic!("Invalid WithdrawLiquidity response"),
-    };
-    let receipt_msg: Cw20ExecuteMsg = from_binary(&receipt).unwrap();
-    let (recipient, amount) = match receipt_msg {
-        Cw20ExecuteMsg::Transfer { recipient, amount } => (recipient, amount),
-        _ => panic!("Invalid WithdrawLiquidity response"),
-    };
-
-    /* Assert that we are credited our funds we withdrew from the pool */
-    assert_eq!(denom, "mTSLA");
-    assert_eq!(recipient, "provider");
-    assert_eq!(amount, Uint128::new(100_000_000));
+    /* Attempt to withdraw an excessive amount of liquidity */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(1_000_000_000),
+        msg: to_binary(&Cw20HookMsg::WithdrawLiquidity {
+            min_received: None,
+            max_leveraged_price: None,
+            deadline: None,
+            receive_as: None,
+        })
+        .unwrap(),
+    });
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("liquidity_token", &[]),
+        msg,
+    );
+    assert!(res.is_err(), "LP was able to withdraw more than the pool!");
 
-    /* Check our LP position after someone else deposits 100 mTSLA */
-    let bin = &query(
-        deps.as_ref(),
+    /* Attempt to withdraw our liquidity */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(100_000_000),
+        msg: to_binary(&Cw20HookMsg::WithdrawLiquidity {
+            min_received: None,
+            max_leveraged_price: None,
+            deadline: None,
+            receive_as: None,
+        })
+        .unwrap(),
+    });
+    let res = execute(
+        deps.as_mut(),
         mock_env(),
-        QueryMsg::LiquidityPosition {
-            address: Addr::unchecked("provider"),
-        },
+        mock_info("liquidity_token", &[]),
+        msg,
     )
     .unwrap();
-    let res: LiquidityPositionResponse = from_binary(&bin).unwrap();
-    let position: ProviderPosition = res.position;
 
-    /* Check that our LP share is zero */
-    assert_eq!(position.asset_pool_partial_share, Uint128::zero());
-    /* Assert other funds have not been touched */
-    assert_eq!(position.asset_pool_total_share, Uint128::new(100_000_000));
+    /* Received liquidity tokens are burned */
+    let (token, burn_msg) = cw20_msg_at(&res, 0);
+    assert_eq!(token, "liquidity_token");
+    assert_eq!(
+        burn_msg,
+        Cw20ExecuteMsg::Burn {
+            amount: Uint128::new(100_000_000)
+        }
+    );
+
+    /* Assert that we are credited our funds we withdrew from the pool */
+    let (denom, receipt_msg) = cw20_msg_at(&res, 1);
+    assert_eq!(denom, "mTSLA");
+    assert_eq!(
+        receipt_msg,
+        Cw20ExecuteMsg::Transfer {
+            recipient: "provider".to_string(),
+            amount: Uint128::new(100_000_000),
+        }
+    );
 
     /* Verify pool state was updated after our withdrawal */
     let res = query(deps.as_ref(), mock_env(), QueryMsg::PoolState {}).unwrap();
@@ -351,8 +727,17 @@ fn price_history() {
     execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg).unwrap();
 
     /* Check price history */
-    let bin =
-        &query(deps.as_ref(), env.clone(), QueryMsg::PriceHistory {}).unwrap();
+    let bin = query(
+        deps.as_ref(),
+        env.clone(),
+        QueryMsg::PriceHistory {
+            start_after: None,
+            end_before: None,
+            limit: None,
+            order: None,
+        },
+    )
+    .unwrap();
     let res: PriceHistoryResponse = from_binary(&bin).unwrap();
     let history = res.price_history;
 
@@ -371,8 +756,17 @@ fn price_history() {
     execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg).unwrap();
 
     /* Check price history */
-    let bin =
-        &query(deps.as_ref(), env.clone(), QueryMsg::PriceHistory {}).unwrap();
+    let bin = query(
+        deps.as_ref(),
+        env.clone(),
+        QueryMsg::PriceHistory {
+            start_after: None,
+            end_before: None,
+            limit: None,
+            order: None,
+        },
+    )
+    .unwrap();
     let res: PriceHistoryResponse = from_binary(&bin).unwrap();
     let history = res.price_history;
 
@@ -391,8 +785,17 @@ fn price_history() {
     execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg).unwrap();
 
     /* Check price history */
-    let bin =
-        &query(deps.as_ref(), env.clone(), QueryMsg::PriceHistory {}).unwrap();
+    let bin = query(
+        deps.as_ref(),
+        env.clone(),
+        QueryMsg::PriceHistory {
+            start_after: None,
+            end_before: None,
+            limit: None,
+            order: None,
+        },
+    )
+    .unwrap();
     let res: PriceHistoryResponse = from_binary(&bin).unwrap();
     let history = res.price_history;
 
@@ -407,53 +810,144 @@ fn price_history() {
 }
 
 #[test]
-fn reset_leverage() {
+fn paginated_price_history() {
     let mut deps = mock_dependencies(&[]);
     let mut env = mock_env();
+    let genesis = env.block.time.seconds();
 
     /* mTSLA pool init */
     mtsla_ust_2x_init(&mut deps);
 
-    /* Triggering event for leverage reset */
-    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
-        sender: "provider".to_string(),
-        amount: Uint128::new(100_000_000),
-        msg: to_binary(&Cw20HookMsg::ProvideLiquidity {}).unwrap(),
-    });
-    execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg).unwrap();
-
-    let res = query(deps.as_ref(), mock_env(), QueryMsg::PoolState {}).unwrap();
-    let pool_state: PoolStateResponse = from_binary(&res).unwrap();
+    /* Record a snapshot every 15 minutes for an hour */
+    for _ in 0..4 {
+        env.block.time = env.block.time.plus_seconds(15 * 60);
+        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "provider".to_string(),
+            amount: Uint128::new(100_000_000),
+            msg: to_binary(&Cw20HookMsg::ProvideLiquidity {}).unwrap(),
+        });
+        execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg)
+            .unwrap();
+    }
+    let timestamps: Vec<u64> = (0..5).map(|n| genesis + n * 15 * 60).collect();
+
+    /* Page forward through history two snapshots at a time */
+    let mut cursor = None;
+    let mut pages = vec![];
+    loop {
+        let msg = QueryMsg::PriceHistory {
+            start_after: cursor,
+            end_before: None,
+            limit: Some(2),
+            order: None,
+        };
+        let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let res: PriceHistoryResponse = from_binary(&bin).unwrap();
+        pages.push(
+            res.price_history
+                .iter()
+                .map(|snapshot| snapshot.timestamp)
+                .collect::<Vec<u64>>(),
+        );
+
+        cursor = res.next_cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
     assert_eq!(
-        pool_state.opening_snapshot.timestamp,
-        env.block.time.seconds()
+        pages,
+        vec![
+            timestamps[0..2].to_vec(),
+            timestamps[2..4].to_vec(),
+            timestamps[4..5].to_vec(),
+        ]
     );
 
-    /* Ensure we do not set leverage too often */
-    env.block.time = env.block.time.plus_seconds(1);
+    /* Newest first, ending before the latest snapshot */
+    let msg = QueryMsg::PriceHistory {
+        start_after: Some(timestamps[0]),
+        end_before: Some(timestamps[4]),
+        limit: Some(2),
+        order: Some(OrderBy::Descending),
+    };
+    let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+    let res: PriceHistoryResponse = from_binary(&bin).unwrap();
+    assert_eq!(res.price_history.len(), 2);
+    assert_eq!(res.price_history[0].timestamp, timestamps[3]);
+    assert_eq!(res.price_history[1].timestamp, timestamps[2]);
+    assert_eq!(res.next_cursor, Some(timestamps[2]));
+
+    /* The last page within the range */
+    let msg = QueryMsg::PriceHistory {
+        start_after: Some(timestamps[0]),
+        end_before: res.next_cursor,
+        limit: Some(2),
+        order: Some(OrderBy::Descending),
+    };
+    let bin = query(deps.as_ref(), env, msg).unwrap();
+    let res: PriceHistoryResponse = from_binary(&bin).unwrap();
+    assert_eq!(res.price_history.len(), 1);
+    assert_eq!(res.price_history[0].timestamp, timestamps[1]);
+    assert_eq!(res.next_cursor, None);
+}
 
-    /* Triggering event for leverage reset */
-    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
-        sender: "provider".to_string(),
-        amount: Uint128::new(100_000_000),
-        msg: to_binary(&Cw20HookMsg::ProvideLiquidity {}).unwrap(),
-    });
-    execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg).unwrap();
+/* Move the mocked mTSLA-UST pool to `u_price` */
+fn set_mtsla_price(deps: &mut OwnedMockDeps, u_price: u128) {
+    deps.querier.with_terraswap_pools(&[(
+        &"mTSLA-UST".to_string(),
+        (
+            &"uusd".to_string(),
+            &Uint128::from(u_price * 1_000),
+            &"mTSLA".to_string(),
+            &Uint128::from(1_000_000_000u128),
+        ),
+    )]);
+}
 
-    /* Query pool state for last leverage reset time */
-    let res = query(deps.as_ref(), mock_env(), QueryMsg::PoolState {}).unwrap();
-    let pool_state: PoolStateResponse = from_binary(&res).unwrap();
+/* Current asset price as seen by the pool */
+fn current_asset_price(deps: &OwnedMockDeps, env: &Env) -> Uint128 {
+    let bin =
+        query(deps.as_ref(), env.clone(), QueryMsg::AllPoolInfo {}).unwrap();
+    let res: AllPoolInfoResponse = from_binary(&bin).unwrap();
+    res.price_context.current_snapshot.asset_price
+}
 
-    /* Should not update - too soon */
-    assert_ne!(
-        pool_state.opening_snapshot.timestamp,
-        env.block.time.seconds()
+#[test]
+fn twap_price_source() {
+    let mut deps = mock_dependencies(&[]);
+    let mut env = mock_env();
+
+    /* Zero length windows are rejected */
+    let msg = InstantiateMsg {
+        price_source: Some(PriceSourceConfig::TerraswapTwap {
+            window_seconds: 0,
+        }),
+        ..mtsla_ust_2x_instantiate_msg()
+    };
+    let info = mock_info("leveraged", &[]);
+    match instantiate(deps.as_mut(), env.clone(), info, msg) {
+        Err(ContractError::InvalidPoolParams {}) => {}
+        _ => panic!("Pool was instantiated with an empty TWAP window"),
+    }
+
+    /* mTSLA pool init, priced by the average of the last hour */
+    mtsla_ust_2x_init_priced_by(
+        &mut deps,
+        Some(PriceSourceConfig::TerraswapTwap {
+            window_seconds: 60 * 60,
+        }),
     );
 
-    /* Wait a day and make sure leverage has reset propery */
-    env.block.time = env.block.time.plus_seconds(24 * 60 * 60);
+    /* The spot price moving within the block does not move the pool price */
+    set_mtsla_price(&mut deps, 1_400_000_000);
+    assert_eq!(
+        current_asset_price(&deps, &env),
+        Uint128::new(1_000_000_000)
+    );
 
-    /* Triggering event for leverage reset */
+    /* 15 minutes later the spot price is recorded */
+    env.block.time = env.block.time.plus_seconds(15 * 60);
     let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
         sender: "provider".to_string(),
         amount: Uint128::new(100_000_000),
@@ -461,12 +955,2696 @@ fn reset_leverage() {
     });
     execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg).unwrap();
 
-    let res = query(deps.as_ref(), mock_env(), QueryMsg::PoolState {}).unwrap();
-    let pool_state: PoolStateResponse = from_binary(&res).unwrap();
+    let bin = query(
+        deps.as_ref(),
+        env.clone(),
+        QueryMsg::PriceHistory {
+            start_after: None,
+            end_before: None,
+            limit: None,
+            order: None,
+        },
+    )
+    .unwrap();
+    let res: PriceHistoryResponse = from_binary(&bin).unwrap();
+    assert_eq!(
+        res.price_history[1].asset_price,
+        Uint128::new(1_400_000_000)
+    );
 
-    /* Should update - leverage has expired */
+    /* After another 15 minutes each price held for half the time */
+    env.block.time = env.block.time.plus_seconds(15 * 60);
     assert_eq!(
-        pool_state.opening_snapshot.timestamp,
-        env.block.time.seconds()
+        current_asset_price(&deps, &env),
+        Uint128::new(1_200_000_000)
+    );
+}
+
+#[test]
+fn candles() {
+    let mut deps = mock_dependencies(&[]);
+    let mut env = mock_env();
+
+    /* mTSLA pool init, recording its genesis snapshot at 02:23:39 UTC */
+    mtsla_ust_2x_init(&mut deps);
+
+    /* Keepers record a snapshot every 15 minutes as mTSLA moves */
+    for u_price in [1_100_000_000, 900_000_000, 1_050_000_000, 1_000_000_000] {
+        set_mtsla_price(&mut deps, u_price);
+        env.block.time = env.block.time.plus_seconds(15 * 60);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("keeper", &[]),
+            ExecuteMsg::Poke {},
+        )
+        .unwrap();
+    }
+    let candles = |resolution, start, limit| {
+        let msg = QueryMsg::Candles {
+            resolution,
+            range: CandleRange { start, end: None },
+            limit,
+        };
+        let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+        from_binary::<CandlesResponse>(&bin).unwrap()
+    };
+
+    /* Hourly candles open at 02:00 and 03:00 */
+    let day = 24 * 60 * 60;
+    let two_am = mock_env().block.time.seconds() / day * day + 2 * 60 * 60;
+    let res = candles(CandleResolution::OneHour, None, None);
+    assert_eq!(res.resolution, CandleResolution::OneHour);
+    assert_eq!(res.candles.len(), 2);
+    assert_eq!(res.candles[0].open_time, two_am);
+    assert_eq!(
+        res.candles[0].asset,
+        Ohlc {
+            open: Uint128::new(1_000_000_000),
+            high: Uint128::new(1_100_000_000),
+            low: Uint128::new(900_000_000),
+            close: Uint128::new(900_000_000),
+        }
+    );
+    assert_eq!(res.candles[1].open_time, two_am + 60 * 60);
+    assert_eq!(res.next_cursor, None);
+
+    /* Five 15 minute candles, paged two at a time */
+    let res = candles(CandleResolution::FifteenMinutes, None, Some(2));
+    assert_eq!(res.candles.len(), 2);
+    assert_eq!(res.candles[0].open_time, two_am + 15 * 60);
+    assert_eq!(res.next_cursor, Some(two_am + 45 * 60));
+
+    let res =
+        candles(CandleResolution::FifteenMinutes, res.next_cursor, Some(2));
+    assert_eq!(res.candles.len(), 2);
+    assert_eq!(res.candles[0].open_time, two_am + 45 * 60);
+    assert_eq!(res.next_cursor, Some(two_am + 75 * 60));
+
+    let res =
+        candles(CandleResolution::FifteenMinutes, res.next_cursor, Some(2));
+    assert_eq!(res.candles.len(), 1);
+    assert_eq!(res.candles[0].asset.close, Uint128::new(1_000_000_000));
+    assert_eq!(res.next_cursor, None);
+}
+
+#[test]
+fn twap_guards_pricing() {
+    let mut deps = mock_dependencies(&[]);
+    let env = mock_env();
+
+    /* mTSLA pool init */
+    mtsla_ust_2x_init(&mut deps);
+
+    /* Provide 100 mTSLA as liquidity */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(100_000_000),
+        msg: to_binary(&Cw20HookMsg::ProvideLiquidity {}).unwrap(),
+    });
+    execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg).unwrap();
+
+    /* Dump mTSLA on TerraSwap right before minting */
+    set_mtsla_price(&mut deps, 900_000_000);
+
+    /* The TWAP has not moved */
+    let msg = QueryMsg::Twap {
+        window_seconds: 60 * 60,
+    };
+    let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+    let res: TwapResponse = from_binary(&bin).unwrap();
+    assert_eq!(res.twap.asset_price, Uint128::new(1_000_000_000));
+    assert_eq!(res.twap.leveraged_price, Uint128::new(1_000_000_000));
+
+    /* Minting is priced at the TWAP, which buys fewer leveraged assets */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "minter".to_string(),
+        amount: Uint128::new(10_000_000),
+        msg: to_binary(&Cw20HookMsg::MintLeveragedPosition {
+            min_received: None,
+            max_leveraged_price: None,
+            deadline: None,
+        })
+        .unwrap(),
+    });
+    let res = execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg)
+        .unwrap();
+    let (_, mint_msg) = cw20_msg_at(&res, 0);
+    assert_eq!(
+        mint_msg,
+        Cw20ExecuteMsg::Mint {
+            recipient: "minter".to_string(),
+            amount: Uint128::new(9_950_000),
+        }
+    );
+
+    /* Pump mTSLA on TerraSwap right before burning */
+    set_mtsla_price(&mut deps, 1_100_000_000);
+
+    /* Burning is priced at the TWAP, which redeems fewer assets */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "minter".to_string(),
+        amount: Uint128::new(4_000_000),
+        msg: to_binary(&Cw20HookMsg::BurnLeveragedPosition {
+            min_received: None,
+            deadline: None,
+            receive_as: None,
+        })
+        .unwrap(),
+    });
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("leveraged_token", &[]),
+        msg,
+    )
+    .unwrap();
+    let (_, redeem_msg) = cw20_msg_at(&res, 1);
+    assert_eq!(
+        redeem_msg,
+        Cw20ExecuteMsg::Transfer {
+            recipient: "minter".to_string(),
+            amount: Uint128::new(4_000_000),
+        }
+    );
+
+    /* Empty windows cannot be averaged */
+    let msg = QueryMsg::Twap { window_seconds: 0 };
+    assert!(query(deps.as_ref(), env, msg).is_err());
+}
+
+#[test]
+fn slippage_limits() {
+    let mut deps = mock_dependencies(&[]);
+    let env = mock_env();
+    let now = env.block.time.seconds();
+
+    /* mTSLA pool init */
+    mtsla_ust_2x_init(&mut deps);
+
+    /* Provide 100 mTSLA as liquidity */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(100_000_000),
+        msg: to_binary(&Cw20HookMsg::ProvideLiquidity {}).unwrap(),
+    });
+    execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg).unwrap();
+
+    /* Minting with 10 mTSLA receives 9.95 position tokens at 1000 */
+    for (min_received, max_leveraged_price, deadline) in [
+        (Some(9_950_001), None, None),
+        (None, Some(999_999_999), None),
+        (None, None, Some(now - 1)),
+    ] {
+        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "minter".to_string(),
+            amount: Uint128::new(10_000_000),
+            msg: to_binary(&Cw20HookMsg::MintLeveragedPosition {
+                min_received: min_received.map(Uint128::new),
+                max_leveraged_price: max_leveraged_price.map(Uint128::new),
+                deadline,
+            })
+            .unwrap(),
+        });
+        let info = mock_info("mTSLA", &[]);
+        match execute(deps.as_mut(), env.clone(), info, msg) {
+            Err(ContractError::SlippageLimitExceeded { expected, actual }) => {
+                if min_received.is_some() {
+                    assert_eq!(expected, Uint128::new(9_950_001));
+                    assert_eq!(actual, Uint128::new(9_950_000));
+                } else {
+                    assert_eq!(expected, Uint128::new(999_999_999));
+                    assert_eq!(actual, Uint128::new(1_000_000_000));
+                }
+            }
+            Err(ContractError::DeadlinePassed {
+                deadline,
+                block_time,
+            }) => {
+                assert_eq!(deadline, now - 1);
+                assert_eq!(block_time, now);
+            }
+            _ => panic!("Mint ignored its slippage limits"),
+        }
+    }
+
+    /* Exactly meeting the limits is fine */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "minter".to_string(),
+        amount: Uint128::new(10_000_000),
+        msg: to_binary(&Cw20HookMsg::MintLeveragedPosition {
+            min_received: Some(Uint128::new(9_950_000)),
+            max_leveraged_price: Some(Uint128::new(1_000_000_000)),
+            deadline: Some(now),
+        })
+        .unwrap(),
+    });
+    execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg).unwrap();
+
+    /* Burning 4 position tokens redeems 4 mTSLA */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "minter".to_string(),
+        amount: Uint128::new(4_000_000),
+        msg: to_binary(&Cw20HookMsg::BurnLeveragedPosition {
+            min_received: Some(Uint128::new(4_000_001)),
+            deadline: None,
+            receive_as: None,
+        })
+        .unwrap(),
+    });
+    let info = mock_info("leveraged_token", &[]);
+    match execute(deps.as_mut(), env.clone(), info, msg) {
+        Err(ContractError::SlippageLimitExceeded { expected, actual }) => {
+            assert_eq!(expected, Uint128::new(4_000_001));
+            assert_eq!(actual, Uint128::new(4_000_000));
+        }
+        _ => panic!("Burn ignored its slippage limits"),
+    }
+
+    /* Withdrawing values minted positions at 1000 */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(1_000_000),
+        msg: to_binary(&Cw20HookMsg::WithdrawLiquidity {
+            min_received: None,
+            max_leveraged_price: Some(Uint128::new(999_000_000)),
+            deadline: None,
+            receive_as: None,
+        })
+        .unwrap(),
+    });
+    let info = mock_info("liquidity_token", &[]);
+    match execute(deps.as_mut(), env, info, msg) {
+        Err(ContractError::SlippageLimitExceeded { expected, actual }) => {
+            assert_eq!(expected, Uint128::new(999_000_000));
+            assert_eq!(actual, Uint128::new(1_000_000_000));
+        }
+        _ => panic!("Withdrawal ignored its slippage limits"),
+    }
+}
+
+#[test]
+fn simulate_trades() {
+    let mut deps = mock_dependencies(&[]);
+    let env = mock_env();
+
+    /* mTSLA pool init */
+    mtsla_ust_2x_init(&mut deps);
+
+    /* Provide 100 mTSLA as liquidity */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(100_000_000),
+        msg: to_binary(&Cw20HookMsg::ProvideLiquidity {}).unwrap(),
+    });
+    execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg).unwrap();
+
+    /* Preview minting with 10 mTSLA */
+    let msg = QueryMsg::SimulateMint {
+        amount: Uint128::new(10_000_000),
+    };
+    let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+    let res: SimulationResponse = from_binary(&bin).unwrap();
+    assert_eq!(
+        res,
+        SimulationResponse {
+            output_amount: Uint128::new(9_950_000),
+            fee_amount: Uint128::new(50_000),
+            protocol_ratio: Some(Uint128::new(11_055_276)),
+            passes_health_check: true,
+        }
+    );
+
+    /* The mint itself matches its preview */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "minter".to_string(),
+        amount: Uint128::new(10_000_000),
+        msg: to_binary(&Cw20HookMsg::MintLeveragedPosition {
+            min_received: None,
+            max_leveraged_price: None,
+            deadline: None,
+        })
+        .unwrap(),
+    });
+    let res = execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg)
+        .unwrap();
+    let (_, mint_msg) = cw20_msg_at(&res, 0);
+    assert_eq!(
+        mint_msg,
+        Cw20ExecuteMsg::Mint {
+            recipient: "minter".to_string(),
+            amount: Uint128::new(9_950_000),
+        }
+    );
+
+    deps.querier.with_token_balances(&[
+        (
+            &"leveraged_token".to_string(),
+            &[(&"minter".to_string(), &Uint128::new(9_950_000))],
+        ),
+        (
+            &"liquidity_token".to_string(),
+            &[(&"provider".to_string(), &Uint128::new(100_000_000))],
+        ),
+    ]);
+
+    /* Preview burning 4 of the minted position tokens */
+    let msg = QueryMsg::SimulateBurn {
+        address: Addr::unchecked("minter"),
+        share: Uint128::new(4_000_000),
+    };
+    let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+    let res: SimulationResponse = from_binary(&bin).unwrap();
+    assert_eq!(
+        res,
+        SimulationResponse {
+            output_amount: Uint128::new(4_000_000),
+            fee_amount: Uint128::zero(),
+            protocol_ratio: Some(Uint128::new(17_815_126)),
+            passes_health_check: true,
+        }
+    );
+
+    /* Cannot preview burning more than the address holds */
+    let msg = QueryMsg::SimulateBurn {
+        address: Addr::unchecked("minter"),
+        share: Uint128::new(9_950_001),
+    };
+    assert!(query(deps.as_ref(), env.clone(), msg).is_err());
+
+    /* Withdrawing all liquidity would leave the minted positions unbacked */
+    let msg = QueryMsg::SimulateWithdraw {
+        address: Addr::unchecked("provider"),
+        share: Uint128::new(100_000_000),
+    };
+    let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+    let res: SimulationResponse = from_binary(&bin).unwrap();
+    assert_eq!(
+        res,
+        SimulationResponse {
+            output_amount: Uint128::new(100_050_000),
+            fee_amount: Uint128::zero(),
+            protocol_ratio: Some(Uint128::new(1_000_000)),
+            passes_health_check: false,
+        }
+    );
+
+    /* Which the withdrawal itself agrees with */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(100_000_000),
+        msg: to_binary(&Cw20HookMsg::WithdrawLiquidity {
+            min_received: None,
+            max_leveraged_price: None,
+            deadline: None,
+            receive_as: None,
+        })
+        .unwrap(),
+    });
+    let info = mock_info("liquidity_token", &[]);
+    match execute(deps.as_mut(), env, info, msg) {
+        Err(ContractError::WouldViolatePoolHealth {}) => {}
+        _ => panic!("Withdrawal did not match its simulation"),
+    }
+}
+
+/* Create a 2x pool only just backed by its reserve
+ * + 2 mTSLA of liquidity
+ * + 10 mTSLA minted
+ * + Minimum protocol ratio 1.2 */
+fn thinly_backed_init(deps: &mut OwnedMockDeps, env: &Env) {
+    let msg = InstantiateMsg {
+        minimum_protocol_ratio: Uint128::new(1_200_000),
+        rebalance_ratio: Uint128::new(1_100_000),
+        ..mtsla_ust_2x_instantiate_msg()
+    };
+    deps.querier.with_terraswap_pools(&[(
+        &"mTSLA-UST".to_string(),
+        (
+            &"uusd".to_string(),
+            &Uint128::from(1_000_000_000_000u128),
+            &"mTSLA".to_string(),
+            &Uint128::from(1_000_000_000u128),
+        ),
+    )]);
+    instantiate(deps.as_mut(), env.clone(), mock_info("creator", &[]), msg)
+        .unwrap();
+    reply_token_instantiated(deps, LEVERAGED_TOKEN_REPLY_ID, "leveraged_token");
+    reply_token_instantiated(deps, LIQUIDITY_TOKEN_REPLY_ID, "liquidity_token");
+
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(2_000_000),
+        msg: to_binary(&Cw20HookMsg::ProvideLiquidity {}).unwrap(),
+    });
+    execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg).unwrap();
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "minter".to_string(),
+        amount: Uint128::new(10_000_000),
+        msg: to_binary(&Cw20HookMsg::MintLeveragedPosition {
+            min_received: None,
+            max_leveraged_price: None,
+            deadline: None,
+        })
+        .unwrap(),
+    });
+    execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg).unwrap();
+
+    deps.querier.with_token_balances(&[
+        (
+            &"leveraged_token".to_string(),
+            &[(&"minter".to_string(), &Uint128::new(9_950_000))],
+        ),
+        (
+            &"liquidity_token".to_string(),
+            &[(&"provider".to_string(), &Uint128::new(2_000_000))],
+        ),
+    ]);
+}
+
+#[test]
+fn underwater_withdraw_simulation() {
+    let mut deps = mock_dependencies(&[]);
+    let env = mock_env();
+    thinly_backed_init(&mut deps, &env);
+
+    /* A 30% rally makes minted positions worth more than the reserve */
+    set_mtsla_price(&mut deps, 1_300_000_000);
+    let bin =
+        query(deps.as_ref(), env.clone(), QueryMsg::ProtocolRatio {}).unwrap();
+    let res: ProtocolRatioResponse = from_binary(&bin).unwrap();
+    assert!(res.pr < Uint128::new(1_000_000));
+
+    /* Which leaves nothing to liquidity providers rather than failing */
+    let msg = QueryMsg::SimulateWithdraw {
+        address: Addr::unchecked("provider"),
+        share: Uint128::new(2_000_000),
+    };
+    let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+    let res: SimulationResponse = from_binary(&bin).unwrap();
+    assert_eq!(
+        res,
+        SimulationResponse {
+            output_amount: Uint128::zero(),
+            fee_amount: Uint128::zero(),
+            protocol_ratio: Some(Uint128::new(979_899)),
+            passes_health_check: false,
+        }
+    );
+
+    /* And the withdrawal is refused */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(2_000_000),
+        msg: to_binary(&Cw20HookMsg::WithdrawLiquidity {
+            min_received: None,
+            max_leveraged_price: None,
+            deadline: None,
+            receive_as: None,
+        })
+        .unwrap(),
+    });
+    let info = mock_info("liquidity_token", &[]);
+    match execute(deps.as_mut(), env, info, msg) {
+        Err(ContractError::WouldViolatePoolHealth {}) => {}
+        _ => panic!("Withdrew from an underwater pool"),
+    }
+}
+
+#[test]
+fn twap_underwater_withdraw_simulation() {
+    let mut deps = mock_dependencies(&[]);
+    let mut env = mock_env();
+    thinly_backed_init(&mut deps, &env);
+
+    /* Price history records an hour of a 30% rally */
+    set_mtsla_price(&mut deps, 1_300_000_000);
+    for _ in 0..3 {
+        env.block.time = env.block.time.plus_seconds(30 * 60);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("keeper", &[]),
+            ExecuteMsg::Poke {},
+        )
+        .unwrap();
+    }
+
+    /* The spot price falls back so the pool looks healthy right now */
+    set_mtsla_price(&mut deps, 1_000_000_000);
+    let bin =
+        query(deps.as_ref(), env.clone(), QueryMsg::ProtocolRatio {}).unwrap();
+    let res: ProtocolRatioResponse = from_binary(&bin).unwrap();
+    assert!(res.pr > Uint128::new(1_000_000));
+
+    /* But withdrawals are priced at the underwater TWAP */
+    let msg = QueryMsg::SimulateWithdraw {
+        address: Addr::unchecked("provider"),
+        share: Uint128::new(2_000_000),
+    };
+    let bin = query(deps.as_ref(), env, msg).unwrap();
+    let res: SimulationResponse = from_binary(&bin).unwrap();
+    assert_eq!(res.output_amount, Uint128::zero());
+    assert!(!res.passes_health_check);
+}
+
+#[test]
+fn short_pool() {
+    let mut deps = mock_dependencies(&[]);
+    let mut env = mock_env();
+
+    /* Create a -2x pool over the same TerraSwap pair */
+    deps.querier.with_terraswap_pools(&[(
+        &"mTSLA-UST".to_string(),
+        (
+            &"uusd".to_string(),
+            &Uint128::from(1_000_000_000_000u128),
+            &"mTSLA".to_string(),
+            &Uint128::from(1_000_000_000u128),
+        ),
+    )]);
+    let msg = InstantiateMsg {
+        direction: Some(LeverageDirection::Short),
+        ..mtsla_ust_2x_instantiate_msg()
+    };
+    let info = mock_info("leveraged", &[]);
+    instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+    reply_token_instantiated(
+        &mut deps,
+        LEVERAGED_TOKEN_REPLY_ID,
+        "leveraged_token",
+    );
+    reply_token_instantiated(
+        &mut deps,
+        LIQUIDITY_TOKEN_REPLY_ID,
+        "liquidity_token",
+    );
+
+    let bin = query(deps.as_ref(), env.clone(), QueryMsg::Hyperparameters {})
+        .unwrap();
+    let hyper_p: HyperparametersResponse = from_binary(&bin).unwrap();
+    assert_eq!(hyper_p.direction, LeverageDirection::Short);
+
+    /* Provide 100 mTSLA as liquidity and mint with 10 mTSLA */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(100_000_000),
+        msg: to_binary(&Cw20HookMsg::ProvideLiquidity {}).unwrap(),
+    });
+    execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg).unwrap();
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "minter".to_string(),
+        amount: Uint128::new(10_000_000),
+        msg: to_binary(&Cw20HookMsg::MintLeveragedPosition {
+            min_received: None,
+            max_leveraged_price: None,
+            deadline: None,
+        })
+        .unwrap(),
+    });
+    execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg).unwrap();
+
+    /* A 10% rise in mTSLA is a 20% drop in the leveraged price */
+    set_mtsla_price(&mut deps, 1_100_000_000);
+    let bin =
+        query(deps.as_ref(), env.clone(), QueryMsg::AllPoolInfo {}).unwrap();
+    let res: AllPoolInfoResponse = from_binary(&bin).unwrap();
+    assert_eq!(
+        res.price_context.current_snapshot.leveraged_price,
+        Uint128::new(800_000_000)
+    );
+
+    /* Minted positions lose value while the reserve gains */
+    let bin =
+        query(deps.as_ref(), env.clone(), QueryMsg::ProtocolRatio {}).unwrap();
+    let res: ProtocolRatioResponse = from_binary(&bin).unwrap();
+    assert_eq!(res.pr, Uint128::new(15_201_005));
+
+    /* A 10% drop in mTSLA is a 20% rise, which is what history records */
+    set_mtsla_price(&mut deps, 900_000_000);
+    env.block.time = env.block.time.plus_seconds(15 * 60);
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(100_000_000),
+        msg: to_binary(&Cw20HookMsg::ProvideLiquidity {}).unwrap(),
+    });
+    execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg).unwrap();
+
+    let bin = query(
+        deps.as_ref(),
+        env.clone(),
+        QueryMsg::PriceHistory {
+            start_after: None,
+            end_before: None,
+            limit: None,
+            order: None,
+        },
+    )
+    .unwrap();
+    let res: PriceHistoryResponse = from_binary(&bin).unwrap();
+    assert_eq!(
+        res.price_history[1].leveraged_price,
+        Uint128::new(1_200_000_000)
+    );
+
+    /* Minted positions gain value while the reserve loses */
+    let bin = query(deps.as_ref(), env, QueryMsg::ProtocolRatio {}).unwrap();
+    let res: ProtocolRatioResponse = from_binary(&bin).unwrap();
+    assert_eq!(res.pr, Uint128::new(15_829_145));
+}
+
+#[test]
+fn knock_out() {
+    let mut deps = mock_dependencies(&[]);
+    let env = mock_env();
+
+    /* mTSLA pool init */
+    mtsla_ust_2x_init(&mut deps);
+
+    /* Provide 100 mTSLA as liquidity and mint with 10 mTSLA */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(100_000_000),
+        msg: to_binary(&Cw20HookMsg::ProvideLiquidity {}).unwrap(),
+    });
+    execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg).unwrap();
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "minter".to_string(),
+        amount: Uint128::new(10_000_000),
+        msg: to_binary(&Cw20HookMsg::MintLeveragedPosition {
+            min_received: None,
+            max_leveraged_price: None,
+            deadline: None,
+        })
+        .unwrap(),
+    });
+    execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg).unwrap();
+
+    /* A 60% drop would take a 2x position below zero */
+    set_mtsla_price(&mut deps, 400_000_000);
+    let bin =
+        query(deps.as_ref(), env.clone(), QueryMsg::AllPoolInfo {}).unwrap();
+    let res: AllPoolInfoResponse = from_binary(&bin).unwrap();
+    assert_eq!(
+        res.price_context.current_snapshot.leveraged_price,
+        Uint128::new(50_000_000)
+    );
+
+    /* Governance is held up by neither the knock-out nor a broken price
+     * source */
+    deps.querier.with_terraswap_pools(&[]);
+    let msg = ExecuteMsg::UpdateHyperparameters {
+        minimum_protocol_ratio: None,
+        rebalance_ratio: None,
+        mint_premium: Some(Uint128::new(600_000)),
+        rebalance_premium: None,
+    };
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("governance", &[]),
+        msg,
+    )
+    .unwrap();
+    set_mtsla_price(&mut deps, 400_000_000);
+
+    /* The spot price alone at the floor knocks nothing out */
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("mTSLA", &[]),
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "provider".to_string(),
+            amount: Uint128::new(1_000_000),
+            msg: to_binary(&Cw20HookMsg::ProvideLiquidity {}).unwrap(),
+        }),
+    )
+    .unwrap();
+    assert!(res.events.is_empty());
+
+    /* Price history records an hour at the floor */
+    let mut env = env;
+    for _ in 0..2 {
+        env.block.time = env.block.time.plus_seconds(30 * 60);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("keeper", &[]),
+            ExecuteMsg::Poke {},
+        )
+        .unwrap();
+        assert!(res.events.is_empty());
+    }
+    env.block.time = env.block.time.plus_seconds(30 * 60);
+
+    /* The next transaction pays out positions at the floor and hands back
+     * the deposit it came with */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(100_000_000),
+        msg: to_binary(&Cw20HookMsg::ProvideLiquidity {}).unwrap(),
+    });
+    let res = execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg)
+        .unwrap();
+    assert_eq!(res.events.len(), 1);
+    assert_eq!(res.events[0].ty, "knock_out");
+    assert_eq!(res.events[0].attributes[1].value, String::from("50000000"));
+    assert_eq!(res.events[0].attributes[2].value, String::from("1243750"));
+    assert_eq!(
+        cw20_msg_at(&res, 0),
+        (
+            "mTSLA".to_string(),
+            Cw20ExecuteMsg::Transfer {
+                recipient: "provider".to_string(),
+                amount: Uint128::new(100_000_000),
+            }
+        )
+    );
+
+    /* The pool stays open with leverage restarted from the floor, and only
+     * the knocked out positions are settled */
+    let bin =
+        query(deps.as_ref(), env.clone(), QueryMsg::PoolState {}).unwrap();
+    let state: PoolStateResponse = from_binary(&bin).unwrap();
+    assert_eq!(state.status, PoolStatus::Active);
+    assert_eq!(state.total_leveraged_assets, Uint128::zero());
+    assert_eq!(state.total_leveraged_pool_share, Uint128::zero());
+    assert_eq!(state.assets_in_reserve, Uint128::new(109_756_250));
+    let bin =
+        query(deps.as_ref(), env.clone(), QueryMsg::AllPoolInfo {}).unwrap();
+    let res: AllPoolInfoResponse = from_binary(&bin).unwrap();
+    assert_eq!(
+        res.price_context.opening_snapshot.leveraged_price,
+        Uint128::new(50_000_000)
+    );
+    let bin =
+        query(deps.as_ref(), env.clone(), QueryMsg::Settlement {}).unwrap();
+    let settlement: SettlementResponse = from_binary(&bin).unwrap();
+    assert_eq!(settlement.leveraged_assets, Uint128::new(1_243_750));
+    assert_eq!(settlement.leveraged_pool_share, Uint128::new(9_950_000));
+    assert_eq!(settlement.liquidity_assets, Uint128::zero());
+
+    /* Providers carry on, but nothing is minted until every knocked out
+     * position has claimed */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(100_000_000),
+        msg: to_binary(&Cw20HookMsg::ProvideLiquidity {}).unwrap(),
+    });
+    execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg).unwrap();
+    let mint_msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "minter".to_string(),
+        amount: Uint128::new(10_000_000),
+        msg: to_binary(&Cw20HookMsg::MintLeveragedPosition {
+            min_received: None,
+            max_leveraged_price: None,
+            deadline: None,
+        })
+        .unwrap(),
+    });
+    match execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("mTSLA", &[]),
+        mint_msg.clone(),
+    ) {
+        Err(ContractError::KnockOutUnclaimed {}) => {}
+        _ => panic!("Minted alongside unclaimed knocked out positions"),
+    }
+
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "minter".to_string(),
+        amount: Uint128::new(9_950_000),
+        msg: to_binary(&Cw20HookMsg::ClaimSettlement {}).unwrap(),
+    });
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("leveraged_token", &[]),
+        msg,
+    )
+    .unwrap();
+    let (_, claim_msg) = cw20_msg_at(&res, 1);
+    assert_eq!(
+        claim_msg,
+        Cw20ExecuteMsg::Transfer {
+            recipient: "minter".to_string(),
+            amount: Uint128::new(1_243_750),
+        }
+    );
+
+    /* Providers have nothing to claim from an open pool */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(1_000_000),
+        msg: to_binary(&Cw20HookMsg::ClaimSettlement {}).unwrap(),
+    });
+    match execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("liquidity_token", &[]),
+        msg,
+    ) {
+        Err(ContractError::NotLiquidating {}) => {}
+        _ => panic!("Providers claimed from an open pool"),
+    }
+
+    execute(deps.as_mut(), env, mock_info("mTSLA", &[]), mint_msg).unwrap();
+}
+
+#[test]
+fn push_oracle_price_source() {
+    let mut deps = mock_dependencies(&[]);
+    let env = mock_env();
+    let now = env.block.time.seconds();
+
+    deps.querier.with_oracle_price(
+        "oracle",
+        Uint128::new(900_000_000),
+        now - 60,
+    );
+
+    /* mTSLA pool init, priced by an oracle accepting 5 minute old prices */
+    mtsla_ust_2x_init_priced_by(
+        &mut deps,
+        Some(PriceSourceConfig::PushOracle {
+            oracle_addr: String::from("oracle"),
+            max_age_seconds: 5 * 60,
+        }),
+    );
+
+    /* The oracle price is used over TerraSwap's */
+    assert_eq!(current_asset_price(&deps, &env), Uint128::new(900_000_000));
+
+    let bin = query(deps.as_ref(), env.clone(), QueryMsg::Hyperparameters {})
+        .unwrap();
+    let hyper_p: HyperparametersResponse = from_binary(&bin).unwrap();
+    assert_eq!(
+        hyper_p.price_source,
+        PriceSourceConfig::PushOracle {
+            oracle_addr: String::from("oracle"),
+            max_age_seconds: 5 * 60,
+        }
+    );
+
+    /* Stale prices are refused */
+    deps.querier.with_oracle_price(
+        "oracle",
+        Uint128::new(900_000_000),
+        now - 301,
+    );
+    assert!(
+        query(deps.as_ref(), env.clone(), QueryMsg::AllPoolInfo {}).is_err()
+    );
+}
+
+#[test]
+fn reset_leverage() {
+    let mut deps = mock_dependencies(&[]);
+    let mut env = mock_env();
+
+    /* mTSLA pool init */
+    mtsla_ust_2x_init(&mut deps);
+
+    /* Triggering event for leverage reset */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(100_000_000),
+        msg: to_binary(&Cw20HookMsg::ProvideLiquidity {}).unwrap(),
+    });
+    execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg).unwrap();
+
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::PoolState {}).unwrap();
+    let pool_state: PoolStateResponse = from_binary(&res).unwrap();
+    assert_eq!(
+        pool_state.opening_snapshot.timestamp,
+        env.block.time.seconds()
+    );
+
+    /* Ensure we do not set leverage too often */
+    env.block.time = env.block.time.plus_seconds(1);
+
+    /* Triggering event for leverage reset */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(100_000_000),
+        msg: to_binary(&Cw20HookMsg::ProvideLiquidity {}).unwrap(),
+    });
+    execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg).unwrap();
+
+    /* Query pool state for last leverage reset time */
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::PoolState {}).unwrap();
+    let pool_state: PoolStateResponse = from_binary(&res).unwrap();
+
+    /* Should not update - too soon */
+    assert_ne!(
+        pool_state.opening_snapshot.timestamp,
+        env.block.time.seconds()
+    );
+
+    /* Wait a day and make sure leverage has reset propery */
+    env.block.time = env.block.time.plus_seconds(24 * 60 * 60);
+
+    /* Triggering event for leverage reset */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(100_000_000),
+        msg: to_binary(&Cw20HookMsg::ProvideLiquidity {}).unwrap(),
+    });
+    execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg).unwrap();
+
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::PoolState {}).unwrap();
+    let pool_state: PoolStateResponse = from_binary(&res).unwrap();
+
+    /* Should update - leverage has expired, as of the last 00:00 UTC */
+    let now = env.block.time.seconds();
+    assert_eq!(
+        pool_state.opening_snapshot.timestamp,
+        now - now % (24 * 60 * 60)
+    );
+}
+
+#[test]
+fn proper_burn() {
+    let mut deps = mock_dependencies(&[]);
+
+    /* mTSLA pool init */
+    mtsla_ust_2x_init(&mut deps);
+
+    /* Provide 100 mTSLA as liquidity */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(100_000_000),
+        msg: to_binary(&Cw20HookMsg::ProvideLiquidity {}).unwrap(),
+    });
+    execute(deps.as_mut(), mock_env(), mock_info("mTSLA", &[]), msg).unwrap();
+
+    /* Mint 9.95 leveraged mTSLA with 10 mTSLA after the 0.5% premium */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "minter".to_string(),
+        amount: Uint128::new(10_000_000),
+        msg: to_binary(&Cw20HookMsg::MintLeveragedPosition {
+            min_received: None,
+            max_leveraged_price: None,
+            deadline: None,
+        })
+        .unwrap(),
+    });
+    execute(deps.as_mut(), mock_env(), mock_info("mTSLA", &[]), msg).unwrap();
+
+    /* Position is read from the leveraged token balances */
+    deps.querier.with_token_balances(&[(
+        &"leveraged_token".to_string(),
+        &[(&"minter".to_string(), &Uint128::new(9_950_000))],
+    )]);
+    let bin = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::LeveragedPosition {
+            address: Addr::unchecked("minter"),
+        },
+    )
+    .unwrap();
+    let res: LeveragedPositionResponse = from_binary(&bin).unwrap();
+    assert_eq!(
+        res.position.leveraged_pool_partial_share,
+        Uint128::new(9_950_000)
+    );
+    assert_eq!(
+        res.position.leveraged_pool_total_share,
+        Uint128::new(9_950_000)
+    );
+
+    /* Only the leveraged token can be burned */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "minter".to_string(),
+        amount: Uint128::new(4_000_000),
+        msg: to_binary(&Cw20HookMsg::BurnLeveragedPosition {
+            min_received: None,
+            deadline: None,
+            receive_as: None,
+        })
+        .unwrap(),
+    });
+    match execute(deps.as_mut(), mock_env(), mock_info("mTSLA", &[]), msg) {
+        Err(ContractError::WrongAssetLOL {}) => {}
+        _ => panic!("Pool burned a position with the wrong Cw20"),
+    }
+
+    /* Send 4 leveraged mTSLA back to the pool to burn them */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "minter".to_string(),
+        amount: Uint128::new(4_000_000),
+        msg: to_binary(&Cw20HookMsg::BurnLeveragedPosition {
+            min_received: None,
+            deadline: None,
+            receive_as: None,
+        })
+        .unwrap(),
+    });
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("leveraged_token", &[]),
+        msg,
+    )
+    .unwrap();
+
+    /* Received leveraged tokens are burned */
+    let (token, burn_msg) = cw20_msg_at(&res, 0);
+    assert_eq!(token, "leveraged_token");
+    assert_eq!(
+        burn_msg,
+        Cw20ExecuteMsg::Burn {
+            amount: Uint128::new(4_000_000)
+        }
+    );
+
+    /* Price has not moved so we are refunded 1:1 */
+    let (token, redeem_msg) = cw20_msg_at(&res, 1);
+    assert_eq!(token, "mTSLA");
+    assert_eq!(
+        redeem_msg,
+        Cw20ExecuteMsg::Transfer {
+            recipient: "minter".to_string(),
+            amount: Uint128::new(4_000_000),
+        }
+    );
+
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::PoolState {}).unwrap();
+    let pool_state: PoolStateResponse = from_binary(&res).unwrap();
+    assert_eq!(pool_state.assets_in_reserve, Uint128::new(106_000_000));
+    assert_eq!(
+        pool_state.total_leveraged_pool_share,
+        Uint128::new(5_950_000)
+    );
+
+    /* Burning the remainder of all positions is always healthy, and the
+     * premium is left behind for the providers */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "minter".to_string(),
+        amount: Uint128::new(5_950_000),
+        msg: to_binary(&Cw20HookMsg::BurnLeveragedPosition {
+            min_received: None,
+            deadline: None,
+            receive_as: None,
+        })
+        .unwrap(),
+    });
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("leveraged_token", &[]),
+        msg,
+    )
+    .unwrap();
+
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::PoolState {}).unwrap();
+    let pool_state: PoolStateResponse = from_binary(&res).unwrap();
+    assert_eq!(pool_state.assets_in_reserve, Uint128::new(100_050_000));
+    assert_eq!(pool_state.total_leveraged_pool_share, Uint128::zero());
+}
+
+#[test]
+fn proper_rebalance() {
+    let mut deps = mock_dependencies(&[]);
+
+    /* mTSLA pool init */
+    mtsla_ust_2x_init(&mut deps);
+
+    /* Provide 100 mTSLA as liquidity */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(100_000_000),
+        msg: to_binary(&Cw20HookMsg::ProvideLiquidity {}).unwrap(),
+    });
+    execute(deps.as_mut(), mock_env(), mock_info("mTSLA", &[]), msg).unwrap();
+
+    /* Mint right up to the minimum PR of 2.5 */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "minter".to_string(),
+        amount: Uint128::new(67_226_890),
+        msg: to_binary(&Cw20HookMsg::MintLeveragedPosition {
+            min_received: None,
+            max_leveraged_price: None,
+            deadline: None,
+        })
+        .unwrap(),
+    });
+    execute(deps.as_mut(), mock_env(), mock_info("mTSLA", &[]), msg).unwrap();
+
+    /* A healthy pool cannot be rebalanced */
+    let msg = ExecuteMsg::Rebalance {};
+    match execute(deps.as_mut(), mock_env(), mock_info("keeper", &[]), msg) {
+        Err(ContractError::RebalanceNotNeeded {}) => {}
+        _ => panic!("Pool was rebalanced above the rebalance ratio"),
+    }
+
+    /* mTSLA rallies 40% which pushes the PR below the rebalance ratio */
+    deps.querier.with_terraswap_pools(&[(
+        &"mTSLA-UST".to_string(),
+        (
+            &"uusd".to_string(),
+            &Uint128::from(1_400_000_000_000u128),
+            &"mTSLA".to_string(),
+            &Uint128::from(1_000_000_000u128),
+        ),
+    )]);
+    let bin =
+        query(deps.as_ref(), mock_env(), QueryMsg::ProtocolRatio {}).unwrap();
+    let res: ProtocolRatioResponse = from_binary(&bin).unwrap();
+    assert_eq!(res.pr, Uint128::new(1_944_444));
+
+    /* Not until the TWAP has caught up with the rally */
+    let msg = ExecuteMsg::Rebalance {};
+    match execute(deps.as_mut(), mock_env(), mock_info("keeper", &[]), msg) {
+        Err(ContractError::RebalanceNotNeeded {}) => {}
+        _ => panic!("Pool was rebalanced at a spot price"),
+    }
+    let mut env = mock_env();
+    for _ in 0..3 {
+        env.block.time = env.block.time.plus_seconds(30 * 60);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("keeper", &[]),
+            ExecuteMsg::Poke {},
+        )
+        .unwrap();
+    }
+
+    /* Anyone can rebalance and is paid the premium for doing so */
+    let msg = ExecuteMsg::Rebalance {};
+    let res =
+        execute(deps.as_mut(), env.clone(), mock_info("keeper", &[]), msg)
+            .unwrap();
+    let (token, reward_msg) = cw20_msg_at(&res, 0);
+    assert_eq!(token, "mTSLA");
+    assert_eq!(
+        reward_msg,
+        Cw20ExecuteMsg::Transfer {
+            recipient: "keeper".to_string(),
+            amount: Uint128::new(1_990_796),
+        }
+    );
+
+    /* PR is restored to the minimum protocol ratio */
+    let bin =
+        query(deps.as_ref(), env.clone(), QueryMsg::ProtocolRatio {}).unwrap();
+    let res: ProtocolRatioResponse = from_binary(&bin).unwrap();
+    assert_eq!(res.pr, Uint128::new(2_500_000));
+
+    /* Positions shrank pro rata without touching anyone's share */
+    let res =
+        query(deps.as_ref(), env.clone(), QueryMsg::PoolState {}).unwrap();
+    let pool_state: PoolStateResponse = from_binary(&res).unwrap();
+    assert_eq!(
+        pool_state.total_leveraged_pool_share,
+        Uint128::new(66_890_756)
+    );
+    assert_eq!(pool_state.total_leveraged_assets, Uint128::new(51_406_784));
+    assert_eq!(pool_state.assets_in_reserve, Uint128::new(165_236_094));
+
+    /* Nothing left to do once the pool is rebalanced */
+    let msg = ExecuteMsg::Rebalance {};
+    match execute(deps.as_mut(), env, mock_info("keeper", &[]), msg) {
+        Err(ContractError::RebalanceNotNeeded {}) => {}
+        _ => panic!("Pool was rebalanced twice"),
+    }
+}
+
+#[test]
+fn underwater_rebalance() {
+    let mut deps = mock_dependencies(&[]);
+    let mut env = mock_env();
+
+    /* -2x mTSLA pool init */
+    deps.querier.with_terraswap_pools(&[(
+        &"mTSLA-UST".to_string(),
+        (
+            &"uusd".to_string(),
+            &Uint128::from(1_000_000_000_000u128),
+            &"mTSLA".to_string(),
+            &Uint128::from(1_000_000_000u128),
+        ),
+    )]);
+    let msg = InstantiateMsg {
+        direction: Some(LeverageDirection::Short),
+        ..mtsla_ust_2x_instantiate_msg()
+    };
+    let info = mock_info("leveraged", &[]);
+    instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+    reply_token_instantiated(
+        &mut deps,
+        LEVERAGED_TOKEN_REPLY_ID,
+        "leveraged_token",
+    );
+    reply_token_instantiated(
+        &mut deps,
+        LIQUIDITY_TOKEN_REPLY_ID,
+        "liquidity_token",
+    );
+
+    /* Provide 100 mTSLA as liquidity and mint with 10 mTSLA */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(100_000_000),
+        msg: to_binary(&Cw20HookMsg::ProvideLiquidity {}).unwrap(),
+    });
+    execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg).unwrap();
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "minter".to_string(),
+        amount: Uint128::new(10_000_000),
+        msg: to_binary(&Cw20HookMsg::MintLeveragedPosition {
+            min_received: None,
+            max_leveraged_price: None,
+            deadline: None,
+        })
+        .unwrap(),
+    });
+    execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg).unwrap();
+
+    /* An hour of a 98% crash leaves the reserve covering less than the
+     * rebalance premium of the short positions */
+    set_mtsla_price(&mut deps, 20_000_000);
+    for _ in 0..3 {
+        env.block.time = env.block.time.plus_seconds(30 * 60);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("keeper", &[]),
+            ExecuteMsg::Poke {},
+        )
+        .unwrap();
+    }
+
+    /* Rebalancing would leave the positions' shares backed by nothing */
+    let msg = ExecuteMsg::Rebalance {};
+    match execute(deps.as_mut(), env.clone(), mock_info("keeper", &[]), msg) {
+        Err(ContractError::RebalanceWouldWipeOut {}) => {}
+        _ => panic!("Rebalance wiped out every position"),
+    }
+    let res =
+        query(deps.as_ref(), env.clone(), QueryMsg::PoolState {}).unwrap();
+    let pool_state: PoolStateResponse = from_binary(&res).unwrap();
+    assert_eq!(pool_state.total_leveraged_assets, Uint128::new(9_950_000));
+
+    /* So the pool is liquidated instead */
+    let msg = ExecuteMsg::Liquidate {};
+    execute(deps.as_mut(), env, mock_info("keeper", &[]), msg).unwrap();
+}
+
+#[test]
+fn proper_migrate() {
+    let mut deps = mock_dependencies(&[]);
+
+    /* mTSLA pool init */
+    mtsla_ust_2x_init(&mut deps);
+    let bin = query(deps.as_ref(), mock_env(), QueryMsg::PoolState {}).unwrap();
+    let pool_state: PoolStateResponse = from_binary(&bin).unwrap();
+
+    /* Rewind storage to a 0.1.0 pool holding positions but no tokens */
+    for key in ["contract_info", "leveraged_token", "liquidity_token"] {
+        deps.storage.remove(key.as_bytes());
+    }
+    LEGACY_POOLSTATE
+        .save(
+            &mut deps.storage,
+            &LegacyPoolState {
+                latest_reset_snapshot: pool_state.opening_snapshot,
+                latest_historic_snapshot: pool_state.opening_snapshot,
+                assets_in_reserve: Uint128::new(110_000_000),
+                total_asset_pool_share: Uint128::new(100_000_000),
+                total_leveraged_assets: Uint128::new(10_000_000),
+                total_leveraged_pool_share: Uint128::new(10_000_000),
+            },
+        )
+        .unwrap();
+    LEGACY_PRICE_DATA
+        .save(
+            &mut deps.storage,
+            &vec![
+                pool_state.opening_snapshot,
+                PriceSnapshot {
+                    timestamp: pool_state.opening_snapshot.timestamp + 15 * 60,
+                    ..pool_state.opening_snapshot
+                },
+            ],
+        )
+        .unwrap();
+    LEGACY_MINTSTATE
+        .save(
+            &mut deps.storage,
+            &Addr::unchecked("minter"),
+            &Uint128::new(10_000_000),
+        )
+        .unwrap();
+    LEGACY_LIQUIDITYSTATE
+        .save(
+            &mut deps.storage,
+            &Addr::unchecked("provider"),
+            &Uint128::new(100_000_000),
+        )
+        .unwrap();
+
+    /* A 0.1.0 pool cannot be migrated without a token code and governance */
+    let msg = MigrateMsg {
+        token_code_id: None,
+        governance: None,
+    };
+    match migrate(deps.as_mut(), mock_env(), msg) {
+        Err(ContractError::InvalidPoolParams {}) => {}
+        _ => panic!("Pool was migrated without its token code"),
+    }
+
+    let msg = MigrateMsg {
+        token_code_id: Some(8),
+        governance: Some(String::from("governance")),
+    };
+    let res = migrate(deps.as_mut(), mock_env(), msg).unwrap();
+
+    /* Positions tracked in storage are issued as pool tokens */
+    for (n, (id, holder, amount)) in [
+        (LEVERAGED_TOKEN_REPLY_ID, "minter", 10_000_000),
+        (LIQUIDITY_TOKEN_REPLY_ID, "provider", 100_000_000),
+    ]
+    .iter()
+    .enumerate()
+    {
+        assert_eq!(res.messages[n].id, *id);
+        match &res.messages[n].msg {
+            CosmosMsg::Wasm(WasmMsg::Instantiate { code_id, msg, .. }) => {
+                assert_eq!(*code_id, 8);
+                let token_msg: TokenInstantiateMsg = from_binary(msg).unwrap();
+                assert_eq!(
+                    token_msg.initial_balances,
+                    vec![Cw20Coin {
+                        address: holder.to_string(),
+                        amount: Uint128::new(*amount),
+                    }]
+                );
+            }
+            _ => panic!("Pool did not instantiate its tokens"),
+        }
+    }
+    reply_token_instantiated(&mut deps, LEVERAGED_TOKEN_REPLY_ID, "lev_token");
+    reply_token_instantiated(&mut deps, LIQUIDITY_TOKEN_REPLY_ID, "liq_token");
+
+    /* The legacy positions are gone and the pool state carried over */
+    assert!(LEGACY_MINTSTATE
+        .may_load(&deps.storage, &Addr::unchecked("minter"))
+        .unwrap()
+        .is_none());
+    let bin = query(deps.as_ref(), mock_env(), QueryMsg::PoolState {}).unwrap();
+    let pool_state: PoolStateResponse = from_binary(&bin).unwrap();
+    assert_eq!(pool_state.assets_in_reserve, Uint128::new(110_000_000));
+    assert_eq!(pool_state.total_leveraged_assets, Uint128::new(10_000_000));
+
+    /* Price history moved into its own layout */
+    assert!(LEGACY_PRICE_DATA.may_load(&deps.storage).unwrap().is_none());
+    let msg = QueryMsg::PriceHistory {
+        start_after: None,
+        end_before: None,
+        limit: None,
+        order: None,
+    };
+    let bin = query(deps.as_ref(), mock_env(), msg).unwrap();
+    let res: PriceHistoryResponse = from_binary(&bin).unwrap();
+    assert_eq!(res.price_history.len(), 2);
+
+    let bin =
+        query(deps.as_ref(), mock_env(), QueryMsg::Hyperparameters {}).unwrap();
+    let hyper_p: HyperparametersResponse = from_binary(&bin).unwrap();
+    assert_eq!(hyper_p.governance_addr, "governance");
+    assert_eq!(
+        hyper_p.leveraged_token_addr,
+        Some(String::from("lev_token"))
+    );
+
+    let version = get_contract_version(&deps.storage).unwrap();
+    assert_eq!(version.contract, "crates.io:leveraged_pool");
+    assert_eq!(version.version, env!("CARGO_PKG_VERSION"));
+
+    /* Migrating to the same version is a no-op */
+    let msg = MigrateMsg {
+        token_code_id: None,
+        governance: None,
+    };
+    let res = migrate(deps.as_mut(), mock_env(), msg.clone()).unwrap();
+    assert!(res.messages.is_empty());
+
+    /* Other contracts cannot be migrated into a pool */
+    set_contract_version(&mut deps.storage, "crates.io:factory", "0.1.0")
+        .unwrap();
+    match migrate(deps.as_mut(), mock_env(), msg) {
+        Err(ContractError::UnsupportedMigration { contract, .. }) => {
+            assert_eq!(contract, "crates.io:factory")
+        }
+        _ => panic!("Pool migrated from another contract"),
+    }
+}
+
+#[test]
+fn factory_leverage_reset() {
+    let mut deps = mock_dependencies(&[]);
+    let mut env = mock_env();
+
+    /* mTSLA pool init, instantiated by the "leveraged" factory */
+    mtsla_ust_2x_init(&mut deps);
+
+    /* Only the factory may reset leverage */
+    let msg = ExecuteMsg::SetDailyLeverageReference {};
+    match execute(deps.as_mut(), env.clone(), mock_info("stranger", &[]), msg) {
+        Err(ContractError::Unauthorized {}) => {}
+        _ => panic!("Must return unauthorized error"),
+    }
+
+    /* Reset after a 10% rise in mTSLA */
+    set_mtsla_price(&mut deps, 1_100_000_000);
+    env.block.time = env.block.time.plus_seconds(60);
+    let msg = ExecuteMsg::SetDailyLeverageReference {};
+    execute(deps.as_mut(), env.clone(), mock_info("leveraged", &[]), msg)
+        .unwrap();
+
+    let expected = PriceSnapshot {
+        asset_price: Uint128::new(1_100_000_000),
+        leveraged_price: Uint128::new(1_200_000_000),
+        timestamp: env.block.time.seconds(),
+    };
+    let bin =
+        query(deps.as_ref(), env.clone(), QueryMsg::PoolState {}).unwrap();
+    let pool_state: PoolStateResponse = from_binary(&bin).unwrap();
+    assert_eq!(pool_state.opening_snapshot, expected);
+
+    /* The new reference is the latest entry in the price history */
+    let msg = QueryMsg::PriceHistory {
+        start_after: None,
+        end_before: None,
+        limit: None,
+        order: Some(OrderBy::Descending),
+    };
+    let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+    let history: PriceHistoryResponse = from_binary(&bin).unwrap();
+    assert_eq!(history.price_history.len(), 2);
+    assert_eq!(history.price_history[0], expected);
+
+    /* Paused and settled pools ignore resets rather than failing the
+     * factory's broadcast */
+    set_mtsla_price(&mut deps, 1_000_000_000);
+    for status in [PoolStatus::Paused, PoolStatus::Settled] {
+        let msg = ExecuteMsg::SetStatus { status };
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("governance", &[]),
+            msg,
+        )
+        .unwrap();
+
+        env.block.time = env.block.time.plus_seconds(60);
+        let msg = ExecuteMsg::SetDailyLeverageReference {};
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("leveraged", &[]),
+            msg,
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+
+        let bin =
+            query(deps.as_ref(), env.clone(), QueryMsg::PoolState {}).unwrap();
+        let pool_state: PoolStateResponse = from_binary(&bin).unwrap();
+        assert_eq!(pool_state.opening_snapshot, expected);
+    }
+}
+
+#[test]
+fn hourly_leverage_reset() {
+    let mut deps = mock_dependencies(&[]);
+    let mut env = mock_env();
+    deps.querier.with_terraswap_pools(&[(
+        &"mTSLA-UST".to_string(),
+        (
+            &"uusd".to_string(),
+            &Uint128::from(1_000_000_000_000u128),
+            &"mTSLA".to_string(),
+            &Uint128::from(1_000_000_000u128),
+        ),
+    )]);
+
+    /* History has to cover the TWAP window */
+    let msg = InstantiateMsg {
+        price_data_n: Some(2),
+        ..mtsla_ust_2x_instantiate_msg()
+    };
+    match instantiate(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("leveraged", &[]),
+        msg,
+    ) {
+        Err(ContractError::InvalidPoolParams {}) => {}
+        _ => panic!("Must return invalid pool params error"),
+    }
+
+    /* Forex style pool resetting every hour */
+    let msg = InstantiateMsg {
+        price_data_expiry: Some(5 * 60),
+        leverage_expiry: Some(60 * 60),
+        price_data_n: Some(12 * 24),
+        ..mtsla_ust_2x_instantiate_msg()
+    };
+    instantiate(deps.as_mut(), env.clone(), mock_info("leveraged", &[]), msg)
+        .unwrap();
+    reply_token_instantiated(
+        &mut deps,
+        LEVERAGED_TOKEN_REPLY_ID,
+        "leveraged_token",
+    );
+    reply_token_instantiated(
+        &mut deps,
+        LIQUIDITY_TOKEN_REPLY_ID,
+        "liquidity_token",
+    );
+
+    let bin = query(deps.as_ref(), env.clone(), QueryMsg::Hyperparameters {})
+        .unwrap();
+    let hyper_p: HyperparametersResponse = from_binary(&bin).unwrap();
+    assert_eq!(hyper_p.price_data_expiry, 5 * 60);
+    assert_eq!(hyper_p.leverage_expiry, 60 * 60);
+    assert_eq!(hyper_p.price_data_n, 12 * 24);
+
+    /* An hour later the next transaction resets leverage */
+    env.block.time = env.block.time.plus_seconds(60 * 60);
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(100_000_000),
+        msg: to_binary(&Cw20HookMsg::ProvideLiquidity {}).unwrap(),
+    });
+    execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg).unwrap();
+
+    let bin =
+        query(deps.as_ref(), env.clone(), QueryMsg::PoolState {}).unwrap();
+    let pool_state: PoolStateResponse = from_binary(&bin).unwrap();
+    let now = env.block.time.seconds();
+    assert_eq!(pool_state.opening_snapshot.timestamp, now - now % (60 * 60));
+}
+
+#[test]
+fn keeper_poke() {
+    let mut deps = mock_dependencies(&[]);
+    let mut env = mock_env();
+    deps.querier.with_terraswap_pools(&[(
+        &"mTSLA-UST".to_string(),
+        (
+            &"uusd".to_string(),
+            &Uint128::from(1_000_000_000_000u128),
+            &"mTSLA".to_string(),
+            &Uint128::from(1_000_000_000u128),
+        ),
+    )]);
+    let msg = InstantiateMsg {
+        keeper_reward: Some(Uint128::new(1_000_000)),
+        ..mtsla_ust_2x_instantiate_msg()
+    };
+    instantiate(deps.as_mut(), env.clone(), mock_info("leveraged", &[]), msg)
+        .unwrap();
+
+    /* Nothing is due right after instantiation */
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("keeper", &[]),
+        ExecuteMsg::Poke {},
+    );
+    match res {
+        Err(ContractError::NothingToPoke {}) => {}
+        _ => panic!("Must return nothing to poke error"),
+    }
+
+    /* Fund the budget with 1.5 mTSLA */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "funder".to_string(),
+        amount: Uint128::new(1_500_000),
+        msg: to_binary(&Cw20HookMsg::FundKeeperBudget {}).unwrap(),
+    });
+    execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg).unwrap();
+
+    /* A snapshot is due after 15 minutes */
+    env.block.time = env.block.time.plus_seconds(15 * 60);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("keeper", &[]),
+        ExecuteMsg::Poke {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: String::from("mTSLA"),
+            funds: vec![],
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: String::from("keeper"),
+                amount: Uint128::new(1_000_000),
+            })
+            .unwrap(),
+        })
+    );
+
+    /* Poking again in the same block does nothing */
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("keeper", &[]),
+        ExecuteMsg::Poke {},
+    );
+    match res {
+        Err(ContractError::NothingToPoke {}) => {}
+        _ => panic!("Must return nothing to poke error"),
+    }
+
+    /* The next poke is paid whatever is left of the budget */
+    env.block.time = env.block.time.plus_seconds(15 * 60);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("keeper", &[]),
+        ExecuteMsg::Poke {},
+    )
+    .unwrap();
+    assert_eq!(res.attributes[2].value, String::from("500000"));
+
+    let bin =
+        query(deps.as_ref(), env.clone(), QueryMsg::PoolState {}).unwrap();
+    let pool_state: PoolStateResponse = from_binary(&bin).unwrap();
+    assert_eq!(pool_state.keeper_budget, Uint128::zero());
+    assert_eq!(pool_state.assets_in_reserve, Uint128::zero());
+
+    /* Once the budget is spent pokes still run but pay nothing */
+    env.block.time = env.block.time.plus_seconds(15 * 60);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("keeper", &[]),
+        ExecuteMsg::Poke {},
+    )
+    .unwrap();
+    assert!(res.messages.is_empty());
+
+    /* Top the budget up with 2 mTSLA next to 100 mTSLA of liquidity */
+    reply_token_instantiated(
+        &mut deps,
+        LEVERAGED_TOKEN_REPLY_ID,
+        "leveraged_token",
+    );
+    reply_token_instantiated(
+        &mut deps,
+        LIQUIDITY_TOKEN_REPLY_ID,
+        "liquidity_token",
+    );
+    for (sender, amount, msg) in [
+        ("funder", 2_000_000, Cw20HookMsg::FundKeeperBudget {}),
+        ("provider", 100_000_000, Cw20HookMsg::ProvideLiquidity {}),
+    ] {
+        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: sender.to_string(),
+            amount: Uint128::new(amount),
+            msg: to_binary(&msg).unwrap(),
+        });
+        execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg)
+            .unwrap();
+    }
+
+    /* Settling the pool owes the unspent budget to providers */
+    let msg = ExecuteMsg::SetStatus {
+        status: PoolStatus::Settled,
+    };
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("governance", &[]),
+        msg,
+    )
+    .unwrap();
+    let bin =
+        query(deps.as_ref(), env.clone(), QueryMsg::PoolState {}).unwrap();
+    let pool_state: PoolStateResponse = from_binary(&bin).unwrap();
+    assert_eq!(pool_state.keeper_budget, Uint128::zero());
+    let bin =
+        query(deps.as_ref(), env.clone(), QueryMsg::Settlement {}).unwrap();
+    let settlement: SettlementResponse = from_binary(&bin).unwrap();
+    assert_eq!(settlement.liquidity_assets, Uint128::new(102_000_000));
+
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(100_000_000),
+        msg: to_binary(&Cw20HookMsg::ClaimSettlement {}).unwrap(),
+    });
+    let res =
+        execute(deps.as_mut(), env, mock_info("liquidity_token", &[]), msg)
+            .unwrap();
+    assert_eq!(
+        cw20_msg_at(&res, 1),
+        (
+            "mTSLA".to_string(),
+            Cw20ExecuteMsg::Transfer {
+                recipient: "provider".to_string(),
+                amount: Uint128::new(102_000_000),
+            }
+        )
+    );
+}
+
+#[test]
+fn native_pool() {
+    let mut deps = mock_dependencies(&[]);
+    let env = mock_env();
+
+    /* Pool over uusd priced in KRT at 1000:1, with a 1% tax on transfers */
+    deps.querier.with_terraswap_pools(&[(
+        &"UST-KRT".to_string(),
+        (
+            &"uusd".to_string(),
+            &Uint128::from(1_000_000_000u128),
+            &"KRT".to_string(),
+            &Uint128::from(1_000_000_000_000u128),
+        ),
+    )]);
+    deps.querier
+        .with_tax(Decimal::percent(1), &[("uusd", Uint128::new(1_000_000))]);
+
+    /* A native pool has no Cw20 asset */
+    let msg = InstantiateMsg {
+        terraswap_pair_addr: String::from("UST-KRT"),
+        leveraged_asset_denom: Some(String::from("uusd")),
+        ..mtsla_ust_2x_instantiate_msg()
+    };
+    match instantiate(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("leveraged", &[]),
+        msg,
+    ) {
+        Err(ContractError::InvalidPoolParams {}) => {}
+        _ => panic!("Must return invalid pool params error"),
+    }
+
+    let msg = InstantiateMsg {
+        terraswap_pair_addr: String::from("UST-KRT"),
+        leveraged_asset_addr: String::new(),
+        leveraged_asset_denom: Some(String::from("uusd")),
+        ..mtsla_ust_2x_instantiate_msg()
+    };
+    instantiate(deps.as_mut(), env.clone(), mock_info("leveraged", &[]), msg)
+        .unwrap();
+    reply_token_instantiated(
+        &mut deps,
+        LEVERAGED_TOKEN_REPLY_ID,
+        "leveraged_token",
+    );
+    reply_token_instantiated(
+        &mut deps,
+        LIQUIDITY_TOKEN_REPLY_ID,
+        "liquidity_token",
+    );
+
+    let bin = query(deps.as_ref(), env.clone(), QueryMsg::Hyperparameters {})
+        .unwrap();
+    let hyper_p: HyperparametersResponse = from_binary(&bin).unwrap();
+    assert_eq!(hyper_p.leveraged_asset_addr, None);
+    assert_eq!(hyper_p.leveraged_asset_denom, Some(String::from("uusd")));
+
+    /* Cw20 deposits are refused */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(100_000_000),
+        msg: to_binary(&Cw20HookMsg::ProvideLiquidity {}).unwrap(),
+    });
+    match execute(deps.as_mut(), env.clone(), mock_info("KRT", &[]), msg) {
+        Err(ContractError::WrongAssetLOL {}) => {}
+        _ => panic!("Must return wrong asset error"),
+    }
+
+    /* Provide 100 UST as liquidity */
+    let info = mock_info("provider", &coins(100_000_000, "uusd"));
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::ProvideLiquidity {},
+    )
+    .unwrap();
+
+    /* Only the pool's coin can be minted with */
+    let msg = ExecuteMsg::MintLeveragedPosition {
+        min_received: None,
+        max_leveraged_price: None,
+        deadline: None,
+    };
+    let info = mock_info("minter", &coins(10_000_000, "uluna"));
+    match execute(deps.as_mut(), env.clone(), info, msg.clone()) {
+        Err(ContractError::Payment(_)) => {}
+        _ => panic!("Must return payment error"),
+    }
+
+    /* Mint 9.95 leveraged UST with 10 UST after the 0.5% premium */
+    let info = mock_info("minter", &coins(10_000_000, "uusd"));
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+    let (token, mint_msg) = cw20_msg_at(&res, 0);
+    assert_eq!(token, "leveraged_token");
+    assert_eq!(
+        mint_msg,
+        Cw20ExecuteMsg::Mint {
+            recipient: "minter".to_string(),
+            amount: Uint128::new(9_950_000),
+        }
+    );
+
+    /* Burning 4 leveraged UST pays out 4 UST less the tax */
+    deps.querier.with_token_balances(&[(
+        &"leveraged_token".to_string(),
+        &[(&"minter".to_string(), &Uint128::new(9_950_000))],
+    )]);
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "minter".to_string(),
+        amount: Uint128::new(4_000_000),
+        msg: to_binary(&Cw20HookMsg::BurnLeveragedPosition {
+            min_received: None,
+            deadline: None,
+            receive_as: None,
+        })
+        .unwrap(),
+    });
+    let res =
+        execute(deps.as_mut(), env, mock_info("leveraged_token", &[]), msg)
+            .unwrap();
+    assert_eq!(
+        res.messages[1].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: "minter".to_string(),
+            amount: coins(3_960_396, "uusd"),
+        })
+    );
+}
+
+#[test]
+fn zap_in() {
+    let mut deps = mock_dependencies(&[]);
+    let env = mock_env();
+
+    /* mTSLA pool init */
+    mtsla_ust_2x_init(&mut deps);
+
+    /* Provide 100 mTSLA as liquidity */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(100_000_000),
+        msg: to_binary(&Cw20HookMsg::ProvideLiquidity {}).unwrap(),
+    });
+    execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg).unwrap();
+    deps.querier.with_token_balances(&[(
+        &"mTSLA".to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(100_000_000))],
+    )]);
+
+    /* Something has to be swapped */
+    let msg = ExecuteMsg::ZapIn {
+        max_spread: Some(Decimal::percent(1)),
+        min_received: Some(Uint128::new(9_900_000)),
+        max_leveraged_price: None,
+        deadline: None,
+    };
+    match execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("zapper", &[]),
+        msg.clone(),
+    ) {
+        Err(ContractError::Payment(_)) => {}
+        _ => panic!("Must return payment error"),
+    }
+
+    /* Swap 10,000 UST for mTSLA */
+    let info = mock_info("zapper", &coins(10_000_000_000, "uusd"));
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(res.messages[0].id, ZAP_IN_REPLY_ID);
+    assert_eq!(res.messages[0].reply_on, ReplyOn::Success);
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: String::from("mTSLA-UST"),
+            funds: coins(10_000_000_000, "uusd"),
+            msg: to_binary(&PairExecuteMsg::Swap {
+                offer_asset: Asset {
+                    info: AssetInfo::NativeToken {
+                        denom: String::from("uusd"),
+                    },
+                    amount: Uint128::new(10_000_000_000),
+                },
+                belief_price: None,
+                max_spread: Some(Decimal::percent(1)),
+                to: None,
+            })
+            .unwrap(),
+        })
+    );
+
+    /* The swap returns 10 mTSLA, which are minted with */
+    deps.querier.with_token_balances(&[(
+        &"mTSLA".to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::new(110_000_000))],
+    )]);
+    let swapped = Reply {
+        id: ZAP_IN_REPLY_ID,
+        result: ContractResult::Ok(SubMsgExecutionResponse {
+            events: vec![],
+            data: None,
+        }),
+    };
+    let res = reply(deps.as_mut(), env.clone(), swapped.clone()).unwrap();
+    let (token, mint_msg) = cw20_msg_at(&res, 0);
+    assert_eq!(token, "leveraged_token");
+    assert_eq!(
+        mint_msg,
+        Cw20ExecuteMsg::Mint {
+            recipient: "zapper".to_string(),
+            amount: Uint128::new(9_950_000),
+        }
+    );
+
+    /* Each swap mints exactly once */
+    assert!(reply(deps.as_mut(), env, swapped).is_err());
+}
+
+#[test]
+fn zap_out() {
+    let mut deps = mock_dependencies(&[]);
+    let env = mock_env();
+
+    /* mTSLA pool init */
+    mtsla_ust_2x_init(&mut deps);
+
+    /* Provide 100 mTSLA as liquidity */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(100_000_000),
+        msg: to_binary(&Cw20HookMsg::ProvideLiquidity {}).unwrap(),
+    });
+    execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg).unwrap();
+
+    /* Mint 9.95 leveraged mTSLA with 10 mTSLA */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "minter".to_string(),
+        amount: Uint128::new(10_000_000),
+        msg: to_binary(&Cw20HookMsg::MintLeveragedPosition {
+            min_received: None,
+            max_leveraged_price: None,
+            deadline: None,
+        })
+        .unwrap(),
+    });
+    execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg).unwrap();
+
+    /* UST transfers are taxed at most 1 UST */
+    deps.querier
+        .with_tax(Decimal::permille(1), &[("uusd", Uint128::new(1_000_000))]);
+
+    /* Burn 4 leveraged mTSLA, asking for at least 1,900 UST at no more than
+     * 1% spread */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "minter".to_string(),
+        amount: Uint128::new(4_000_000),
+        msg: to_binary(&Cw20HookMsg::BurnLeveragedPosition {
+            min_received: None,
+            deadline: None,
+            receive_as: Some(ReceiveAs::Stable {
+                min_output: Some(Uint128::new(1_900_000_000)),
+                max_spread: Some(Decimal::percent(1)),
+            }),
+        })
+        .unwrap(),
+    });
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("leveraged_token", &[]),
+        msg,
+    )
+    .unwrap();
+
+    /* The redeemed 4 mTSLA are sent to the pair to be swapped */
+    assert_eq!(res.messages.len(), 2);
+    assert_eq!(res.messages[1].id, ZAP_OUT_REPLY_ID);
+    assert_eq!(res.messages[1].reply_on, ReplyOn::Success);
+    let (token, send_msg) = cw20_msg_at(&res, 1);
+    assert_eq!(token, "mTSLA");
+    assert_eq!(
+        send_msg,
+        Cw20ExecuteMsg::Send {
+            contract: "mTSLA-UST".to_string(),
+            amount: Uint128::new(4_000_000),
+            msg: to_binary(&PairCw20HookMsg::Swap {
+                belief_price: None,
+                max_spread: Some(Decimal::percent(1)),
+                to: None,
+            })
+            .unwrap(),
+        }
+    );
+
+    /* The swap returns 2,000 UST, which are forwarded to the minter less the
+     * tax */
+    deps.querier
+        .with_native_balances(MOCK_CONTRACT_ADDR, coins(2_000_000_000, "uusd"));
+    let swapped = Reply {
+        id: ZAP_OUT_REPLY_ID,
+        result: ContractResult::Ok(SubMsgExecutionResponse {
+            events: vec![],
+            data: None,
+        }),
+    };
+    let res = reply(deps.as_mut(), env.clone(), swapped.clone()).unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: "minter".to_string(),
+            amount: coins(1_999_000_000, "uusd"),
+        })
+    );
+
+    /* Each swap pays out exactly once */
+    assert!(reply(deps.as_mut(), env.clone(), swapped.clone()).is_err());
+
+    /* Withdraw 10 mTSLA of liquidity, asking for all the UST the swap returns
+     * which leaves nothing for the tax */
+    deps.querier
+        .with_native_balances(MOCK_CONTRACT_ADDR, vec![]);
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(10_000_000),
+        msg: to_binary(&Cw20HookMsg::WithdrawLiquidity {
+            min_received: None,
+            max_leveraged_price: None,
+            deadline: None,
+            receive_as: Some(ReceiveAs::Stable {
+                min_output: Some(Uint128::new(4_990_000_000)),
+                max_spread: None,
+            }),
+        })
+        .unwrap(),
+    });
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("liquidity_token", &[]),
+        msg,
+    )
+    .unwrap();
+    assert_eq!(res.messages[1].id, ZAP_OUT_REPLY_ID);
+
+    deps.querier
+        .with_native_balances(MOCK_CONTRACT_ADDR, coins(4_990_000_000, "uusd"));
+    match reply(deps.as_mut(), env, swapped) {
+        Err(ContractError::SlippageLimitExceeded { expected, actual }) => {
+            assert_eq!(expected, Uint128::new(4_990_000_000));
+            assert_eq!(actual, Uint128::new(4_989_000_000));
+        }
+        _ => panic!("Must return slippage error"),
+    }
+}
+
+#[test]
+fn liquidation() {
+    let mut deps = mock_dependencies(&[]);
+
+    /* mTSLA pool init, liquidated as soon as rebalancing is needed */
+    deps.querier.with_terraswap_pools(&[(
+        &"mTSLA-UST".to_string(),
+        (
+            &"uusd".to_string(),
+            &Uint128::from(1_000_000_000_000u128),
+            &"mTSLA".to_string(),
+            &Uint128::from(1_000_000_000u128),
+        ),
+    )]);
+    let msg = InstantiateMsg {
+        liquidation_ratio: Some(Uint128::new(2_000_000)),
+        ..mtsla_ust_2x_instantiate_msg()
+    };
+    instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg)
+        .unwrap();
+    reply_token_instantiated(
+        &mut deps,
+        LEVERAGED_TOKEN_REPLY_ID,
+        "leveraged_token",
+    );
+    reply_token_instantiated(
+        &mut deps,
+        LIQUIDITY_TOKEN_REPLY_ID,
+        "liquidity_token",
+    );
+
+    /* Provide 100 mTSLA as liquidity */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(100_000_000),
+        msg: to_binary(&Cw20HookMsg::ProvideLiquidity {}).unwrap(),
+    });
+    execute(deps.as_mut(), mock_env(), mock_info("mTSLA", &[]), msg).unwrap();
+
+    /* Mint right up to the minimum PR of 2.5 */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "minter".to_string(),
+        amount: Uint128::new(67_226_890),
+        msg: to_binary(&Cw20HookMsg::MintLeveragedPosition {
+            min_received: None,
+            max_leveraged_price: None,
+            deadline: None,
+        })
+        .unwrap(),
+    });
+    execute(deps.as_mut(), mock_env(), mock_info("mTSLA", &[]), msg).unwrap();
+
+    /* A healthy pool cannot be liquidated, nor settlements claimed */
+    let msg = ExecuteMsg::Liquidate {};
+    match execute(deps.as_mut(), mock_env(), mock_info("keeper", &[]), msg) {
+        Err(ContractError::LiquidationNotNeeded {}) => {}
+        _ => panic!("Pool was liquidated above the liquidation ratio"),
+    }
+    let claim = |sender: &str, amount: u128| {
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: sender.to_string(),
+            amount: Uint128::new(amount),
+            msg: to_binary(&Cw20HookMsg::ClaimSettlement {}).unwrap(),
+        })
+    };
+    match execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("leveraged_token", &[]),
+        claim("minter", 1_000_000),
+    ) {
+        Err(ContractError::NotLiquidating {}) => {}
+        _ => panic!("Settlement claimed from a healthy pool"),
+    }
+
+    /* A 40% spike in the spot price alone leaves the TWAP healthy */
+    set_mtsla_price(&mut deps, 1_400_000_000);
+    let msg = ExecuteMsg::Liquidate {};
+    match execute(deps.as_mut(), mock_env(), mock_info("keeper", &[]), msg) {
+        Err(ContractError::LiquidationNotNeeded {}) => {}
+        _ => panic!("Pool was liquidated at a spot price"),
+    }
+
+    /* An hour of the 40% rally pushes the TWAP below the ratio as well */
+    let mut env = mock_env();
+    for _ in 0..3 {
+        env.block.time = env.block.time.plus_seconds(30 * 60);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("keeper", &[]),
+            ExecuteMsg::Poke {},
+        )
+        .unwrap();
+    }
+    let msg = ExecuteMsg::Liquidate {};
+    execute(deps.as_mut(), env.clone(), mock_info("keeper", &[]), msg).unwrap();
+
+    /* Minters are owed their unleveraged equivalence and providers the rest */
+    let bin =
+        query(deps.as_ref(), mock_env(), QueryMsg::Settlement {}).unwrap();
+    let settlement: SettlementResponse = from_binary(&bin).unwrap();
+    assert_eq!(settlement.leveraged_assets, Uint128::new(86_002_400));
+    assert_eq!(settlement.leveraged_pool_share, Uint128::new(66_890_756));
+    assert_eq!(settlement.liquidity_assets, Uint128::new(81_224_490));
+    assert_eq!(settlement.asset_pool_share, Uint128::new(100_000_000));
+
+    /* The pool is frozen */
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "minter".to_string(),
+        amount: Uint128::new(10_000_000),
+        msg: to_binary(&Cw20HookMsg::MintLeveragedPosition {
+            min_received: None,
+            max_leveraged_price: None,
+            deadline: None,
+        })
+        .unwrap(),
+    });
+    match execute(deps.as_mut(), mock_env(), mock_info("mTSLA", &[]), msg) {
+        Err(ContractError::PoolLiquidating {}) => {}
+        _ => panic!("Minted into a liquidated pool"),
+    }
+    let msg = ExecuteMsg::Rebalance {};
+    match execute(deps.as_mut(), mock_env(), mock_info("keeper", &[]), msg) {
+        Err(ContractError::PoolLiquidating {}) => {}
+        _ => panic!("Rebalanced a liquidated pool"),
+    }
+
+    /* Half of the leveraged tokens claim half the minters' settlement */
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("leveraged_token", &[]),
+        claim("minter", 33_445_378),
+    )
+    .unwrap();
+    let (token, burn_msg) = cw20_msg_at(&res, 0);
+    assert_eq!(token, "leveraged_token");
+    assert_eq!(
+        burn_msg,
+        Cw20ExecuteMsg::Burn {
+            amount: Uint128::new(33_445_378)
+        }
+    );
+    let (token, claim_msg) = cw20_msg_at(&res, 1);
+    assert_eq!(token, "mTSLA");
+    assert_eq!(
+        claim_msg,
+        Cw20ExecuteMsg::Transfer {
+            recipient: "minter".to_string(),
+            amount: Uint128::new(43_001_200),
+        }
+    );
+
+    /* Providers claim what is left of the reserve */
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("liquidity_token", &[]),
+        claim("provider", 100_000_000),
+    )
+    .unwrap();
+    let (token, claim_msg) = cw20_msg_at(&res, 1);
+    assert_eq!(token, "mTSLA");
+    assert_eq!(
+        claim_msg,
+        Cw20ExecuteMsg::Transfer {
+            recipient: "provider".to_string(),
+            amount: Uint128::new(81_224_490),
+        }
+    );
+
+    /* Nobody claims more than their share */
+    match execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("liquidity_token", &[]),
+        claim("provider", 1),
+    ) {
+        Err(ContractError::InsufficientFunds {}) => {}
+        _ => panic!("Claimed more than the remaining share"),
+    }
+}
+
+#[test]
+fn pool_status() {
+    let mut deps = mock_dependencies(&[]);
+    let mut env = mock_env();
+
+    /* mTSLA pool init with a guardian, pausing on 20% moves and paying
+     * keepers 0.1 mTSLA */
+    deps.querier.with_terraswap_pools(&[(
+        &"mTSLA-UST".to_string(),
+        (
+            &"uusd".to_string(),
+            &Uint128::from(1_000_000_000_000u128),
+            &"mTSLA".to_string(),
+            &Uint128::from(1_000_000_000u128),
+        ),
+    )]);
+    let msg = InstantiateMsg {
+        guardian: Some(String::from("guardian")),
+        circuit_breaker: Some(Uint128::new(200_000)),
+        keeper_reward: Some(Uint128::new(100_000)),
+        ..mtsla_ust_2x_instantiate_msg()
+    };
+    instantiate(deps.as_mut(), env.clone(), mock_info("creator", &[]), msg)
+        .unwrap();
+    reply_token_instantiated(
+        &mut deps,
+        LEVERAGED_TOKEN_REPLY_ID,
+        "leveraged_token",
+    );
+    reply_token_instantiated(
+        &mut deps,
+        LIQUIDITY_TOKEN_REPLY_ID,
+        "liquidity_token",
+    );
+    let status = |deps: &OwnedMockDeps| {
+        let bin =
+            query(deps.as_ref(), mock_env(), QueryMsg::PoolState {}).unwrap();
+        from_binary::<PoolStateResponse>(&bin).unwrap().status
+    };
+    let set_status = |sender: &str, status: PoolStatus| {
+        (mock_info(sender, &[]), ExecuteMsg::SetStatus { status })
+    };
+    let mint_msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "minter".to_string(),
+        amount: Uint128::new(10_000_000),
+        msg: to_binary(&Cw20HookMsg::MintLeveragedPosition {
+            min_received: None,
+            max_leveraged_price: None,
+            deadline: None,
+        })
+        .unwrap(),
+    });
+    let provide_msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "provider".to_string(),
+        amount: Uint128::new(100_000_000),
+        msg: to_binary(&Cw20HookMsg::ProvideLiquidity {}).unwrap(),
+    });
+
+    /* Only the guardian and governance may change the status */
+    let (info, msg) = set_status("anyone", PoolStatus::MintPaused);
+    match execute(deps.as_mut(), env.clone(), info, msg) {
+        Err(ContractError::Unauthorized {}) => {}
+        _ => panic!("Must return unauthorized error"),
+    }
+
+    /* Pausing mints still lets liquidity in */
+    let (info, msg) = set_status("guardian", PoolStatus::MintPaused);
+    execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+    assert_eq!(status(&deps), PoolStatus::MintPaused);
+    match execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("mTSLA", &[]),
+        mint_msg.clone(),
+    ) {
+        Err(ContractError::MintsPaused {}) => {}
+        _ => panic!("Minted while mints were paused"),
+    }
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("mTSLA", &[]),
+        provide_msg.clone(),
+    )
+    .unwrap();
+
+    let (info, msg) = set_status("governance", PoolStatus::Active);
+    execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("mTSLA", &[]),
+        mint_msg.clone(),
+    )
+    .unwrap();
+
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "funder".to_string(),
+        amount: Uint128::new(1_000_000),
+        msg: to_binary(&Cw20HookMsg::FundKeeperBudget {}).unwrap(),
+    });
+    execute(deps.as_mut(), env.clone(), mock_info("mTSLA", &[]), msg).unwrap();
+
+    /* mTSLA jumps 30%, so the next mint trips the circuit breaker before it
+     * is priced and gets its deposit back */
+    set_mtsla_price(&mut deps, 1_300_000_000);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("mTSLA", &[]),
+        mint_msg.clone(),
+    )
+    .unwrap();
+    assert_eq!(status(&deps), PoolStatus::Paused);
+    assert_eq!(res.events[0].ty, "circuit_breaker");
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(
+        cw20_msg_at(&res, 0),
+        (
+            "mTSLA".to_string(),
+            Cw20ExecuteMsg::Transfer {
+                recipient: "minter".to_string(),
+                amount: Uint128::new(10_000_000),
+            }
+        )
+    );
+
+    /* A paused pool accepts nothing but status and hyperparameter changes */
+    match execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("mTSLA", &[]),
+        provide_msg,
+    ) {
+        Err(ContractError::PoolPaused {}) => {}
+        _ => panic!("Provided liquidity to a paused pool"),
+    }
+    match execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("keeper", &[]),
+        ExecuteMsg::Poke {},
+    ) {
+        Err(ContractError::PoolPaused {}) => {}
+        _ => panic!("Poked a paused pool"),
+    }
+    let msg = ExecuteMsg::UpdateHyperparameters {
+        minimum_protocol_ratio: Some(Uint128::new(2_750_000)),
+        rebalance_ratio: None,
+        mint_premium: None,
+        rebalance_premium: None,
+    };
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("governance", &[]),
+        msg,
+    )
+    .unwrap();
+    let bin = query(deps.as_ref(), env.clone(), QueryMsg::Hyperparameters {})
+        .unwrap();
+    let hyper_p: HyperparametersResponse = from_binary(&bin).unwrap();
+    assert_eq!(hyper_p.minimum_protocol_ratio, Uint128::new(2_750_000));
+    assert_eq!(status(&deps), PoolStatus::Paused);
+
+    /* Resuming takes the current price as the breaker's reference */
+    let (info, msg) = set_status("guardian", PoolStatus::Active);
+    execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+    assert_eq!(status(&deps), PoolStatus::Active);
+    match execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("keeper", &[]),
+        ExecuteMsg::Poke {},
+    ) {
+        Err(ContractError::NothingToPoke {}) => {}
+        _ => panic!("Must return nothing to poke error"),
+    }
+    env.block.time = env.block.time.plus_seconds(15 * 60);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("keeper", &[]),
+        ExecuteMsg::Poke {},
+    )
+    .unwrap();
+    assert_eq!(status(&deps), PoolStatus::Active);
+    assert_eq!(res.messages.len(), 1);
+
+    /* A poke tripping the circuit breaker earns the keeper nothing */
+    set_mtsla_price(&mut deps, 1_000_000_000);
+    env.block.time = env.block.time.plus_seconds(15 * 60);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("keeper", &[]),
+        ExecuteMsg::Poke {},
+    )
+    .unwrap();
+    assert_eq!(status(&deps), PoolStatus::Paused);
+    assert_eq!(res.events[0].ty, "circuit_breaker");
+    assert!(res.messages.is_empty());
+
+    let (info, msg) = set_status("guardian", PoolStatus::Active);
+    execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+    /* Settling liquidates the pool at the TWAP rather than the spot price */
+    let msg = QueryMsg::Twap {
+        window_seconds: 60 * 60,
+    };
+    let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+    let twap = from_binary::<TwapResponse>(&bin).unwrap().twap;
+    assert_ne!(twap.asset_price, current_asset_price(&deps, &env));
+    let (info, msg) = set_status("guardian", PoolStatus::Settled);
+    execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+    assert_eq!(status(&deps), PoolStatus::Settled);
+    let bin =
+        query(deps.as_ref(), env.clone(), QueryMsg::Settlement {}).unwrap();
+    let settlement: SettlementResponse = from_binary(&bin).unwrap();
+    assert_eq!(settlement.settled_at, twap);
+    assert_eq!(settlement.leveraged_pool_share, Uint128::new(9_950_000));
+    assert_eq!(settlement.asset_pool_share, Uint128::new(100_000_000));
+
+    let (info, msg) = set_status("governance", PoolStatus::Active);
+    match execute(deps.as_mut(), env, info, msg) {
+        Err(ContractError::PoolLiquidating {}) => {}
+        _ => panic!("Resumed a settled pool"),
+    }
+}
+
+#[test]
+fn pool_caps() {
+    let mut deps = mock_dependencies(&[]);
+
+    /* mTSLA pool init capped at 150 mTSLA in reserve, 30 mTSLA of minted
+     * value and 20 mTSLA per position */
+    deps.querier.with_terraswap_pools(&[(
+        &"mTSLA-UST".to_string(),
+        (
+            &"uusd".to_string(),
+            &Uint128::from(1_000_000_000_000u128),
+            &"mTSLA".to_string(),
+            &Uint128::from(1_000_000_000u128),
+        ),
+    )]);
+    let msg = InstantiateMsg {
+        max_total_leveraged_value: Some(Uint128::new(30_000_000)),
+        max_assets_in_reserve: Some(Uint128::new(150_000_000)),
+        max_position_per_address: Some(Uint128::new(20_000_000)),
+        ..mtsla_ust_2x_instantiate_msg()
+    };
+    instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg)
+        .unwrap();
+    reply_token_instantiated(
+        &mut deps,
+        LEVERAGED_TOKEN_REPLY_ID,
+        "leveraged_token",
+    );
+    reply_token_instantiated(
+        &mut deps,
+        LIQUIDITY_TOKEN_REPLY_ID,
+        "liquidity_token",
+    );
+    let capacity = |deps: &OwnedMockDeps| {
+        let bin =
+            query(deps.as_ref(), mock_env(), QueryMsg::AllPoolInfo {}).unwrap();
+        from_binary::<AllPoolInfoResponse>(&bin).unwrap().capacity
+    };
+    let provide = |amount: u128| {
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "provider".to_string(),
+            amount: Uint128::new(amount),
+            msg: to_binary(&Cw20HookMsg::ProvideLiquidity {}).unwrap(),
+        })
+    };
+    let mint = |minter: &str, amount: u128| {
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: minter.to_string(),
+            amount: Uint128::new(amount),
+            msg: to_binary(&Cw20HookMsg::MintLeveragedPosition {
+                min_received: None,
+                max_leveraged_price: None,
+                deadline: None,
+            })
+            .unwrap(),
+        })
+    };
+    assert_eq!(
+        capacity(&deps),
+        PoolCapacity {
+            remaining_leveraged_value: Some(Uint128::new(30_000_000)),
+            remaining_assets_in_reserve: Some(Uint128::new(150_000_000)),
+            max_position_per_address: Some(Uint128::new(20_000_000)),
+        }
+    );
+
+    /* Provide 100 mTSLA, leaving room for 50 more */
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("mTSLA", &[]),
+        provide(100_000_000),
+    )
+    .unwrap();
+    match execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("mTSLA", &[]),
+        provide(60_000_000),
+    ) {
+        Err(ContractError::ReserveCapExceeded {
+            remaining,
+            requested,
+        }) => {
+            assert_eq!(remaining, Uint128::new(50_000_000));
+            assert_eq!(requested, Uint128::new(60_000_000));
+        }
+        _ => panic!("Reserve grew past its cap"),
+    }
+
+    /* One address cannot mint past its cap in one go */
+    deps.querier.with_token_balances(&[(
+        &"leveraged_token".to_string(),
+        &[(&"whale".to_string(), &Uint128::zero())],
+    )]);
+    match execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("mTSLA", &[]),
+        mint("whale", 25_000_000),
+    ) {
+        Err(ContractError::PositionCapExceeded { cap, position }) => {
+            assert_eq!(cap, Uint128::new(20_000_000));
+            assert_eq!(position, Uint128::new(24_875_000));
+        }
+        _ => panic!("Position grew past its cap"),
+    }
+
+    /* Nor across mints */
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("mTSLA", &[]),
+        mint("whale", 15_000_000),
+    )
+    .unwrap();
+    deps.querier.with_token_balances(&[(
+        &"leveraged_token".to_string(),
+        &[(&"whale".to_string(), &Uint128::new(14_925_000))],
+    )]);
+    match execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("mTSLA", &[]),
+        mint("whale", 6_000_000),
+    ) {
+        Err(ContractError::PositionCapExceeded { position, .. }) => {
+            assert_eq!(position, Uint128::new(20_895_000));
+        }
+        _ => panic!("Position grew past its cap"),
+    }
+
+    /* Other minters share what is left of the pool's minted value */
+    match execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("mTSLA", &[]),
+        mint("minnow", 16_000_000),
+    ) {
+        Err(ContractError::LeveragedValueCapExceeded {
+            remaining,
+            requested,
+        }) => {
+            assert_eq!(remaining, Uint128::new(15_075_000));
+            assert_eq!(requested, Uint128::new(15_920_000));
+        }
+        _ => panic!("Minted value grew past its cap"),
+    }
+
+    assert_eq!(
+        capacity(&deps),
+        PoolCapacity {
+            remaining_leveraged_value: Some(Uint128::new(15_075_000)),
+            remaining_assets_in_reserve: Some(Uint128::new(35_000_000)),
+            max_position_per_address: Some(Uint128::new(20_000_000)),
+        }
     );
 }
@@ -0,0 +1,70 @@
+/*
+ * Pool tokens
+ *
+ * Helpers for the Cw20s a pool instantiates to represent its positions
+ */
+use crate::response::MsgInstantiateContractResponse;
+use cosmwasm_std::{
+    to_binary, Addr, Env, Reply, ReplyOn, StdError, StdResult, SubMsg, WasmMsg,
+};
+use cw20::{Cw20Coin, MinterResponse};
+use protobuf::Message;
+use terraswap::token::InstantiateMsg as TokenInstantiateMsg;
+
+/**
+ * Instantiate a Cw20 with the pool as its only minter. The new address is
+ * delivered to the reply entrypoint under `reply_id`
+ */
+pub fn instantiate_pool_token(
+    env: &Env,
+    token_code_id: u64,
+    name: &str,
+    symbol: &str,
+    initial_balances: Vec<Cw20Coin>,
+    reply_id: u64,
+) -> StdResult<SubMsg> {
+    Ok(SubMsg {
+        msg: WasmMsg::Instantiate {
+            admin: None,
+            code_id: token_code_id,
+            funds: vec![],
+            label: "".to_string(),
+            msg: to_binary(&TokenInstantiateMsg {
+                name: name.to_string(),
+                symbol: symbol.to_string(),
+                decimals: 6,
+                initial_balances,
+                mint: Some(MinterResponse {
+                    minter: env.contract.address.to_string(),
+                    cap: None,
+                }),
+            })?,
+        }
+        .into(),
+        gas_limit: None,
+        id: reply_id,
+        reply_on: ReplyOn::Success,
+    })
+}
+
+/**
+ * Extract the new contract address from a WasmMsg::Instantiate reply
+ */
+pub fn parse_instantiated_addr(msg: Reply) -> StdResult<Addr> {
+    let data = msg
+        .result
+        .into_result()
+        .map_err(StdError::generic_err)?
+        .data
+        .ok_or_else(|| StdError::generic_err("Missing instantiate data"))?;
+
+    let res: MsgInstantiateContractResponse =
+        Message::parse_from_bytes(data.as_slice()).map_err(|_| {
+            StdError::parse_err(
+                "MsgInstantiateContractResponse",
+                "Failed to instantiate token",
+            )
+        })?;
+
+    Ok(Addr::unchecked(res.get_contract_address()))
+}
@@ -0,0 +1,298 @@
+/*
+ * Price oracles
+ *
+ * Sources a pool may use to price its leveraged asset
+ */
+use crate::pool::{PriceSnapshot, TSPricePoint, PRECISION};
+use cosmwasm_std::{
+    to_binary, Addr, Env, QuerierWrapper, QueryRequest, StdError, StdResult, Uint128, WasmQuery,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use terraswap::asset::AssetInfo;
+use terraswap::pair::{PoolResponse as TerraSwapPoolResponse, QueryMsg as TerraSwapPairQueryMsg};
+
+/**
+ * Which price source a pool is configured with
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceSourceConfig {
+    /**
+     * Reserve ratio of the TerraSwap pair, cheap but trivially moved
+     * within a single block
+     */
+    TerraswapSpot {},
+
+    /**
+     * Time-weighted average of the TerraSwap prices the pool has recorded
+     * over the last `window_seconds`
+     */
+    TerraswapTwap { window_seconds: u64 },
+
+    /**
+     * Contract which has prices pushed to it and answers
+     * `PushOracleQueryMsg::Price`. Prices older than `max_age_seconds` are
+     * rejected
+     */
+    PushOracle {
+        oracle_addr: String,
+        max_age_seconds: u64,
+    },
+}
+
+impl Default for PriceSourceConfig {
+    fn default() -> Self {
+        PriceSourceConfig::TerraswapSpot {}
+    }
+}
+
+/**
+ * Anything able to price one unit of the leveraged asset in its denomination,
+ * precise out to 6 decimals
+ */
+pub trait PriceSource {
+    /**
+     * Price the pool values positions at
+     */
+    fn query_price(&self, env: &Env, querier: QuerierWrapper) -> StdResult<TSPricePoint>;
+
+    /**
+     * Raw reading which the pool should record into its price history. This
+     * is the price itself unless the source derives its price from history
+     */
+    fn query_observation(&self, env: &Env, querier: QuerierWrapper) -> StdResult<TSPricePoint> {
+        self.query_price(env, querier)
+    }
+}
+
+/**
+ * Spot price from the reserves of a TerraSwap pair
+ */
+pub struct TerraSwapSpot {
+    pub pair: Addr,
+    pub asset: Addr,
+}
+
+impl PriceSource for TerraSwapSpot {
+    fn query_price(&self, env: &Env, querier: QuerierWrapper) -> StdResult<TSPricePoint> {
+        /* Query TS contract */
+        let res: TerraSwapPoolResponse = querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+            contract_addr: self.pair.to_string(),
+            msg: to_binary(&TerraSwapPairQueryMsg::Pool {})?,
+        }))?;
+
+        /* Should always return 2 assets */
+        if res.assets.len() != 2 {
+            return Err(StdError::generic_err("Should always return 2 assets"));
+        }
+
+        /* Separate the primary asset from its denomination */
+        let mut asset_amt = 0u128;
+        let mut capital_amt = 0u128;
+        for a in res.assets {
+            let is_asset = match a.info {
+                AssetInfo::Token { contract_addr } => contract_addr == self.asset.as_str(),
+                AssetInfo::NativeToken { denom } => denom == self.asset.as_str(),
+            };
+
+            if is_asset {
+                asset_amt = a.amount.u128();
+            } else {
+                capital_amt = a.amount.u128();
+            }
+        }
+
+        if asset_amt == 0u128 || capital_amt == 0u128 {
+            return Err(StdError::generic_err("Should always return 2 assets"));
+        }
+
+        /* Derive price from pool volume */
+        Ok(TSPricePoint {
+            u_price: Uint128::from(capital_amt * PRECISION / asset_amt),
+            timestamp: env.block.time.seconds(),
+        })
+    }
+}
+
+/**
+ * Time-weighted average of recorded TerraSwap spot prices. The current spot
+ * price is recorded into history but carries no weight until a later block,
+ * s/t it cannot be moved within a single block
+ */
+pub struct TerraSwapTwap {
+    pub spot: TerraSwapSpot,
+    pub window_seconds: u64,
+
+    /* Recorded prices, oldest first */
+    pub observations: Vec<PriceSnapshot>,
+}
+
+impl PriceSource for TerraSwapTwap {
+    fn query_price(&self, env: &Env, querier: QuerierWrapper) -> StdResult<TSPricePoint> {
+        let spot = self.spot.query_price(env, querier)?;
+
+        let mut observations: Vec<TSPricePoint> = self
+            .observations
+            .iter()
+            .map(|snapshot| TSPricePoint {
+                u_price: snapshot.asset_price,
+                timestamp: snapshot.timestamp,
+            })
+            .collect();
+        observations.push(spot);
+
+        Ok(TSPricePoint {
+            u_price: time_weighted_average(&observations, spot.timestamp, self.window_seconds)?,
+            timestamp: spot.timestamp,
+        })
+    }
+
+    fn query_observation(&self, env: &Env, querier: QuerierWrapper) -> StdResult<TSPricePoint> {
+        self.spot.query_price(env, querier)
+    }
+}
+
+/**
+ * Queries answered by a push oracle
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PushOracleQueryMsg {
+    Price { asset: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PushOraclePriceResponse {
+    /* Price precise out to 6 decimals */
+    pub price: Uint128,
+
+    /* Time the price was pushed in seconds since 1970-01-01T00:00:00Z */
+    pub last_updated: u64,
+}
+
+/**
+ * Latest price pushed to an oracle contract
+ */
+pub struct PushOracle {
+    pub oracle: Addr,
+    pub asset: Addr,
+    pub max_age_seconds: u64,
+}
+
+impl PriceSource for PushOracle {
+    fn query_price(&self, env: &Env, querier: QuerierWrapper) -> StdResult<TSPricePoint> {
+        let res: PushOraclePriceResponse =
+            querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+                contract_addr: self.oracle.to_string(),
+                msg: to_binary(&PushOracleQueryMsg::Price {
+                    asset: self.asset.to_string(),
+                })?,
+            }))?;
+
+        let currently = env.block.time.seconds();
+        if currently.saturating_sub(res.last_updated) > self.max_age_seconds {
+            return Err(StdError::generic_err("Oracle price is stale"));
+        }
+
+        if res.price.is_zero() {
+            return Err(StdError::generic_err("Oracle price is zero"));
+        }
+
+        Ok(TSPricePoint {
+            u_price: res.price,
+            timestamp: res.last_updated,
+        })
+    }
+}
+
+/**
+ * Average of `observations` (oldest first) over the `window` seconds before
+ * `now`. Each price holds from its timestamp until the next observation, so
+ * an observation at `now` only counts when it is the only one in the window
+ */
+pub fn time_weighted_average(
+    observations: &[TSPricePoint],
+    now: u64,
+    window: u64,
+) -> StdResult<Uint128> {
+    let latest = observations
+        .last()
+        .ok_or_else(|| StdError::generic_err("No price observations"))?;
+    let start = now.saturating_sub(window);
+
+    let mut weighted_sum = Uint128::zero();
+    let mut total_weight = 0u64;
+    for (n, observation) in observations.iter().enumerate() {
+        let until = observations
+            .get(n + 1)
+            .map_or(now, |next| next.timestamp)
+            .min(now);
+        let from = observation.timestamp.max(start);
+
+        if until > from {
+            weighted_sum += observation
+                .u_price
+                .checked_mul(Uint128::from(until - from))?;
+            total_weight += until - from;
+        }
+    }
+
+    /* Nothing held for any time, so the oldest price in the window stands */
+    if total_weight == 0 {
+        let oldest = observations
+            .iter()
+            .find(|observation| observation.timestamp >= start)
+            .unwrap_or(latest);
+        return Ok(oldest.u_price);
+    }
+
+    Ok(weighted_sum / Uint128::from(total_weight))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(u_price: u128, timestamp: u64) -> TSPricePoint {
+        TSPricePoint {
+            u_price: Uint128::new(u_price),
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn twap_weights_by_time() {
+        /* 100 for 30s then 200 for 10s */
+        let observations = [point(100, 0), point(200, 30), point(999, 40)];
+        assert_eq!(
+            time_weighted_average(&observations, 40, 40).unwrap(),
+            Uint128::new(125)
+        );
+
+        /* Only the last 20s, observations before the window still hold */
+        assert_eq!(
+            time_weighted_average(&observations, 40, 20).unwrap(),
+            Uint128::new(150)
+        );
+    }
+
+    #[test]
+    fn twap_without_history() {
+        /* A lone observation at `now` is the price */
+        let observations = [point(999, 40)];
+        assert_eq!(
+            time_weighted_average(&observations, 40, 20).unwrap(),
+            Uint128::new(999)
+        );
+
+        /* Observations within the same block do not displace the first */
+        let observations = [point(100, 40), point(999, 40)];
+        assert_eq!(
+            time_weighted_average(&observations, 40, 20).unwrap(),
+            Uint128::new(100)
+        );
+
+        assert!(time_weighted_average(&[], 40, 20).is_err());
+    }
+}
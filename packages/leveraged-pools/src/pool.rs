@@ -1,4 +1,5 @@
-use cosmwasm_std::{Addr, StdResult, Uint128, Uint256};
+use crate::oracle::PriceSourceConfig;
+use cosmwasm_std::{Addr, Decimal, StdResult, Uint128, Uint256};
 use cw20::Cw20ReceiveMsg;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -7,6 +8,11 @@ use std::vec::Vec;
 
 pub const PRECISION: u128 = 1_000_000;
 
+/**
+ * Premiums are percentages precise out to 6 decimals, i.e. 0_500_000 is 0.5%
+ */
+pub const PREMIUM_PRECISION: u128 = 100 * PRECISION;
+
 /**
  * Timestamp in seconds since 1970-01-01T00:00:00Z
  */
@@ -29,6 +35,51 @@ pub struct PriceSnapshot {
     pub timestamp: u64,
 }
 
+/**
+ * Whether a pool's leveraged asset moves with or against its backing asset
+ */
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Default, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LeverageDirection {
+    /* Leveraged price rises by `leverage_amount` times the asset's change */
+    #[default]
+    Long,
+
+    /* Leveraged price falls by `leverage_amount` times the asset's change */
+    Short,
+}
+
+/**
+ * What a pool currently accepts
+ */
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Default, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PoolStatus {
+    /* Open for everything */
+    #[default]
+    Active,
+
+    /* No new leveraged positions, everything else carries on */
+    MintPaused,
+
+    /* Nothing but status and hyperparameter changes, no prices are queried */
+    Paused,
+
+    /* Liquidated for good, positions may only claim their settlement */
+    Settled,
+}
+
+impl PoolStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PoolStatus::Active => "active",
+            PoolStatus::MintPaused => "mint_paused",
+            PoolStatus::Paused => "paused",
+            PoolStatus::Settled => "settled",
+        }
+    }
+}
+
 /**
  * Hyperparameter init
  */
@@ -41,15 +92,157 @@ pub struct InstantiateMsg {
     pub rebalance_premium: Uint128,
     pub terraswap_pair_addr: String,
     pub leveraged_asset_addr: String,
+
+    /* Native coin backing the pool (e.g. "uusd") in place of a Cw20, in which
+     * case leveraged_asset_addr must be empty */
+    pub leveraged_asset_denom: Option<String>,
+
+    /* Code ID of the Cw20 contract issued to represent pool positions */
+    pub token_code_id: u64,
+
+    /* Address allowed to adjust hyperparameters, defaults to the instantiator
+     * or, for pools created by the factory, to the factory owner */
+    pub governance: Option<String>,
+
+    /* Where the asset price comes from, defaults to the TerraSwap spot price */
+    pub price_source: Option<PriceSourceConfig>,
+
+    /* Seconds of price history averaged into the TWAP mints, burns and
+     * withdrawals are checked against, defaults to an hour */
+    pub twap_window: Option<u64>,
+
+    /* Long or short (inverse) leverage, defaults to long */
+    pub direction: Option<LeverageDirection>,
+
+    /* Leveraged price, as a ratio of the price at the last leverage reset
+     * precise out to 6 decimals, at which minted positions are knocked out.
+     * Defaults to 0_050_000 (5%) */
+    pub knock_out_floor: Option<Uint128>,
+
+    /* Seconds between recorded price snapshots, defaults to 15 minutes */
+    pub price_data_expiry: Option<u64>,
+
+    /* Seconds between leverage resets, defaults to a day */
+    pub leverage_expiry: Option<u64>,
+
+    /* Seconds after 00:00 UTC at which leverage epochs start, less than
+     * leverage_expiry. Defaults to 0, resetting at midnight UTC */
+    pub leverage_reset_offset: Option<u64>,
+
+    /* Number of price snapshots kept, defaults to 90 days of 15 minute
+     * snapshots. Together with price_data_expiry these must cover the
+     * twap_window */
+    pub price_data_n: Option<u64>,
+
+    /* Backing assets paid from the keeper budget to whoever pokes the pool
+     * when a snapshot or reset is due, defaults to nothing */
+    pub keeper_reward: Option<Uint128>,
+
+    /* Protocol ratio, precise out to 6 decimals, below which anyone may
+     * liquidate the pool. At most the rebalance ratio, defaults to 1_000_000
+     * (100%) */
+    pub liquidation_ratio: Option<Uint128>,
+
+    /* Address allowed to pause the pool alongside governance, defaults to
+     * governance alone */
+    pub guardian: Option<String>,
+
+    /* Largest move in the asset price between consecutive snapshots, as a
+     * ratio precise out to 6 decimals, beyond which the pool pauses itself.
+     * Defaults to no circuit breaker */
+    pub circuit_breaker: Option<Uint128>,
+
+    /* Caps, all in backing assets and uncapped by default, on the value of
+     * all minted positions, on the reserve and on the value of any one
+     * address's minted position */
+    pub max_total_leveraged_value: Option<Uint128>,
+    pub max_assets_in_reserve: Option<Uint128>,
+    pub max_position_per_address: Option<Uint128>,
+}
+
+/**
+ * Parameters for upgrading a deployed pool. Pools deployed before contract
+ * versioning (0.1.0) tracked positions in storage and need both fields to
+ * move those positions into the pool tokens
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateMsg {
+    /* Code ID of the Cw20 contract issued to represent pool positions */
+    pub token_code_id: Option<u64>,
+
+    /* Address allowed to adjust hyperparameters */
+    pub governance: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
     Receive(Cw20ReceiveMsg),
-    WithdrawLiquidity { share_of_pool: Uint128 },
-    BurnLeveragedAsset { share_of_pool: Uint128 },
     SetDailyLeverageReference {},
+
+    /**
+     * Pools backed by a native coin take deposits as the coin sent along with
+     * these, in place of the equivalent Cw20 hooks
+     */
+    ProvideLiquidity {},
+    MintLeveragedPosition {
+        min_received: Option<Uint128>,
+        max_leveraged_price: Option<Uint128>,
+        deadline: Option<u64>,
+    },
+    FundKeeperBudget {},
+
+    /**
+     * Swap the sent coin (e.g. UST) into the backing asset through the pool's
+     * TerraSwap pair, failing beyond `max_spread`, and mint a leveraged
+     * position from the proceeds within the same transaction
+     */
+    ZapIn {
+        max_spread: Option<Decimal>,
+        min_received: Option<Uint128>,
+        max_leveraged_price: Option<Uint128>,
+        deadline: Option<u64>,
+    },
+
+    /**
+     * Shrink minted positions once the protocol ratio falls below the
+     * rebalance ratio at both the spot price and the TWAP, paying the caller
+     * the rebalance premium. Fails if no position would be left standing
+     */
+    Rebalance {},
+
+    /**
+     * Record a price snapshot and reset leverage if either is due, paying the
+     * caller the keeper reward. Fails if neither was due
+     */
+    Poke {},
+
+    /**
+     * Liquidate the pool once the protocol ratio falls below the liquidation
+     * ratio. Mints and everything else are frozen for good and positions may
+     * only claim their settlement
+     */
+    Liquidate {},
+
+    /**
+     * Pause or resume the pool on behalf of its guardian or governance.
+     * Setting it `settled` liquidates the pool at the current price whatever
+     * its protocol ratio, and a settled pool never resumes
+     */
+    SetStatus {
+        status: PoolStatus,
+    },
+
+    /**
+     * Governance adjustment of the pool's hyperparameters. Every parameter
+     * may only move within a bounded delta per call
+     */
+    UpdateHyperparameters {
+        minimum_protocol_ratio: Option<Uint128>,
+        rebalance_ratio: Option<Uint128>,
+        mint_premium: Option<Uint128>,
+        rebalance_premium: Option<Uint128>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -59,19 +252,113 @@ pub enum Cw20HookMsg {
     ProvideLiquidity {},
 
     /**
-     * Absorb a CW20 and open a leveraged position
+     * Absorb a CW20 and open a leveraged position of at least `min_received`
+     * position tokens, priced at no more than `max_leveraged_price`
+     */
+    MintLeveragedPosition {
+        min_received: Option<Uint128>,
+        max_leveraged_price: Option<Uint128>,
+        deadline: Option<u64>,
+    },
+
+    /**
+     * Burn the received leveraged position tokens and redeem them for their
+     * unleveraged equivalent, at least `min_received`
+     */
+    BurnLeveragedPosition {
+        min_received: Option<Uint128>,
+        deadline: Option<u64>,
+        receive_as: Option<ReceiveAs>,
+    },
+
+    /**
+     * Burn the received liquidity tokens and withdraw their share of the pool,
+     * at least `min_received`, while minted positions are valued at no more
+     * than `max_leveraged_price`
      */
-    MintLeveragedPosition {},
+    WithdrawLiquidity {
+        min_received: Option<Uint128>,
+        max_leveraged_price: Option<Uint128>,
+        deadline: Option<u64>,
+        receive_as: Option<ReceiveAs>,
+    },
+
+    /**
+     * Add the received backing assets to the budget keepers are rewarded from
+     */
+    FundKeeperBudget {},
+
+    /**
+     * Burn the received leveraged or liquidity tokens of a liquidated pool,
+     * or the leveraged tokens of knocked out positions, for their pro rata
+     * share of its settlement
+     */
+    ClaimSettlement {},
+}
+
+/**
+ * What burns and withdrawals pay out in: the backing asset itself, or the
+ * other asset of the pool's TerraSwap pair (e.g. UST) by swapping the
+ * redeemed backing asset, failing beyond `max_spread` or if less than
+ * `min_output` is received after the Terra tax
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveAs {
+    #[default]
+    Backing,
+    Stable {
+        min_output: Option<Uint128>,
+        max_spread: Option<Decimal>,
+    },
+}
+
+/**
+ * Bounds a user places on a mint, burn or withdrawal s/t a price move between
+ * signing and execution cannot leave them with less than they expected
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default, JsonSchema)]
+pub struct SlippageLimits {
+    /* Fewest tokens the user accepts receiving */
+    pub min_received: Option<Uint128>,
+
+    /* Highest leveraged price the user accepts being priced at */
+    pub max_leveraged_price: Option<Uint128>,
+
+    /* Latest block time in seconds since 1970-01-01T00:00:00Z to execute at */
+    pub deadline: Option<u64>,
 }
 
 pub struct TryMint {
     pub sender: Addr,
     pub amount: Uint128,
+    pub limits: SlippageLimits,
 }
 
 pub struct TryBurn {
     pub sender: Addr,
-    pub pool_share: Uint128,
+    pub amount: Uint128,
+    pub limits: SlippageLimits,
+    pub receive_as: ReceiveAs,
+}
+
+pub struct HyperparametersUpdate {
+    pub sender: Addr,
+    pub minimum_protocol_ratio: Option<Uint128>,
+    pub rebalance_ratio: Option<Uint128>,
+    pub mint_premium: Option<Uint128>,
+    pub rebalance_premium: Option<Uint128>,
+}
+
+/**
+ * Record of a single hyperparameter adjusted by governance
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct HyperparameterChange {
+    pub parameter: String,
+    pub previous: Uint128,
+    pub updated: Uint128,
+    pub timestamp: u64,
 }
 
 /**
@@ -95,6 +382,14 @@ pub struct ProvideLiquidityMsg {
     pub amount: Uint128,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct WithdrawLiquidityMsg {
+    pub sender: Addr,
+    pub share_of_pool: Uint128,
+    pub limits: SlippageLimits,
+    pub receive_as: ReceiveAs,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct ProviderPosition {
     pub asset_pool_partial_share: Uint128,
@@ -107,15 +402,108 @@ pub enum QueryMsg {
     Hyperparameters {},
     PoolState {},
     AllPoolInfo {},
-    PriceHistory {},
+
+    /**
+     * Page of price history between the exclusive `start_after` and
+     * `end_before` timestamps, at most `limit` snapshots in `order`
+     * (oldest first by default)
+     */
+    PriceHistory {
+        start_after: Option<u64>,
+        end_before: Option<u64>,
+        limit: Option<u32>,
+        order: Option<OrderBy>,
+    },
+
+    /**
+     * Page of adjustments governance made to the hyperparameters, numbered
+     * from 0 in the order they were made, between the exclusive
+     * `start_after` and `end_before` numbers, at most `limit` changes in
+     * `order` (oldest first by default)
+     */
+    HyperparameterChanges {
+        start_after: Option<u64>,
+        end_before: Option<u64>,
+        limit: Option<u32>,
+        order: Option<OrderBy>,
+    },
+
     ProtocolRatio {},
-    LiquidityPosition { address: Addr },
-    LeveragedPosition { address: Addr },
+    LiquidityPosition {
+        address: Addr,
+    },
+    LeveragedPosition {
+        address: Addr,
+    },
+    FeesCollected {},
+
+    /**
+     * What remains to be claimed from a liquidated pool or by knocked out
+     * positions
+     */
+    Settlement {},
+
+    Twap {
+        window_seconds: u64,
+    },
+
+    /**
+     * Page of at most `limit` OHLC candles at `resolution` opening within
+     * `range`, oldest first
+     */
+    Candles {
+        resolution: CandleResolution,
+        range: CandleRange,
+        limit: Option<u32>,
+    },
+
+    /**
+     * Preview minting with `amount` unleveraged assets
+     */
+    SimulateMint {
+        amount: Uint128,
+    },
+
+    /**
+     * Preview `address` burning `share` of its leveraged position tokens
+     */
+    SimulateBurn {
+        address: Addr,
+        share: Uint128,
+    },
+
+    /**
+     * Preview `address` withdrawing `share` of its liquidity tokens
+     */
+    SimulateWithdraw {
+        address: Addr,
+        share: Uint128,
+    },
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderBy {
+    Ascending,
+    Descending,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct PriceHistoryResponse {
     pub price_history: Vec<PriceSnapshot>,
+
+    /* Timestamp of the last snapshot returned while more remain, to be passed
+     * as `start_after` when ascending or `end_before` when descending */
+    pub next_cursor: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct HyperparameterChangesResponse {
+    pub changes: Vec<HyperparameterChange>,
+
+    /* Number of the last change returned while more remain, to be passed as
+     * `start_after` when ascending or `end_before` when descending */
+    pub next_cursor: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -133,6 +521,145 @@ pub struct ProtocolRatioResponse {
     pub pr: Uint128,
 }
 
+/**
+ * Time-weighted average of the price history and current price
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TwapResponse {
+    pub window_seconds: u64,
+    pub twap: PriceSnapshot,
+}
+
+/**
+ * Width of a candle
+ */
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, JsonSchema)]
+pub enum CandleResolution {
+    #[serde(rename = "15m")]
+    FifteenMinutes,
+    #[serde(rename = "1h")]
+    OneHour,
+    #[serde(rename = "1d")]
+    OneDay,
+}
+
+impl CandleResolution {
+    pub fn seconds(&self) -> u64 {
+        match self {
+            CandleResolution::FifteenMinutes => 15 * 60,
+            CandleResolution::OneHour => 60 * 60,
+            CandleResolution::OneDay => 24 * 60 * 60,
+        }
+    }
+}
+
+/**
+ * Candles opening at or after `start` and before `end`, both timestamps in
+ * seconds since 1970-01-01T00:00:00Z
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CandleRange {
+    pub start: Option<u64>,
+    pub end: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Ohlc {
+    pub open: Uint128,
+    pub high: Uint128,
+    pub low: Uint128,
+    pub close: Uint128,
+}
+
+impl Ohlc {
+    /* Candle which opened at `price` */
+    pub fn opened_at(price: Uint128) -> Self {
+        Ohlc {
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+        }
+    }
+
+    /* Record a later `price` into the candle */
+    pub fn record(&mut self, price: Uint128) {
+        self.high = self.high.max(price);
+        self.low = self.low.min(price);
+        self.close = price;
+    }
+}
+
+/**
+ * Prices recorded into history over one candle
+ */
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Candle {
+    /* Start of the candle in seconds since 1970-01-01T00:00:00Z */
+    pub open_time: u64,
+
+    /* Price of the unleveraged asset */
+    pub asset: Ohlc,
+
+    /* Derived price of the leveraged asset */
+    pub leveraged: Ohlc,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CandlesResponse {
+    pub resolution: CandleResolution,
+    pub candles: Vec<Candle>,
+
+    /* Open time of the first candle left out while more remain, to be passed
+     * as the `start` of the range for the following page */
+    pub next_cursor: Option<u64>,
+}
+
+/**
+ * Preview of a mint, burn or withdrawal as it would execute in this block
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SimulationResponse {
+    /* Position tokens minted, or backing assets paid out */
+    pub output_amount: Uint128,
+
+    /* Fees charged in the backing asset */
+    pub fee_amount: Uint128,
+
+    /* Protocol ratio after the trade, None once nothing is minted */
+    pub protocol_ratio: Option<Uint128>,
+
+    /* Whether the trade keeps the protocol ratio at or above the minimum */
+    pub passes_health_check: bool,
+}
+
+/**
+ * Lifetime totals of fees charged by the pool, in the backing asset
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FeesCollectedResponse {
+    /* Charged on mints and credited to liquidity providers */
+    pub mint_premium: Uint128,
+}
+
+/**
+ * Backing assets set aside when a pool was liquidated, less what has been
+ * claimed since
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SettlementResponse {
+    /* Prices the pool was liquidated at */
+    pub settled_at: PriceSnapshot,
+
+    /* Owed to the remaining leveraged pool share */
+    pub leveraged_assets: Uint128,
+    pub leveraged_pool_share: Uint128,
+
+    /* Owed to the remaining liquidity pool share */
+    pub liquidity_assets: Uint128,
+    pub asset_pool_share: Uint128,
+}
+
 /**
  * If no parameters were adjusted over the contract lifetime these are the
  * values the contract was initialized with
@@ -140,12 +667,64 @@ pub struct ProtocolRatioResponse {
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct HyperparametersResponse {
     pub leverage_amount: Uint128,
+    pub direction: LeverageDirection,
     pub minimum_protocol_ratio: Uint128,
     pub rebalance_ratio: Uint128,
     pub mint_premium: Uint128,
     pub rebalance_premium: Uint128,
     pub terraswap_pair_addr: String,
-    pub leveraged_asset_addr: String,
+    /* None for pools backed by a native coin */
+    pub leveraged_asset_addr: Option<String>,
+
+    /* Native coin backing the pool, if it is not backed by a Cw20 */
+    pub leveraged_asset_denom: Option<String>,
+
+    /* Cw20 representing leveraged positions, None until it is instantiated */
+    pub leveraged_token_addr: Option<String>,
+
+    /* Cw20 representing liquidity positions, None until it is instantiated */
+    pub liquidity_token_addr: Option<String>,
+
+    /* Address allowed to adjust hyperparameters */
+    pub governance_addr: String,
+
+    /* Address allowed to pause the pool alongside governance */
+    pub guardian_addr: Option<String>,
+
+    /* Where the asset price comes from */
+    pub price_source: PriceSourceConfig,
+
+    /* Seconds of price history averaged into the TWAP */
+    pub twap_window: u64,
+
+    /* Ratio of the reset leveraged price at which positions are knocked out */
+    pub knock_out_floor: Uint128,
+
+    /* Seconds between recorded price snapshots */
+    pub price_data_expiry: u64,
+
+    /* Seconds between leverage resets */
+    pub leverage_expiry: u64,
+
+    /* Seconds after 00:00 UTC at which leverage epochs start */
+    pub leverage_reset_offset: u64,
+
+    /* Number of price snapshots kept */
+    pub price_data_n: u64,
+
+    /* Backing assets paid to keepers per useful poke */
+    pub keeper_reward: Uint128,
+
+    /* Protocol ratio below which the pool may be liquidated */
+    pub liquidation_ratio: Uint128,
+
+    /* Asset price move between snapshots which pauses the pool, if any */
+    pub circuit_breaker: Option<Uint128>,
+
+    /* Caps in backing assets, if any */
+    pub max_total_leveraged_value: Option<Uint128>,
+    pub max_assets_in_reserve: Option<Uint128>,
+    pub max_position_per_address: Option<Uint128>,
 }
 
 /**
@@ -164,8 +743,8 @@ pub struct PoolStateResponse {
     pub assets_in_reserve: Uint128,
 
     /**
-     * Minted assets
-     * TODO remove in favor of total_leveraged_pool_share
+     * Leveraged assets backing all minted positions, which shrinks relative
+     * to total_leveraged_pool_share whenever the pool is rebalanced
      */
     pub total_leveraged_assets: Uint128,
 
@@ -180,6 +759,16 @@ pub struct PoolStateResponse {
      * Total share of all minted leveraged assets
      */
     pub total_leveraged_pool_share: Uint128,
+
+    /**
+     * Backing assets set aside to reward keepers
+     */
+    pub keeper_budget: Uint128,
+
+    /**
+     * What the pool currently accepts
+     */
+    pub status: PoolStatus,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -196,6 +785,23 @@ pub struct AllPoolInfoResponse {
     pub hyperparameters: HyperparametersResponse,
     pub pool_state: PoolStateResponse,
     pub price_context: PriceContext,
+    pub capacity: PoolCapacity,
+}
+
+/**
+ * Room left under a pool's caps at the current price, in backing assets. None
+ * where the pool is uncapped
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PoolCapacity {
+    /* Value which may still be minted */
+    pub remaining_leveraged_value: Option<Uint128>,
+
+    /* Assets which may still be deposited by minters and providers */
+    pub remaining_assets_in_reserve: Option<Uint128>,
+
+    /* Largest position value any one address may mint up to */
+    pub max_position_per_address: Option<Uint128>,
 }
 
 /**